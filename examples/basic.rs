@@ -1,9 +1,12 @@
 //! Basic example: connect with in-memory store and handle QR / Connected events.
 //!
-//! Run with: `cargo run --example basic`
+//! Run with: `cargo run --example basic --features full,qr`
+//!
+//! QR codes are drawn in the terminal; set `WA_QR_PNG=qr.png` to also write each code as a PNG.
 
+use futures::StreamExt;
 use std::sync::Arc;
-use whatsapp_pkg::{store::MemoryStore, Client};
+use whatsapp_pkg::{qr, store::MemoryStore, Client, Event};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -11,36 +14,59 @@ async fn main() -> anyhow::Result<()> {
 
     let store = Arc::new(MemoryStore::new());
     let client = Client::new(store);
+    let png_path = std::env::var("WA_QR_PNG").ok();
 
     client
-        .add_event_handler(|evt| match evt {
-            whatsapp_pkg::Event::Qr { codes } => {
+        .add_event_handler(move |evt| match evt {
+            Event::QrCode { code, timeout } => {
+                match qr::render_terminal(&code) {
+                    Ok(art) => println!("{}", art),
+                    Err(e) => println!("[Event] QR code (render failed: {}): {}", e, code),
+                }
                 println!(
-                    "[Event] QR codes (scan with WhatsApp Linked Devices): {:?}",
-                    codes
+                    "[Event] Scan with WhatsApp > Linked Devices (valid for {}s)",
+                    timeout.as_secs()
                 );
+                if let Some(path) = &png_path {
+                    let written = qr::render_png(&code, 8)
+                        .map_err(anyhow::Error::from)
+                        .and_then(|png| std::fs::write(path, png).map_err(anyhow::Error::from));
+                    if let Err(e) = written {
+                        println!("[Event] Could not write {}: {}", path, e);
+                    }
+                }
+            }
+            Event::QrTimeout => {
+                println!("[Event] QR codes expired.");
             }
-            whatsapp_pkg::Event::Connected => {
+            Event::Connected => {
                 println!("[Event] Connected and logged in.");
             }
-            whatsapp_pkg::Event::PairSuccess { id, platform, .. } => {
+            Event::PairSuccess { id, platform, .. } => {
                 println!("[Event] Pair success: {} on {}", id, platform);
             }
-            whatsapp_pkg::Event::Disconnected { reason } => {
+            Event::Disconnected { reason } => {
                 println!("[Event] Disconnected: {}", reason);
             }
             _ => {}
         })
         .await;
 
+    let mut events = client.events();
     println!("Connecting...");
     client.connect().await?;
 
     if client.is_logged_in() {
         println!("Already logged in.");
-    } else {
-        println!("No session: show the QR from the event above to link this device.");
+        return Ok(());
     }
 
+    // Keep running until the device is linked or the QR codes run out.
+    while let Some(evt) = events.next().await {
+        match evt {
+            Event::Connected | Event::QrTimeout => break,
+            _ => {}
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,407 @@
+//! Client configuration and builder.
+
+use super::{Client, DEFAULT_WS_URL};
+use crate::events::DEFAULT_EVENT_BUFFER;
+use crate::store::Store;
+use crate::transport::TransportFactory;
+use crate::types::Jid;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub use crate::proto::wa_companion_reg::device_props::PlatformType;
+pub use crate::proto::wa_wa6::client_payload::user_agent::Platform as UserAgentPlatform;
+
+/// Version of WhatsApp Web that the client identifies as.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ClientVersion {
+    pub primary: u32,
+    pub secondary: u32,
+    pub tertiary: u32,
+}
+
+impl ClientVersion {
+    /// Version sent when none is configured.
+    pub const DEFAULT: Self = Self::new(2, 3000, 1_023_223_821);
+
+    pub const fn new(primary: u32, secondary: u32, tertiary: u32) -> Self {
+        Self {
+            primary,
+            secondary,
+            tertiary,
+        }
+    }
+}
+
+impl Default for ClientVersion {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl std::fmt::Display for ClientVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.primary, self.secondary, self.tertiary)
+    }
+}
+
+/// User agent sent to the server in the client payload on every connection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UserAgent {
+    pub platform: UserAgentPlatform,
+    pub version: ClientVersion,
+    pub os_version: String,
+    pub os_build_number: String,
+    pub manufacturer: String,
+    pub device: String,
+    /// Mobile country/network codes; "000" for companion devices.
+    pub mcc: String,
+    pub mnc: String,
+    /// ISO 639-1 language code, e.g. "en".
+    pub locale_language: String,
+    /// ISO 3166-1 alpha-2 country code, e.g. "US".
+    pub locale_country: String,
+}
+
+impl Default for UserAgent {
+    fn default() -> Self {
+        Self {
+            platform: UserAgentPlatform::Web,
+            version: ClientVersion::DEFAULT,
+            os_version: "0.1.0".into(),
+            os_build_number: "0.1.0".into(),
+            manufacturer: String::new(),
+            device: "Desktop".into(),
+            mcc: "000".into(),
+            mnc: "000".into(),
+            locale_language: "en".into(),
+            locale_country: "US".into(),
+        }
+    }
+}
+
+/// History sync limits requested from the phone when pairing.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HistorySyncConfig {
+    pub full_sync_days_limit: Option<u32>,
+    pub full_sync_size_mb_limit: Option<u32>,
+    pub storage_quota_mb: Option<u32>,
+}
+
+/// Properties of this companion device, shown on the phone under "Linked devices".
+/// Only sent when pairing; changing them has no effect on an already linked device.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceProps {
+    /// Name shown for the device (the "browser" or OS name).
+    pub os: String,
+    /// Platform icon shown for the device.
+    pub platform_type: PlatformType,
+    /// Ask the phone for a full history sync instead of only recent chats.
+    pub require_full_sync: bool,
+    pub history_sync: Option<HistorySyncConfig>,
+}
+
+impl Default for DeviceProps {
+    fn default() -> Self {
+        Self {
+            os: "whatsapp-pkg".into(),
+            platform_type: PlatformType::Unknown,
+            require_full_sync: false,
+            history_sync: None,
+        }
+    }
+}
+
+/// Automatic reconnection after the connection drops unexpectedly.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    pub enabled: bool,
+    /// Give up after this many consecutive failed attempts (`None` = retry forever).
+    pub max_attempts: Option<u32>,
+    /// Delay before the first attempt; doubled after each failure up to `max_delay`.
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl ReconnectPolicy {
+    /// Never reconnect automatically.
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            ..Self::default()
+        }
+    }
+
+    /// Delay before reconnect attempt number `attempt` (starting at 1).
+    pub fn delay_for(&self, attempt: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.initial_delay
+            .checked_mul(factor)
+            .map_or(self.max_delay, |d| d.min(self.max_delay))
+    }
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_attempts: None,
+            initial_delay: Duration::from_secs(2),
+            max_delay: Duration::from_secs(120),
+        }
+    }
+}
+
+/// Called before accepting a pair-success with the phone's JID, platform and business name;
+/// returning `false` rejects the link.
+pub type PrePairCallback = Arc<dyn Fn(&Jid, &str, &str) -> bool + Send + Sync>;
+
+/// Client settings; build with [ClientBuilder], read back with [Client::config].
+#[derive(Clone)]
+pub struct ClientConfig {
+    pub ws_url: String,
+    /// HTTP proxy (`http://[user:pass@]host:port`) used for the WebSocket connection.
+    pub proxy: Option<String>,
+    pub user_agent: UserAgent,
+    pub device_props: DeviceProps,
+    /// Maximum time for opening the socket and for completing the handshake.
+    pub connect_timeout: Duration,
+    /// Maximum time to wait for the response to a request sent to the server.
+    pub request_timeout: Duration,
+    pub reconnect: ReconnectPolicy,
+    /// Events buffered per [EventStream](crate::events::EventStream) before it lags.
+    pub event_buffer: usize,
+    /// Opens connections; `None` when built without the `full` feature and no factory was set.
+    pub transport_factory: Option<Arc<dyn TransportFactory>>,
+    /// Decides whether to accept each pairing, see [ClientBuilder::pre_pair_callback].
+    pub pre_pair_callback: Option<PrePairCallback>,
+    /// Send a delivery receipt for every message received (default `true`).
+    pub automatic_receipts: bool,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            ws_url: DEFAULT_WS_URL.to_string(),
+            proxy: None,
+            user_agent: UserAgent::default(),
+            device_props: DeviceProps::default(),
+            connect_timeout: Duration::from_secs(20),
+            request_timeout: Duration::from_secs(75),
+            reconnect: ReconnectPolicy::default(),
+            event_buffer: DEFAULT_EVENT_BUFFER,
+            transport_factory: default_transport_factory(),
+            pre_pair_callback: None,
+            automatic_receipts: true,
+        }
+    }
+}
+
+impl std::fmt::Debug for ClientConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientConfig")
+            .field("ws_url", &self.ws_url)
+            .field("proxy", &self.proxy)
+            .field("user_agent", &self.user_agent)
+            .field("device_props", &self.device_props)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("request_timeout", &self.request_timeout)
+            .field("reconnect", &self.reconnect)
+            .field("event_buffer", &self.event_buffer)
+            .field("transport_factory", &self.transport_factory.is_some())
+            .field("pre_pair_callback", &self.pre_pair_callback.is_some())
+            .field("automatic_receipts", &self.automatic_receipts)
+            .finish()
+    }
+}
+
+#[cfg(feature = "full")]
+fn default_transport_factory() -> Option<Arc<dyn TransportFactory>> {
+    Some(Arc::new(crate::socket::NoiseTransportFactory))
+}
+
+#[cfg(not(feature = "full"))]
+fn default_transport_factory() -> Option<Arc<dyn TransportFactory>> {
+    None
+}
+
+/// Builder for [Client]. [Client::new] is shorthand for `ClientBuilder::new(store).build()`.
+///
+/// ```ignore
+/// let client = Client::builder(store)
+///     .os_name("My Bot")
+///     .platform_type(PlatformType::Desktop)
+///     .connect_timeout(Duration::from_secs(10))
+///     .build();
+/// ```
+pub struct ClientBuilder {
+    store: Store,
+    config: ClientConfig,
+}
+
+impl ClientBuilder {
+    pub fn new(store: Store) -> Self {
+        Self {
+            store,
+            config: ClientConfig::default(),
+        }
+    }
+
+    /// Use a custom transport (e.g. a different socket implementation or a test double).
+    pub fn transport_factory(mut self, factory: Arc<dyn TransportFactory>) -> Self {
+        self.config.transport_factory = Some(factory);
+        self
+    }
+
+    /// WebSocket URL to connect to (default [DEFAULT_WS_URL]).
+    pub fn ws_url(mut self, url: impl Into<String>) -> Self {
+        self.config.ws_url = url.into();
+        self
+    }
+
+    /// Tunnel the connection through an HTTP proxy (`http://[user:pass@]host:port`). Other schemes
+    /// fail to connect with [ConnectionError::InvalidProxy](crate::error::ConnectionError::InvalidProxy).
+    pub fn proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.config.proxy = Some(proxy.into());
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: UserAgent) -> Self {
+        self.config.user_agent = user_agent;
+        self
+    }
+
+    /// WhatsApp Web version to identify as.
+    pub fn version(mut self, version: ClientVersion) -> Self {
+        self.config.user_agent.version = version;
+        self
+    }
+
+    pub fn device_props(mut self, props: DeviceProps) -> Self {
+        self.config.device_props = props;
+        self
+    }
+
+    /// Device name shown on the phone (see [DeviceProps::os]).
+    pub fn os_name(mut self, os: impl Into<String>) -> Self {
+        self.config.device_props.os = os.into();
+        self
+    }
+
+    pub fn platform_type(mut self, platform_type: PlatformType) -> Self {
+        self.config.device_props.platform_type = platform_type;
+        self
+    }
+
+    pub fn require_full_sync(mut self, require: bool) -> Self {
+        self.config.device_props.require_full_sync = require;
+        self
+    }
+
+    pub fn history_sync(mut self, config: HistorySyncConfig) -> Self {
+        self.config.device_props.history_sync = Some(config);
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.config.connect_timeout = timeout;
+        self
+    }
+
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.config.request_timeout = timeout;
+        self
+    }
+
+    pub fn reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.config.reconnect = policy;
+        self
+    }
+
+    /// Events buffered per event stream (before a slow consumer lags) and per async event
+    /// handler (before events are dropped for it); default [DEFAULT_EVENT_BUFFER].
+    pub fn event_buffer(mut self, size: usize) -> Self {
+        self.config.event_buffer = size.max(1);
+        self
+    }
+
+    /// Whether to send delivery receipts for received messages automatically (default
+    /// `true`). Senders see messages as undelivered without them.
+    pub fn automatic_receipts(mut self, enabled: bool) -> Self {
+        self.config.automatic_receipts = enabled;
+        self
+    }
+
+    /// Inspect the phone linking this device before the pairing is accepted: the callback
+    /// gets its JID, platform and business name, and returning `false` rejects the link
+    /// (the client emits [Event::PairError](crate::Event::PairError) with
+    /// [PairingError::RejectedLocally](crate::error::PairingError::RejectedLocally)).
+    pub fn pre_pair_callback(
+        mut self,
+        callback: impl Fn(&Jid, &str, &str) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.config.pre_pair_callback = Some(Arc::new(callback));
+        self
+    }
+
+    pub fn build(self) -> Client {
+        Client::with_config(self.store, self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MemoryStore;
+
+    #[test]
+    fn builder_defaults_match_client_new() {
+        let client = Client::new(Arc::new(MemoryStore::new()));
+        let config = client.config();
+        assert_eq!(config.ws_url, DEFAULT_WS_URL);
+        assert_eq!(config.user_agent.version, ClientVersion::DEFAULT);
+        assert_eq!(config.device_props, DeviceProps::default());
+        assert!(config.proxy.is_none());
+        assert_eq!(config.transport_factory.is_some(), cfg!(feature = "full"));
+    }
+
+    #[test]
+    fn builder_overrides_config() {
+        let client = ClientBuilder::new(Arc::new(MemoryStore::new()))
+            .ws_url("wss://example.test/ws")
+            .proxy("http://127.0.0.1:3128")
+            .version(ClientVersion::new(2, 2400, 1))
+            .os_name("Ops Bot")
+            .platform_type(PlatformType::Desktop)
+            .require_full_sync(true)
+            .connect_timeout(Duration::from_secs(5))
+            .reconnect_policy(ReconnectPolicy::disabled())
+            .automatic_receipts(false)
+            .build();
+        let config = client.config();
+        assert_eq!(config.ws_url, "wss://example.test/ws");
+        assert_eq!(config.proxy.as_deref(), Some("http://127.0.0.1:3128"));
+        assert_eq!(config.user_agent.version.to_string(), "2.2400.1");
+        assert_eq!(config.device_props.os, "Ops Bot");
+        assert_eq!(config.device_props.platform_type, PlatformType::Desktop);
+        assert!(config.device_props.require_full_sync);
+        assert_eq!(config.connect_timeout, Duration::from_secs(5));
+        assert!(!config.reconnect.enabled);
+        assert!(!config.automatic_receipts);
+    }
+
+    #[test]
+    fn reconnect_delay_backs_off_to_max() {
+        let policy = ReconnectPolicy {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(10),
+            ..ReconnectPolicy::default()
+        };
+        assert_eq!(policy.delay_for(1), Duration::from_secs(1));
+        assert_eq!(policy.delay_for(2), Duration::from_secs(2));
+        assert_eq!(policy.delay_for(4), Duration::from_secs(8));
+        assert_eq!(policy.delay_for(5), Duration::from_secs(10));
+        assert_eq!(policy.delay_for(100), Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,722 @@
+//! Connection lifecycle: connect, authentication, receive loop, stream errors and reconnects.
+
+use super::Client;
+use crate::binary::Node;
+use crate::error::{ConnectFailureReason, ConnectionError, Error};
+use crate::events::{ConnectionState, Event, TempBanReason};
+use crate::transport::{DialOptions, FrameReceiver, HandshakeParams};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Outcome of the authentication phase, reported by the receive loop to `connect`.
+pub(super) type AuthResult = crate::Result<()>;
+
+impl Client {
+    /// Current state of the connection.
+    pub fn connection_state(&self) -> ConnectionState {
+        *self.state.lock().unwrap()
+    }
+
+    /// Connect to WhatsApp servers. If no session, registers as a new device and emits QR
+    /// events for pairing ([Event::Qr], then [Event::QrCode] as codes rotate).
+    ///
+    /// Returns once the server has accepted the login (or sent the pairing refs, in which
+    /// case the state stays [ConnectionState::Authenticating] until paired), or with the error that stopped the
+    /// connection attempt (socket, handshake, timeout or a `<failure>` from the server); the
+    /// client is left [ConnectionState::Disconnected] on error. State transitions are emitted
+    /// as [Event::ConnectionStateChanged].
+    pub async fn connect(&self) -> crate::Result<()> {
+        if self.connection_state() != ConnectionState::Disconnected {
+            return Err(ConnectionError::AlreadyConnected.into());
+        }
+        self.load_device().await?;
+        let device = self.prepare_device().await?;
+        let factory = self
+            .config
+            .transport_factory
+            .clone()
+            .ok_or(ConnectionError::NoTransport)?;
+        if !self
+            .transition(ConnectionState::Disconnected, ConnectionState::Connecting)
+            .await
+        {
+            return Err(ConnectionError::AlreadyConnected.into());
+        }
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+
+        let result = async {
+            let timeout = self.config.connect_timeout;
+            let opts = DialOptions {
+                url: self.config.ws_url.clone(),
+                proxy: self.config.proxy.clone(),
+            };
+            let pending = with_timeout(timeout, factory.connect(&opts)).await?;
+
+            self.set_state(ConnectionState::Handshaking).await;
+            let payload = match &device.id {
+                Some(jid) => self.config.login_payload(jid),
+                None => self.config.registration_payload(&device),
+            };
+            let params = HandshakeParams {
+                noise_key: device.noise_key_priv,
+                client_payload: prost::Message::encode_to_vec(&payload),
+            };
+            let (transport, receiver) = with_timeout(timeout, pending.handshake(&params)).await?;
+
+            let (auth_tx, auth_rx) = oneshot::channel();
+            *self.auth_tx.lock().unwrap() = Some(auth_tx);
+            *self.transport.write().await = Some(transport);
+            self.set_state(ConnectionState::Authenticating).await;
+            let task = tokio::spawn(self.clone().recv_loop(receiver, generation));
+            *self.recv_task.lock().unwrap() = Some(task);
+
+            with_timeout(timeout, async {
+                auth_rx
+                    .await
+                    .unwrap_or(Err(ConnectionError::Disconnected.into()))
+            })
+            .await
+        }
+        .await;
+
+        if result.is_err() && self.generation.load(Ordering::SeqCst) == generation {
+            self.close_connection(true).await;
+        }
+        result
+    }
+
+    /// Disconnect and optionally clear session. Clears the transport when present.
+    /// Also cancels any pending automatic reconnect. With `logout` the local session is
+    /// deleted (emitting [Event::LoggedOut]) without telling the server; [Client::logout]
+    /// also unlinks the device on the phone.
+    pub async fn disconnect(&self, logout: bool) -> crate::Result<()> {
+        self.reconnect_epoch.fetch_add(1, Ordering::SeqCst);
+        self.close_connection(true).await;
+        if logout {
+            let was_logged_in = self.is_logged_in();
+            self.clear_session().await?;
+            if was_logged_in {
+                self.dispatch_event(Event::LoggedOut {
+                    on_connect: false,
+                    reason: None,
+                })
+                .await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Tear down the current connection (if any) and move to Disconnected.
+    /// `abort_recv` must be false when called from the receive loop itself.
+    pub(super) async fn close_connection(&self, abort_recv: bool) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.stop_qr_rotation();
+        self.clear_response_waiters();
+        let task = self.recv_task.lock().unwrap().take();
+        if let (true, Some(task)) = (abort_recv, task) {
+            task.abort();
+        }
+        if let Some(tx) = self.auth_tx.lock().unwrap().take() {
+            let _ = tx.send(Err(ConnectionError::Disconnected.into()));
+        }
+        if let Some(transport) = self.transport.write().await.take() {
+            if let Err(e) = transport.close().await {
+                tracing::debug!(error = %e, "error closing transport");
+            }
+        }
+        self.set_state(ConnectionState::Disconnected).await;
+    }
+
+    /// Delete the stored device and forget the session.
+    pub(super) async fn clear_session(&self) -> crate::Result<()> {
+        let device = self.device.write().await.take();
+        self.logged_in.store(false, Ordering::SeqCst);
+        if let Some(jid) = device.and_then(|d| d.id) {
+            self.store.delete(&jid).await?;
+        }
+        Ok(())
+    }
+
+    pub(super) async fn set_state(&self, state: ConnectionState) {
+        let changed = {
+            let mut current = self.state.lock().unwrap();
+            std::mem::replace(&mut *current, state) != state
+        };
+        if changed {
+            self.dispatch_event(Event::ConnectionStateChanged { state })
+                .await;
+        }
+    }
+
+    /// Move from `from` to `to` atomically; false if the state was not `from`.
+    async fn transition(&self, from: ConnectionState, to: ConnectionState) -> bool {
+        {
+            let mut current = self.state.lock().unwrap();
+            if *current != from {
+                return false;
+            }
+            *current = to;
+        }
+        self.dispatch_event(Event::ConnectionStateChanged { state: to })
+            .await;
+        true
+    }
+
+    fn is_current(&self, generation: u64) -> bool {
+        self.generation.load(Ordering::SeqCst) == generation
+    }
+
+    /// Report the outcome of authentication to the pending `connect` call.
+    pub(super) fn finish_auth(&self, result: AuthResult) {
+        if let Some(tx) = self.auth_tx.lock().unwrap().take() {
+            let _ = tx.send(result);
+        }
+    }
+
+    async fn recv_loop(self, receiver: Box<dyn FrameReceiver>, generation: u64) {
+        let err = loop {
+            let frame = match receiver.next_frame().await {
+                Ok(frame) => frame,
+                Err(e) => break e,
+            };
+            if !self.is_current(generation) {
+                return;
+            }
+            match Node::decode(&frame) {
+                Ok(node) => {
+                    tracing::debug!(tag = %node.tag, "incoming node");
+                    self.handle_node(node).await;
+                }
+                Err(e) => tracing::warn!(error = %e, "failed to decode incoming frame"),
+            }
+            if !self.is_current(generation) {
+                return;
+            }
+        };
+        if self.is_current(generation) {
+            let was_connected = self.connection_state() == ConnectionState::Connected;
+            self.close_connection(false).await;
+            if was_connected {
+                self.dispatch_event(Event::Disconnected {
+                    reason: err.to_string(),
+                })
+                .await;
+                self.spawn_reconnect(false);
+            }
+        }
+    }
+
+    /// Route an incoming node to its handler.
+    pub(super) async fn handle_node(&self, node: Node) {
+        let Some(node) = self.receive_response(node) else {
+            return;
+        };
+        match node.tag.as_str() {
+            "success" => self.handle_success().await,
+            "failure" => self.handle_connect_failure(&node).await,
+            "stream:error" => self.handle_stream_error(&node).await,
+            "iq" => match node.get_children().first() {
+                Some(pair) if pair.tag == "pair-device" => {
+                    self.handle_pair_device(&node, pair).await
+                }
+                Some(success) if success.tag == "pair-success" => {
+                    self.handle_pair_success(&node, success)
+                }
+                _ => tracing::debug!(id = ?node.attrs.get("id"), "unhandled iq"),
+            },
+            "message" => self.handle_encrypted_message(&node).await,
+            "receipt" => self.handle_receipt(&node).await,
+            "notification" => self.handle_notification(&node).await,
+            "chatstate" => self.handle_chat_state(&node).await,
+            "presence" => self.handle_presence(&node).await,
+            "call" => tracing::debug!(from = ?node.attrs.get("from"), "unhandled call"),
+            "ib" => self.handle_ib(&node).await,
+            _ => tracing::debug!(tag = %node.tag, "unhandled node"),
+        }
+        // The server redelivers these until they're acked, so ack only once handled.
+        if matches!(
+            node.tag.as_str(),
+            "message" | "receipt" | "notification" | "call"
+        ) {
+            if let Err(e) = self.send_ack(&node).await {
+                tracing::warn!(tag = %node.tag, id = ?node.attrs.get("id"), error = %e, "failed to send ack");
+            }
+        }
+    }
+
+    /// Acknowledge a stanza so the server stops delivering it again.
+    pub(super) async fn send_ack(&self, node: &Node) -> crate::Result<()> {
+        let mut ack = Node::new("ack").with_attr("class", node.tag.as_str());
+        if let Some(from) = node.attrs.get("from") {
+            ack = ack.with_attr("to", from.as_str());
+        }
+        for key in ["id", "participant", "recipient"] {
+            if let Some(value) = node.attrs.get(key) {
+                ack = ack.with_attr(key, value.as_str());
+            }
+        }
+        // A message's type is implied by its class.
+        if let Some(kind) = node.attrs.get("type").filter(|_| node.tag != "message") {
+            ack = ack.with_attr("type", kind.as_str());
+        }
+        self.send_node(&ack).await
+    }
+
+    async fn handle_success(&self) {
+        self.logged_in.store(true, Ordering::SeqCst);
+        self.set_state(ConnectionState::Connected).await;
+        self.dispatch_event(Event::Connected).await;
+        self.finish_auth(Ok(()));
+        if self.has_prekey_keys().await {
+            self.spawn_ensure_prekeys();
+        }
+    }
+
+    async fn handle_connect_failure(&self, node: &Node) {
+        let code = attr_i32(node, "reason").unwrap_or(0);
+        let reason = ConnectFailureReason::from_code(code);
+        tracing::warn!(%reason, "server rejected connection");
+        self.finish_auth(Err(ConnectionError::ConnectFailure(reason).into()));
+        self.close_connection(false).await;
+        if reason.is_logged_out() {
+            if let Err(e) = self.clear_session().await {
+                tracing::error!(error = %e, "failed to delete session after logout");
+            }
+            self.dispatch_event(Event::LoggedOut {
+                on_connect: true,
+                reason: Some(reason),
+            })
+            .await;
+        } else if reason == ConnectFailureReason::TempBanned {
+            let code = TempBanReason::from_code(attr_i32(node, "code").unwrap_or(0));
+            let expire = Duration::from_secs(attr_i32(node, "expire").unwrap_or(0).max(0) as u64);
+            self.dispatch_event(Event::TemporaryBan { code, expire })
+                .await;
+        }
+    }
+
+    /// `<ib>`: informational nodes from the server.
+    async fn handle_ib(&self, node: &Node) {
+        for child in node.get_children() {
+            match child.tag.as_str() {
+                // The phone scanned our QR code but has multidevice disabled; the same code
+                // can be scanned again once it is enabled.
+                "downgrade_webclient" => {
+                    self.dispatch_event(Event::QrScannedWithoutMultidevice)
+                        .await
+                }
+                tag => tracing::debug!(tag, "unhandled ib child"),
+            }
+        }
+    }
+
+    async fn handle_stream_error(&self, node: &Node) {
+        let code = node.attrs.get("code").map(String::as_str).unwrap_or("");
+        let conflict = node
+            .get_child_by_tag("conflict")
+            .and_then(|c| c.attrs.get("type"))
+            .map(String::as_str);
+        tracing::warn!(code, ?conflict, "stream error");
+        let logged_out = code == "401" || conflict == Some("device_removed");
+        if logged_out {
+            // Before closing, so requests failing with the connection (such as a pending
+            // logout) already see that the session is gone.
+            self.logged_in.store(false, Ordering::SeqCst);
+        }
+        self.close_connection(false).await;
+        match (code, conflict) {
+            ("515", _) => {
+                // Restart required (e.g. right after pairing): reconnect straight away.
+                self.spawn_reconnect(true);
+            }
+            _ if logged_out => {
+                if let Err(e) = self.clear_session().await {
+                    tracing::error!(error = %e, "failed to delete session after logout");
+                }
+                self.dispatch_event(Event::LoggedOut {
+                    on_connect: false,
+                    reason: Some(ConnectFailureReason::LoggedOut),
+                })
+                .await;
+            }
+            (_, Some("replaced")) => self.dispatch_event(Event::StreamReplaced).await,
+            _ => {
+                self.dispatch_event(Event::Disconnected {
+                    reason: format!("stream error {}", code),
+                })
+                .await;
+                self.spawn_reconnect(false);
+            }
+        }
+    }
+
+    /// Reconnect in the background according to the reconnect policy. `immediate` skips the
+    /// first delay and ignores `enabled` (used when the server asks for a restart).
+    /// Stops when the session is logged out, someone else connects or calls `disconnect`.
+    fn spawn_reconnect(&self, immediate: bool) {
+        let policy = self.config.reconnect.clone();
+        if !policy.enabled && !immediate {
+            return;
+        }
+        let client = self.clone();
+        let epoch = self.reconnect_epoch.load(Ordering::SeqCst);
+        tokio::spawn(async move {
+            let mut attempt = 0u32;
+            loop {
+                attempt += 1;
+                if policy.max_attempts.is_some_and(|max| attempt > max) {
+                    tracing::warn!(attempts = attempt - 1, "giving up reconnecting");
+                    return;
+                }
+                if !(immediate && attempt == 1) {
+                    tokio::time::sleep(policy.delay_for(attempt)).await;
+                }
+                if client.reconnect_epoch.load(Ordering::SeqCst) != epoch
+                    || client.connection_state() != ConnectionState::Disconnected
+                    || !client.is_logged_in()
+                {
+                    return;
+                }
+                match client.connect().await {
+                    Ok(()) => return,
+                    Err(Error::Connection(ConnectionError::ConnectFailure(reason)))
+                        if reason.is_logged_out() =>
+                    {
+                        return
+                    }
+                    Err(Error::Connection(ConnectionError::AlreadyConnected)) => return,
+                    Err(e) => tracing::warn!(attempt, error = %e, "reconnect failed"),
+                }
+                if !policy.enabled {
+                    return;
+                }
+            }
+        });
+    }
+}
+
+fn attr_i32(node: &Node, key: &str) -> Option<i32> {
+    node.attrs.get(key).and_then(|v| v.parse().ok())
+}
+
+async fn with_timeout<T>(
+    timeout: Duration,
+    fut: impl std::future::Future<Output = crate::Result<T>>,
+) -> crate::Result<T> {
+    tokio::time::timeout(timeout, fut)
+        .await
+        .map_err(|_| Error::Connection(ConnectionError::Timeout))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::{paired_device, store_with, HandshakeBehavior, MockServer};
+    use crate::client::ReconnectPolicy;
+    use crate::store::{DeviceStore, Store};
+    use futures::StreamExt;
+
+    fn client_with(store: Store, server: &MockServer) -> Client {
+        Client::builder(store)
+            .transport_factory(server.factory())
+            .connect_timeout(Duration::from_millis(200))
+            .reconnect_policy(ReconnectPolicy::disabled())
+            .build()
+    }
+
+    fn states(events: &[Event]) -> Vec<ConnectionState> {
+        events
+            .iter()
+            .filter_map(|e| match e {
+                Event::ConnectionStateChanged { state } => Some(*state),
+                _ => None,
+            })
+            .collect()
+    }
+
+    async fn drain(stream: &mut crate::events::EventStream) -> Vec<Event> {
+        let mut out = Vec::new();
+        while let Ok(Some(evt)) =
+            tokio::time::timeout(Duration::from_millis(50), stream.next()).await
+        {
+            out.push(evt);
+        }
+        out
+    }
+
+    #[tokio::test]
+    async fn connect_walks_through_states() {
+        let server = MockServer::accepting();
+        let client = client_with(store_with(paired_device()).await, &server);
+        let mut events = client.events();
+        client.connect().await.unwrap();
+        assert_eq!(client.connection_state(), ConnectionState::Connected);
+        let events = drain(&mut events).await;
+        assert_eq!(
+            states(&events),
+            vec![
+                ConnectionState::Connecting,
+                ConnectionState::Handshaking,
+                ConnectionState::Authenticating,
+                ConnectionState::Connected,
+            ]
+        );
+        assert!(events.iter().any(|e| matches!(e, Event::Connected)));
+    }
+
+    #[tokio::test]
+    async fn connect_without_transport_fails() {
+        let client = Client::builder(store_with(paired_device()).await).build();
+        if client.config().transport_factory.is_some() {
+            return;
+        }
+        let err = client.connect().await.unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Connection(ConnectionError::NoTransport)
+        ));
+        assert_eq!(client.connection_state(), ConnectionState::Disconnected);
+    }
+
+    #[tokio::test]
+    async fn dial_failure_is_returned() {
+        let server = MockServer::accepting();
+        server.fail_dial("connection refused");
+        let client = client_with(store_with(paired_device()).await, &server);
+        let err = client.connect().await.unwrap_err();
+        assert!(
+            matches!(err, Error::Connection(ConnectionError::WebSocket(ref m)) if m == "connection refused")
+        );
+        assert_eq!(client.connection_state(), ConnectionState::Disconnected);
+        assert!(!client.is_connected());
+    }
+
+    #[tokio::test]
+    async fn handshake_failure_is_returned() {
+        let server = MockServer::accepting();
+        server.set_handshake(HandshakeBehavior::Fail);
+        let client = client_with(store_with(paired_device()).await, &server);
+        let mut events = client.events();
+        let err = client.connect().await.unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Connection(ConnectionError::HandshakeFailed(_))
+        ));
+        assert_eq!(
+            states(&drain(&mut events).await),
+            vec![
+                ConnectionState::Connecting,
+                ConnectionState::Handshaking,
+                ConnectionState::Disconnected,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn stalled_handshake_times_out() {
+        let server = MockServer::accepting();
+        server.set_handshake(HandshakeBehavior::Stall);
+        let client = client_with(store_with(paired_device()).await, &server);
+        let err = client.connect().await.unwrap_err();
+        assert!(matches!(err, Error::Connection(ConnectionError::Timeout)));
+        assert_eq!(client.connection_state(), ConnectionState::Disconnected);
+    }
+
+    #[tokio::test]
+    async fn missing_login_response_times_out() {
+        let server = MockServer::new();
+        let client = client_with(store_with(paired_device()).await, &server);
+        let err = client.connect().await.unwrap_err();
+        assert!(matches!(err, Error::Connection(ConnectionError::Timeout)));
+        assert_eq!(client.connection_state(), ConnectionState::Disconnected);
+    }
+
+    #[tokio::test]
+    async fn connect_twice_is_rejected() {
+        let server = MockServer::accepting();
+        let client = client_with(store_with(paired_device()).await, &server);
+        client.connect().await.unwrap();
+        let err = client.connect().await.unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Connection(ConnectionError::AlreadyConnected)
+        ));
+        assert_eq!(server.dials().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn logged_out_failure_deletes_session() {
+        let server = MockServer::new();
+        server.greet_with(vec![Node::new("failure").with_attr("reason", "401")]);
+        let store = store_with(paired_device()).await;
+        let client = client_with(store.clone(), &server);
+        let mut events = client.events();
+        let err = client.connect().await.unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Connection(ConnectionError::ConnectFailure(
+                ConnectFailureReason::LoggedOut
+            ))
+        ));
+        assert!(!client.is_logged_in());
+        assert!(store.get_first_device().await.unwrap().is_none());
+        assert!(drain(&mut events).await.iter().any(|e| matches!(
+            e,
+            Event::LoggedOut {
+                on_connect: true,
+                reason: Some(ConnectFailureReason::LoggedOut)
+            }
+        )));
+    }
+
+    #[tokio::test]
+    async fn temp_ban_failure_emits_event() {
+        let server = MockServer::new();
+        server.greet_with(vec![Node::new("failure")
+            .with_attr("reason", "402")
+            .with_attr("code", "101")
+            .with_attr("expire", "3600")]);
+        let client = client_with(store_with(paired_device()).await, &server);
+        let mut events = client.events();
+        let err = client.connect().await.unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Connection(ConnectionError::ConnectFailure(
+                ConnectFailureReason::TempBanned
+            ))
+        ));
+        assert!(drain(&mut events).await.iter().any(|e| matches!(
+            e,
+            Event::TemporaryBan { code, expire }
+                if code.code() == 101 && *expire == Duration::from_secs(3600)
+        )));
+    }
+
+    #[tokio::test]
+    async fn dropped_connection_emits_disconnected_and_reconnects() {
+        let server = MockServer::accepting();
+        let client = Client::builder(store_with(paired_device()).await)
+            .transport_factory(server.factory())
+            .reconnect_policy(ReconnectPolicy {
+                initial_delay: Duration::from_millis(10),
+                ..ReconnectPolicy::default()
+            })
+            .build();
+        client.connect().await.unwrap();
+        let mut events = client.events();
+        server.drop_connection();
+        let events = drain(&mut events).await;
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, Event::Disconnected { .. })));
+        assert_eq!(
+            states(&events),
+            vec![
+                ConnectionState::Disconnected,
+                ConnectionState::Connecting,
+                ConnectionState::Handshaking,
+                ConnectionState::Authenticating,
+                ConnectionState::Connected,
+            ]
+        );
+        assert_eq!(server.dials().len(), 2);
+        assert!(client.is_connected());
+    }
+
+    #[tokio::test]
+    async fn disconnect_does_not_reconnect() {
+        let server = MockServer::accepting();
+        let client = Client::builder(store_with(paired_device()).await)
+            .transport_factory(server.factory())
+            .reconnect_policy(ReconnectPolicy {
+                initial_delay: Duration::from_millis(10),
+                ..ReconnectPolicy::default()
+            })
+            .build();
+        client.connect().await.unwrap();
+        client.disconnect(false).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(server.dials().len(), 1);
+        assert_eq!(client.connection_state(), ConnectionState::Disconnected);
+        assert!(client.is_logged_in());
+    }
+
+    #[tokio::test]
+    async fn stream_replaced_does_not_reconnect() {
+        let server = MockServer::accepting();
+        let client = Client::builder(store_with(paired_device()).await)
+            .transport_factory(server.factory())
+            .reconnect_policy(ReconnectPolicy {
+                initial_delay: Duration::from_millis(10),
+                ..ReconnectPolicy::default()
+            })
+            .build();
+        client.connect().await.unwrap();
+        let mut events = client.events();
+        server.push(
+            Node::new("stream:error")
+                .with_children(vec![Node::new("conflict").with_attr("type", "replaced")]),
+        );
+        let events = drain(&mut events).await;
+        assert!(events.iter().any(|e| matches!(e, Event::StreamReplaced)));
+        assert_eq!(server.dials().len(), 1);
+        assert_eq!(client.connection_state(), ConnectionState::Disconnected);
+    }
+
+    #[tokio::test]
+    async fn stream_error_515_reconnects_immediately() {
+        let server = MockServer::accepting();
+        let client = client_with(store_with(paired_device()).await, &server);
+        client.connect().await.unwrap();
+        server.push(Node::new("stream:error").with_attr("code", "515"));
+        drain(&mut client.events()).await;
+        assert_eq!(server.dials().len(), 2);
+        assert!(client.is_connected());
+    }
+
+    #[tokio::test]
+    async fn stanzas_are_acked_after_handling() {
+        let server = MockServer::accepting();
+        let client = client_with(store_with(paired_device()).await, &server);
+        client.connect().await.unwrap();
+        server.push(
+            Node::new("notification")
+                .with_attr("from", "s.whatsapp.net")
+                .with_attr("id", "n-1")
+                .with_attr("type", "devices")
+                .with_attr("t", "1700000000"),
+        );
+        let ack = server.next_sent().await;
+        assert_eq!(ack.tag, "ack");
+        assert_eq!(ack.attrs["class"], "notification");
+        assert_eq!(ack.attrs["id"], "n-1");
+        assert_eq!(ack.attrs["to"], "s.whatsapp.net");
+        assert_eq!(ack.attrs["type"], "devices");
+        assert!(!ack.attrs.contains_key("t"));
+
+        server.push(
+            Node::new("call")
+                .with_attr("from", "5522222:1@s.whatsapp.net")
+                .with_attr("id", "c-1")
+                .with_children(vec![Node::new("offer").with_attr("call-id", "ABC")]),
+        );
+        let ack = server.next_sent().await;
+        assert_eq!(ack.attrs["class"], "call");
+        assert_eq!(ack.attrs["id"], "c-1");
+        assert_eq!(ack.attrs["to"], "5522222:1@s.whatsapp.net");
+        assert!(!ack.attrs.contains_key("type"));
+
+        // Nothing else is acked.
+        server.push(Node::new("ib").with_children(vec![Node::new("dirty")]));
+        server.push(
+            Node::new("receipt")
+                .with_attr("from", "120363001@g.us")
+                .with_attr("participant", "5522222:1@s.whatsapp.net")
+                .with_attr("id", "3EB0AA"),
+        );
+        let ack = server.next_sent().await;
+        assert_eq!(ack.attrs["class"], "receipt");
+        assert_eq!(ack.attrs["participant"], "5522222:1@s.whatsapp.net");
+    }
+}
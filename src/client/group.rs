@@ -0,0 +1,28 @@
+//! Group queries (`w:g2`).
+
+use super::request::{InfoQuery, IqType};
+use super::Client;
+use crate::binary::Node;
+use crate::types::Jid;
+
+impl Client {
+    /// Current participants of a group (user JIDs).
+    pub(crate) async fn get_group_participants(&self, group: &Jid) -> crate::Result<Vec<Jid>> {
+        let response = self
+            .send_iq(InfoQuery {
+                namespace: "w:g2",
+                iq_type: IqType::Get,
+                to: group.clone(),
+                content: vec![Node::new("query").with_attr("request", "interactive")],
+            })
+            .await?;
+        Ok(response
+            .get_child_by_tag("group")
+            .map(Node::get_children)
+            .unwrap_or_default()
+            .iter()
+            .filter(|p| p.tag == "participant")
+            .filter_map(|p| p.attrs.get("jid")?.parse().ok())
+            .collect())
+    }
+}
@@ -0,0 +1,259 @@
+//! Event handler registration.
+//!
+//! Ordering guarantees:
+//! - Synchronous handlers run inline while an event is dispatched, in registration order.
+//!   Dispatch does not continue until every synchronous handler has returned, so they should
+//!   be quick and must not block.
+//! - Each asynchronous handler has its own queue and receives events in the order they were
+//!   emitted, one at a time: the future for the next event is only created after the previous
+//!   one has completed. Different async handlers run concurrently and are not ordered relative
+//!   to each other or to synchronous handlers. The queue holds up to
+//!   [ClientBuilder::event_buffer](super::ClientBuilder::event_buffer) events; while it is
+//!   full, new events are dropped for that handler (with a warning) instead of piling up.
+//! - After [Client::remove_event_handler] returns, the handler receives no further events.
+//!   An async handler finishes the events already queued for it before it is dropped.
+
+use super::Client;
+use crate::events::Event;
+use std::future::Future;
+use std::sync::atomic::Ordering;
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+/// Identifier of a registered event handler, used to remove it again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EventHandlerId(u64);
+
+/// Synchronous handler callback; boxed so the client field stays Send + Sync.
+type SyncHandler = Box<dyn Fn(Event) + Send + Sync>;
+
+pub(super) enum EventHandler {
+    Sync(SyncHandler),
+    /// Queue feeding the task that drives an async handler.
+    Async(mpsc::Sender<Event>),
+}
+
+impl EventHandler {
+    pub(super) fn call(&self, evt: &Event) {
+        match self {
+            Self::Sync(f) => f(evt.clone()),
+            Self::Async(tx) => match tx.try_send(evt.clone()) {
+                Err(TrySendError::Full(_)) => {
+                    tracing::warn!("async event handler queue full, dropping event");
+                }
+                // The task only stops once this sender is dropped.
+                Err(TrySendError::Closed(_)) | Ok(()) => {}
+            },
+        }
+    }
+}
+
+impl Client {
+    /// Add an event handler (called for every event). Mirrors AddEventHandler.
+    /// Returns an ID that can be passed to [Client::remove_event_handler].
+    pub async fn add_event_handler<F>(&self, f: F) -> EventHandlerId
+    where
+        F: Fn(Event) + Send + Sync + 'static,
+    {
+        self.register_handler(EventHandler::Sync(Box::new(f))).await
+    }
+
+    /// Add an async event handler. The returned future is awaited before the handler receives
+    /// the next event, without blocking dispatch to other handlers, so it may call back into
+    /// the client (e.g. to send a reply).
+    pub async fn add_async_event_handler<F, Fut>(&self, f: F) -> EventHandlerId
+    where
+        F: Fn(Event) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let (tx, mut rx) = mpsc::channel::<Event>(self.config.event_buffer);
+        tokio::spawn(async move {
+            while let Some(evt) = rx.recv().await {
+                f(evt).await;
+            }
+        });
+        self.register_handler(EventHandler::Async(tx)).await
+    }
+
+    /// Remove a handler added with [Client::add_event_handler] or
+    /// [Client::add_async_event_handler]. Returns false if the ID was not registered.
+    pub async fn remove_event_handler(&self, id: EventHandlerId) -> bool {
+        let mut handlers = self.handlers.write().await;
+        match handlers.iter().position(|(hid, _)| *hid == id) {
+            Some(idx) => {
+                handlers.remove(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    async fn register_handler(&self, handler: EventHandler) -> EventHandlerId {
+        let id = EventHandlerId(self.next_handler_id.fetch_add(1, Ordering::Relaxed));
+        self.handlers.write().await.push((id, handler));
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MemoryStore;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    fn new_client() -> Client {
+        Client::new(Arc::new(MemoryStore::new()))
+    }
+
+    fn history_sync(chunk_order: u32) -> Event {
+        Event::HistorySync {
+            chunk_order,
+            progress: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn sync_handlers_run_in_registration_order() {
+        let client = new_client();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        for name in ["first", "second"] {
+            let seen = Arc::clone(&seen);
+            client
+                .add_event_handler(move |_| seen.lock().unwrap().push(name))
+                .await;
+        }
+        client.dispatch_event(Event::Connected).await;
+        assert_eq!(*seen.lock().unwrap(), vec!["first", "second"]);
+    }
+
+    #[tokio::test]
+    async fn removed_handler_is_not_called() {
+        let client = new_client();
+        let count = Arc::new(Mutex::new(0));
+        let count_clone = Arc::clone(&count);
+        let id = client
+            .add_event_handler(move |_| *count_clone.lock().unwrap() += 1)
+            .await;
+        client.dispatch_event(Event::Connected).await;
+        assert!(client.remove_event_handler(id).await);
+        assert!(!client.remove_event_handler(id).await);
+        client.dispatch_event(Event::Connected).await;
+        assert_eq!(*count.lock().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn async_handler_receives_events_in_order() {
+        let client = new_client();
+        let (done_tx, mut done_rx) = mpsc::unbounded_channel();
+        client
+            .add_async_event_handler(move |evt| {
+                let done_tx = done_tx.clone();
+                async move {
+                    if let Event::HistorySync { chunk_order, .. } = evt {
+                        // Earlier events sleep longer; order must still be preserved.
+                        let delay = 5u64.saturating_sub(chunk_order as u64);
+                        tokio::time::sleep(Duration::from_millis(delay)).await;
+                        done_tx.send(chunk_order).unwrap();
+                    }
+                }
+            })
+            .await;
+        for i in 0..5 {
+            client.dispatch_event(history_sync(i)).await;
+        }
+        let mut seen = Vec::new();
+        for _ in 0..5 {
+            seen.push(done_rx.recv().await.unwrap());
+        }
+        assert_eq!(seen, vec![0, 1, 2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn async_handler_does_not_block_dispatch() {
+        let client = new_client();
+        let (release_tx, release_rx) = tokio::sync::oneshot::channel::<()>();
+        let release_rx = Arc::new(tokio::sync::Mutex::new(Some(release_rx)));
+        client
+            .add_async_event_handler(move |_| {
+                let release_rx = Arc::clone(&release_rx);
+                async move {
+                    if let Some(rx) = release_rx.lock().await.take() {
+                        let _ = rx.await;
+                    }
+                }
+            })
+            .await;
+        let sync_seen = Arc::new(Mutex::new(0));
+        let sync_seen_clone = Arc::clone(&sync_seen);
+        client
+            .add_event_handler(move |_| *sync_seen_clone.lock().unwrap() += 1)
+            .await;
+        tokio::time::timeout(Duration::from_secs(1), async {
+            client.dispatch_event(Event::Connected).await;
+            client.dispatch_event(Event::Connected).await;
+        })
+        .await
+        .expect("dispatch blocked on async handler");
+        assert_eq!(*sync_seen.lock().unwrap(), 2);
+        release_tx.send(()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn removed_async_handler_stops_receiving() {
+        let client = new_client();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let id = client
+            .add_async_event_handler(move |evt| {
+                let tx = tx.clone();
+                async move {
+                    let _ = tx.send(evt);
+                }
+            })
+            .await;
+        client.dispatch_event(history_sync(1)).await;
+        assert!(client.remove_event_handler(id).await);
+        client.dispatch_event(history_sync(2)).await;
+        assert!(matches!(
+            rx.recv().await,
+            Some(Event::HistorySync { chunk_order: 1, .. })
+        ));
+        // The handler task ends once its queue is dropped, closing the channel.
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn full_async_handler_queue_drops_events() {
+        let client = Client::builder(Arc::new(MemoryStore::new()))
+            .event_buffer(2)
+            .build();
+        let (release_tx, release_rx) = tokio::sync::oneshot::channel::<()>();
+        let release_rx = Arc::new(tokio::sync::Mutex::new(Some(release_rx)));
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        client
+            .add_async_event_handler(move |evt| {
+                let release_rx = Arc::clone(&release_rx);
+                let tx = tx.clone();
+                async move {
+                    if let Some(rx) = release_rx.lock().await.take() {
+                        let _ = rx.await;
+                    }
+                    let _ = tx.send(evt);
+                }
+            })
+            .await;
+        // The first event is taken off the queue and blocks the handler.
+        client.dispatch_event(history_sync(0)).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        for i in 1..5 {
+            client.dispatch_event(history_sync(i)).await;
+        }
+        release_tx.send(()).unwrap();
+        let mut seen = Vec::new();
+        while let Ok(Some(Event::HistorySync { chunk_order, .. })) =
+            tokio::time::timeout(Duration::from_millis(100), rx.recv()).await
+        {
+            seen.push(chunk_order);
+        }
+        assert_eq!(seen, vec![0, 1, 2]);
+    }
+}
@@ -0,0 +1,281 @@
+//! In-memory transport for client tests: plays the server side of a connection.
+
+use crate::binary::Node;
+use crate::client::{Client, ClientBuilder};
+use crate::error::{ConnectionError, Error};
+use crate::proto::wa_e2e::Message;
+use crate::store::{Device, DeviceStore, MemoryStore, Store};
+use crate::transport::{
+    DialOptions, FrameReceiver, HandshakeParams, PendingConnection, Transport, TransportFactory,
+};
+use crate::types::Jid;
+use crate::Result;
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// How the mock server reacts to the handshake.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum HandshakeBehavior {
+    #[default]
+    Accept,
+    Fail,
+    /// Never complete (for timeout tests).
+    Stall,
+}
+
+#[derive(Default)]
+struct MockState {
+    dials: Vec<DialOptions>,
+    handshakes: Vec<HandshakeParams>,
+    dial_error: Option<String>,
+    handshake: HandshakeBehavior,
+    /// Nodes sent to the client right after every successful handshake.
+    greeting: Vec<Node>,
+    /// Server-to-client channel of the most recent connection.
+    to_client: Option<mpsc::UnboundedSender<Vec<u8>>>,
+}
+
+/// Transport factory whose connections are driven by the test.
+#[derive(Clone)]
+pub(crate) struct MockServer {
+    state: Arc<Mutex<MockState>>,
+    sent_tx: mpsc::UnboundedSender<Node>,
+    sent_rx: Arc<tokio::sync::Mutex<mpsc::UnboundedReceiver<Node>>>,
+}
+
+impl MockServer {
+    pub(crate) fn new() -> Self {
+        let (sent_tx, sent_rx) = mpsc::unbounded_channel();
+        Self {
+            state: Arc::new(Mutex::new(MockState::default())),
+            sent_tx,
+            sent_rx: Arc::new(tokio::sync::Mutex::new(sent_rx)),
+        }
+    }
+
+    /// Server that logs every connection in with a `<success>` node.
+    pub(crate) fn accepting() -> Self {
+        let server = Self::new();
+        server.greet_with(vec![Node::new("success")]);
+        server
+    }
+
+    /// Server that answers every connection with a `pair-device` IQ carrying `refs`.
+    pub(crate) fn pairing(refs: &[&str]) -> Self {
+        let server = Self::new();
+        let refs = refs
+            .iter()
+            .map(|r| Node::new("ref").with_content(r.as_bytes().to_vec()))
+            .collect();
+        server.greet_with(vec![Node::new("iq")
+            .with_attr("type", "set")
+            .with_attr("id", "pair-1")
+            .with_attr("from", "s.whatsapp.net")
+            .with_children(vec![Node::new("pair-device").with_children(refs)])]);
+        server
+    }
+
+    pub(crate) fn factory(&self) -> Arc<dyn TransportFactory> {
+        Arc::new(self.clone())
+    }
+
+    pub(crate) fn fail_dial(&self, error: &str) {
+        self.state.lock().unwrap().dial_error = Some(error.to_string());
+    }
+
+    pub(crate) fn set_handshake(&self, behavior: HandshakeBehavior) {
+        self.state.lock().unwrap().handshake = behavior;
+    }
+
+    /// Nodes to send as soon as a connection completes its handshake.
+    pub(crate) fn greet_with(&self, nodes: Vec<Node>) {
+        self.state.lock().unwrap().greeting = nodes;
+    }
+
+    pub(crate) fn dials(&self) -> Vec<DialOptions> {
+        self.state.lock().unwrap().dials.clone()
+    }
+
+    pub(crate) fn handshakes(&self) -> Vec<HandshakeParams> {
+        self.state.lock().unwrap().handshakes.clone()
+    }
+
+    /// Send a node to the client on the current connection.
+    pub(crate) fn push(&self, node: Node) {
+        let state = self.state.lock().unwrap();
+        let tx = state.to_client.as_ref().expect("no open connection");
+        tx.send(node.encode().unwrap())
+            .expect("client receiver dropped");
+    }
+
+    /// Close the current connection from the server side.
+    pub(crate) fn drop_connection(&self) {
+        self.state.lock().unwrap().to_client = None;
+    }
+
+    /// Next node the client sent, failing the test if none arrives within a second.
+    pub(crate) async fn next_sent(&self) -> Node {
+        let mut rx = self.sent_rx.lock().await;
+        tokio::time::timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .expect("timed out waiting for client to send a node")
+            .expect("mock server channel closed")
+    }
+
+    /// Like [MockServer::next_sent], skipping the `<ack>`s for stanzas the server pushed.
+    pub(crate) async fn next_sent_skipping_acks(&self) -> Node {
+        loop {
+            let node = self.next_sent().await;
+            if node.tag != "ack" {
+                return node;
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl TransportFactory for MockServer {
+    async fn connect(&self, opts: &DialOptions) -> Result<Box<dyn PendingConnection>> {
+        let mut state = self.state.lock().unwrap();
+        state.dials.push(opts.clone());
+        if let Some(err) = &state.dial_error {
+            return Err(Error::Connection(ConnectionError::WebSocket(err.clone())));
+        }
+        Ok(Box::new(MockPending {
+            server: self.clone(),
+        }))
+    }
+}
+
+struct MockPending {
+    server: MockServer,
+}
+
+#[async_trait]
+impl PendingConnection for MockPending {
+    async fn handshake(
+        self: Box<Self>,
+        params: &HandshakeParams,
+    ) -> Result<(Arc<dyn Transport>, Box<dyn FrameReceiver>)> {
+        let behavior = {
+            let mut state = self.server.state.lock().unwrap();
+            state.handshakes.push(params.clone());
+            state.handshake
+        };
+        match behavior {
+            HandshakeBehavior::Accept => {}
+            HandshakeBehavior::Fail => {
+                return Err(Error::Connection(ConnectionError::HandshakeFailed(
+                    "mock server rejected handshake".into(),
+                )))
+            }
+            HandshakeBehavior::Stall => futures::future::pending::<()>().await,
+        }
+        let (to_client, from_server) = mpsc::unbounded_channel();
+        {
+            let mut state = self.server.state.lock().unwrap();
+            for node in &state.greeting {
+                to_client.send(node.encode()?).unwrap();
+            }
+            state.to_client = Some(to_client);
+        }
+        Ok((
+            Arc::new(MockTransport {
+                sent_tx: self.server.sent_tx.clone(),
+            }),
+            Box::new(MockReceiver {
+                rx: tokio::sync::Mutex::new(from_server),
+            }),
+        ))
+    }
+}
+
+struct MockTransport {
+    sent_tx: mpsc::UnboundedSender<Node>,
+}
+
+#[async_trait]
+impl Transport for MockTransport {
+    async fn send(&self, data: &[u8]) -> Result<()> {
+        let node = Node::decode(data)?;
+        self.sent_tx
+            .send(node)
+            .map_err(|_| Error::Connection(ConnectionError::Disconnected))
+    }
+
+    async fn close(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+struct MockReceiver {
+    rx: tokio::sync::Mutex<mpsc::UnboundedReceiver<Vec<u8>>>,
+}
+
+#[async_trait]
+impl FrameReceiver for MockReceiver {
+    async fn next_frame(&self) -> Result<Vec<u8>> {
+        self.rx
+            .lock()
+            .await
+            .recv()
+            .await
+            .ok_or(Error::Connection(ConnectionError::Disconnected))
+    }
+}
+
+/// Parse a JID literal.
+pub(crate) fn jid(s: &str) -> Jid {
+    s.parse().unwrap()
+}
+
+/// Plain text message.
+pub(crate) fn text(body: &str) -> Message {
+    Message {
+        conversation: Some(body.into()),
+        ..Default::default()
+    }
+}
+
+/// Paired device `123:4@s.whatsapp.net`, without keys.
+pub(crate) fn paired_device() -> Device {
+    Device {
+        id: Some(jid("123:4@s.whatsapp.net")),
+        ..Default::default()
+    }
+}
+
+/// Memory store holding `device`.
+pub(crate) async fn store_with(device: Device) -> Arc<MemoryStore> {
+    let store = Arc::new(MemoryStore::new());
+    store.save(&device).await.unwrap();
+    store
+}
+
+/// Connect a client on `store` to a new accepting mock server; `configure` adjusts the builder.
+pub(crate) async fn connect_client(
+    store: Store,
+    configure: impl FnOnce(ClientBuilder) -> ClientBuilder,
+) -> (Client, MockServer) {
+    let server = MockServer::accepting();
+    let client = configure(Client::builder(store).transport_factory(server.factory())).build();
+    client.connect().await.unwrap();
+    (client, server)
+}
+
+/// Connected client for [paired_device].
+pub(crate) async fn connected_client() -> (Client, MockServer, Arc<MemoryStore>) {
+    connected_client_with(paired_device(), |builder| builder).await
+}
+
+/// Connected client for `device`; `configure` adjusts the builder.
+pub(crate) async fn connected_client_with(
+    device: Device,
+    configure: impl FnOnce(ClientBuilder) -> ClientBuilder,
+) -> (Client, MockServer, Arc<MemoryStore>) {
+    let store = store_with(device).await;
+    let (client, server) = connect_client(store.clone(), configure).await;
+    (client, server, store)
+}
@@ -1,74 +1,172 @@
 //! Main client.
 
+mod builder;
+mod connection;
+mod group;
+mod handler;
+#[cfg(test)]
+pub(crate) mod mock;
+mod notification;
+mod pair;
+mod pair_code;
+mod payload;
+mod prekeys;
+mod presence;
+mod receipt;
+mod receive;
+mod request;
+mod retry;
 mod send;
+mod usync;
 
 use crate::binary::Node;
-use crate::error::{ConnectionError, Error};
-use crate::events::Event;
+use crate::error::{ConnectionError, Error, SendError};
+use crate::events::{ConnectionState, Event, EventStream};
 use crate::store::{Device, Store};
 use crate::transport::Transport;
 use crate::types::{Jid, MessageId};
 use sha2::Digest;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
-
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, oneshot, RwLock};
+use tokio::task::JoinHandle;
+
+pub use builder::{
+    ClientBuilder, ClientConfig, ClientVersion, DeviceProps, HistorySyncConfig, PlatformType,
+    PrePairCallback, ReconnectPolicy, UserAgent, UserAgentPlatform,
+};
+pub use handler::EventHandlerId;
+pub use pair::{QR_FIRST_TIMEOUT, QR_NEXT_TIMEOUT};
+pub use pair_code::PairClientType;
 pub use send::{SendRequestExtra, SendResponse};
 
-/// Parameters for completing pairing after QR or pair-code flow.
+use handler::EventHandler;
+
+/// Contents of the server's `pair-success`, for [Client::complete_pairing].
 #[derive(Clone, Debug)]
 pub struct CompletePairingParams<'a> {
-    /// Raw device identity from server (payload, or payload || HMAC-SHA256 tag if verifying).
+    /// The `device-identity` child: an encoded ADVSignedDeviceIdentityHMAC.
     pub device_identity_bytes: &'a [u8],
-    /// Request ID from the pairing flow.
+    /// ID of the pair-success IQ, answered by `pair-device-sign` (or an error).
     pub req_id: &'a str,
     pub business_name: &'a str,
     pub platform: &'a str,
     pub jid: Jid,
     pub lid: Jid,
-    /// If set, device_identity_bytes is verified as payload || HMAC tag before use.
-    pub hmac_key: Option<&'a [u8]>,
 }
 
-/// Type alias for event handlers so the client field is not overly complex and is Send + Sync.
-type EventHandler = Box<dyn Fn(Event) + Send + Sync>;
-
 /// Default WebSocket URL for WhatsApp Web.
 pub const DEFAULT_WS_URL: &str = "wss://web.whatsapp.com/ws";
 
 /// Client for the WhatsApp web multidevice API.
+///
+/// Cloning is cheap and every clone shares the same connection, session and handlers.
+#[derive(Clone)]
 pub struct Client {
     store: Store,
+    config: Arc<ClientConfig>,
     device: Arc<RwLock<Option<Device>>>,
-    _event_tx: mpsc::UnboundedSender<Event>,
-    handlers: Arc<RwLock<Vec<EventHandler>>>,
-    connected: AtomicBool,
-    logged_in: AtomicBool,
-    /// When set, send_node() uses this transport (e.g. Noise over WebSocket). Set by connect() when feature "full" is enabled.
+    event_tx: broadcast::Sender<Event>,
+    handlers: Arc<RwLock<Vec<(EventHandlerId, EventHandler)>>>,
+    next_handler_id: Arc<AtomicU64>,
+    state: Arc<Mutex<ConnectionState>>,
+    logged_in: Arc<AtomicBool>,
+    /// When set, send_node() uses this transport (e.g. Noise over WebSocket). Set by connect() once the handshake succeeds.
     transport: Arc<RwLock<Option<Arc<dyn Transport>>>>,
+    /// Task reading frames from the current connection.
+    recv_task: Arc<Mutex<Option<JoinHandle<()>>>>,
+    /// Task rotating QR codes while waiting to be paired.
+    qr_task: Arc<Mutex<Option<JoinHandle<()>>>>,
+    /// Bumped whenever a connection is opened or torn down; stale receive loops compare against it.
+    generation: Arc<AtomicU64>,
+    /// Bumped by disconnect() to cancel pending automatic reconnects.
+    reconnect_epoch: Arc<AtomicU64>,
+    /// Completes the pending connect() when the server answers the login.
+    auth_tx: Arc<Mutex<Option<oneshot::Sender<connection::AuthResult>>>>,
+    /// Request ids are `<prefix>-<counter>`; the prefix is random per client.
+    request_id_prefix: Arc<str>,
+    next_request_id: Arc<AtomicU64>,
+    /// IQ requests waiting for their response, by id.
+    response_waiters: Arc<Mutex<HashMap<String, oneshot::Sender<Node>>>>,
+    /// State of an ongoing pair-code (phone number) link, see [Client::pair_phone].
+    phone_linking: Arc<Mutex<Option<pair_code::PhoneLinking>>>,
+    /// Devices known to have our sender key, by group.
+    sender_key_distributed: Arc<Mutex<HashMap<Jid, HashSet<Jid>>>>,
+    /// Held while uploading prekeys, so concurrent top-ups don't upload the same batch twice.
+    prekey_upload: Arc<tokio::sync::Mutex<()>>,
+    /// Held from loading a Signal session or sender key until its advanced state is stored,
+    /// so concurrent sends and receives don't overwrite each other's ratchet steps.
+    signal_lock: Arc<tokio::sync::Mutex<()>>,
+    /// Messages we sent recently, to encrypt them again for retry receipts.
+    recent_messages: Arc<Mutex<retry::RecentMessages>>,
+    /// Retry receipts sent per message we couldn't decrypt.
+    message_retries: Arc<Mutex<retry::MessageRetries>>,
+    /// Set while our presence is available: delivery receipts are then plain instead of
+    /// `inactive`.
+    send_active_receipts: Arc<AtomicBool>,
 }
 
 impl Client {
-    /// Create a new client with the given device store.
+    /// Create a new client with the given device store and default settings.
+    /// Use [Client::builder] to customize the connection and device properties.
     pub fn new(store: Store) -> Self {
-        let (event_tx, _event_rx) = mpsc::unbounded_channel();
+        ClientBuilder::new(store).build()
+    }
+
+    /// Start building a client with custom settings.
+    pub fn builder(store: Store) -> ClientBuilder {
+        ClientBuilder::new(store)
+    }
+
+    fn with_config(store: Store, config: ClientConfig) -> Self {
+        let (event_tx, _) = broadcast::channel(config.event_buffer);
         Self {
             store,
+            config: Arc::new(config),
             device: Arc::new(RwLock::new(None)),
-            _event_tx: event_tx,
+            event_tx,
             handlers: Arc::new(RwLock::new(Vec::new())),
-            connected: AtomicBool::new(false),
-            logged_in: AtomicBool::new(false),
+            next_handler_id: Arc::new(AtomicU64::new(1)),
+            state: Arc::new(Mutex::new(ConnectionState::Disconnected)),
+            logged_in: Arc::new(AtomicBool::new(false)),
             transport: Arc::new(RwLock::new(None)),
+            recv_task: Arc::new(Mutex::new(None)),
+            qr_task: Arc::new(Mutex::new(None)),
+            generation: Arc::new(AtomicU64::new(0)),
+            reconnect_epoch: Arc::new(AtomicU64::new(0)),
+            auth_tx: Arc::new(Mutex::new(None)),
+            request_id_prefix: format!("{}.{}", rand::random::<u16>(), rand::random::<u16>())
+                .into(),
+            next_request_id: Arc::new(AtomicU64::new(1)),
+            response_waiters: Arc::new(Mutex::new(HashMap::new())),
+            phone_linking: Arc::new(Mutex::new(None)),
+            sender_key_distributed: Arc::new(Mutex::new(HashMap::new())),
+            prekey_upload: Arc::new(tokio::sync::Mutex::new(())),
+            signal_lock: Arc::new(tokio::sync::Mutex::new(())),
+            recent_messages: Arc::new(Mutex::new(retry::RecentMessages::default())),
+            message_retries: Arc::new(Mutex::new(retry::MessageRetries::default())),
+            send_active_receipts: Arc::new(AtomicBool::new(false)),
         }
     }
 
-    /// Add an event handler (called for every event). Mirrors AddEventHandler.
-    pub async fn add_event_handler<F>(&self, f: F)
-    where
-        F: Fn(Event) + Send + Sync + 'static,
-    {
-        self.handlers.write().await.push(Box::new(f));
+    /// Settings the client was built with.
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    /// Subscribe to events as an async stream. Each call returns an independent stream that
+    /// sees every event emitted after it was created; callback handlers are unaffected.
+    ///
+    /// ```ignore
+    /// use futures::StreamExt;
+    /// let mut events = client.events();
+    /// while let Some(evt) = events.next().await {
+    ///     println!("{:?}", evt);
+    /// }
+    /// ```
+    pub fn events(&self) -> EventStream {
+        EventStream::new(self.event_tx.subscribe())
     }
 
     /// Load device from store and mark logged in if present.
@@ -83,68 +181,41 @@ impl Client {
         Ok(())
     }
 
-    /// Connect to WhatsApp servers. If no session, will emit QR events for pairing.
-    /// With feature "full", performs a real WebSocket + Noise handshake and stores the transport.
-    pub async fn connect(&self) -> crate::Result<()> {
-        self.load_device().await?;
-        let device = self.device.read().await.clone();
-        if device.as_ref().is_none_or(|d| !d.is_logged_in()) {
-            self.dispatch_event(Event::Qr {
-                codes: vec!["STUB_QR_CODE".to_string()],
+    /// Unlink this device: ask the server to remove it from the account, then disconnect,
+    /// delete the local session and emit [Event::LoggedOut]. Succeeds if the server already
+    /// considered us logged out. Use [Client::disconnect] with `logout = true` to only forget
+    /// the session locally.
+    pub async fn logout(&self) -> crate::Result<()> {
+        let own_id = self.get_own_id().await.ok_or(Error::NotLoggedIn)?;
+        if !self.is_connected() {
+            return Err(Error::NotConnected);
+        }
+        let removed = self
+            .send_iq(request::InfoQuery {
+                namespace: "md",
+                iq_type: request::IqType::Set,
+                to: Jid::default_server(),
+                content: vec![Node::new("remove-companion-device")
+                    .with_attr("jid", own_id.to_string())
+                    .with_attr("reason", "user_initiated")],
             })
             .await;
-            return Ok(());
-        }
-        #[cfg(feature = "full")]
-        {
-            if let Ok((noise_tx, noise_rx)) = crate::socket::connect_noise_default().await {
-                let transport: Arc<dyn Transport> = Arc::new(noise_tx);
-                *self.transport.write().await = Some(Arc::clone(&transport));
-                tokio::spawn(Self::recv_loop(noise_rx));
-            }
-        }
-        self.connected.store(true, Ordering::SeqCst);
-        self.logged_in.store(true, Ordering::SeqCst);
-        self.dispatch_event(Event::Connected).await;
-        Ok(())
-    }
-
-    #[cfg(feature = "full")]
-    async fn recv_loop(noise_rx: crate::socket::NoiseRecv) {
-        while let Ok(frame) = noise_rx.next_decrypted_frame().await {
-            if let Ok(node) = Node::decode(&frame) {
-                tracing::debug!(tag = %node.tag, "incoming node");
-                // TODO: dispatch node to handlers / handle server nodes
-                let _ = node;
-            }
-        }
-    }
-
-    /// Disconnect and optionally clear session. Clears the transport when present.
-    pub async fn disconnect(&self, logout: bool) -> crate::Result<()> {
-        if logout {
-            let device = self.device.read().await.clone();
-            if let Some(ref d) = device {
-                if let Some(ref jid) = d.id {
-                    self.store.delete(jid).await?;
-                }
+        match removed {
+            Ok(_) => {}
+            Err(Error::Send(SendError::Iq { code: 401, .. })) => {
+                tracing::info!("server already considers this device logged out");
             }
-            *self.device.write().await = None;
-            self.logged_in.store(false, Ordering::SeqCst);
+            // The server dropped the device and closed the stream before answering; the
+            // stream error already deleted the session and emitted LoggedOut.
+            Err(_) if !self.is_logged_in() => return Ok(()),
+            Err(e) => return Err(e),
         }
-        *self.transport.write().await = None;
-        self.connected.store(false, Ordering::SeqCst);
-        Ok(())
-    }
-
-    /// Log out (unpair) and disconnect.
-    pub async fn logout(&self) -> crate::Result<()> {
         self.disconnect(true).await
     }
 
-    /// Whether the client is connected.
+    /// Whether the client is connected and logged in (state [ConnectionState::Connected]).
     pub fn is_connected(&self) -> bool {
-        self.connected.load(Ordering::SeqCst)
+        self.connection_state() == ConnectionState::Connected
     }
 
     /// Whether the client has a logged-in session.
@@ -157,6 +228,30 @@ impl Client {
         self.device.read().await.as_ref().and_then(|d| d.id.clone())
     }
 
+    /// Set the display name others see for us, saved with the session. Needed before
+    /// [Client::send_presence].
+    pub async fn set_push_name(&self, name: &str) -> crate::Result<()> {
+        let mut guard = self.device.write().await;
+        let device = guard
+            .as_mut()
+            .filter(|d| d.is_logged_in())
+            .ok_or(Error::NotLoggedIn)?;
+        device.push_name = Some(name.to_string());
+        self.store.save(device).await?;
+        Ok(())
+    }
+
+    /// Whether `jid` belongs to our own account (by phone number or LID), on any device.
+    pub(crate) async fn is_own_user(&self, jid: &Jid) -> bool {
+        let device = self.device.read().await;
+        device.as_ref().is_some_and(|d| {
+            [d.id.as_ref(), d.lid.as_ref()]
+                .into_iter()
+                .flatten()
+                .any(|own| own.user == jid.user && own.server == jid.server)
+        })
+    }
+
     /// Generate a message ID (3EB0 + hex of hash).
     pub fn generate_message_id(&self) -> MessageId {
         use std::time::{SystemTime, UNIX_EPOCH};
@@ -173,14 +268,15 @@ impl Client {
     }
 
     async fn dispatch_event(&self, evt: Event) {
+        // No subscribed streams is not an error; the event is simply not buffered.
+        let _ = self.event_tx.send(evt.clone());
         let handlers = self.handlers.read().await;
-        for f in handlers.iter() {
-            f(evt.clone());
+        for (_, handler) in handlers.iter() {
+            handler.call(&evt);
         }
     }
 
-    /// Send a raw node over the transport when connected (with feature "full"). Used when send_message is implemented over the wire.
-    #[allow(dead_code)]
+    /// Send a raw node over the current transport.
     pub(crate) async fn send_node(&self, node: &Node) -> crate::Result<()> {
         let transport = self.transport.read().await;
         let t = transport
@@ -190,69 +286,49 @@ impl Client {
         t.send(&data).await
     }
 
-    /// Send a text message. Stub: requires live connection.
-    pub async fn send_message(
-        &self,
-        _to: &Jid,
-        _body: &str,
-        extra: Option<SendRequestExtra>,
-    ) -> crate::Result<SendResponse> {
-        if !self.is_connected() {
-            return Err(Error::NotConnected);
-        }
-        let id = extra
-            .as_ref()
-            .and_then(|e| e.id.clone())
-            .unwrap_or_else(|| self.generate_message_id());
-        Ok(SendResponse {
-            timestamp: std::time::SystemTime::now(),
-            id: id.clone(),
-            server_id: None,
-            sender: self.get_own_id().await,
-        })
-    }
-
-    /// Parse pair-success and save device. Called when QR is scanned.
-    /// If `params.hmac_key` is set, verifies `params.device_identity_bytes` (payload || HMAC-SHA256 tag) before proceeding.
-    /// Generates pairing keys (Noise, identity, adv secret), signs the verified payload for storage, and persists the device.
+    /// Finish pairing from the server's pair-success: verify the device identity HMAC with
+    /// the stored adv secret and the account signature over our identity key, add our device
+    /// signature, save the device and confirm with `pair-device-sign`.
+    ///
+    /// Emits [Event::PairSuccess]; on failure the pairing is rejected, the client disconnects
+    /// and [Event::PairError] is emitted. The client calls this itself when pair-success
+    /// arrives.
     pub async fn complete_pairing(&self, params: CompletePairingParams<'_>) -> crate::Result<()> {
-        let verified_payload = if let Some(key) = params.hmac_key {
-            crate::pairing::verify_device_identity(params.device_identity_bytes, key)?.payload
-        } else {
-            params.device_identity_bytes.to_vec()
-        };
-
-        let keys = crate::pairing::generate_pairing_keys();
-        let account =
-            crate::pairing::sign_device_identity(&verified_payload, &keys.identity_private)?;
-
-        let mut device = self.store.get_first_device().await?.unwrap_or_default();
-        device.id = Some(params.jid.clone());
-        device.lid = Some(params.lid.clone());
-        device.business_name = Some(params.business_name.to_string());
-        device.platform = Some(params.platform.to_string());
-        device.noise_key_pub = Some(keys.noise_public);
-        device.identity_key_pub = Some(keys.identity_public);
-        device.identity_key_priv = Some(keys.identity_private);
-        device.adv_secret_key = Some(keys.adv_secret);
-        device.account = Some(account);
-        device.registration_id = 0;
-        device.signed_prekey_id = 0;
-        self.store.save(&device).await?;
-        *self.device.write().await = Some(device);
-        self.logged_in.store(true, Ordering::SeqCst);
-        self.dispatch_event(Event::PairSuccess {
-            id: params.jid.clone(),
-            lid: params.lid.clone(),
-            business_name: params.business_name.to_string(),
-            platform: params.platform.to_string(),
-        })
-        .await;
-        Ok(())
+        self.stop_qr_rotation();
+        let result = self.pair_device(&params).await;
+        match &result {
+            Ok(()) => {
+                tracing::info!(jid = %params.jid, "paired");
+                self.logged_in.store(true, Ordering::SeqCst);
+                self.dispatch_event(Event::PairSuccess {
+                    id: params.jid.clone(),
+                    lid: params.lid.clone(),
+                    business_name: params.business_name.to_string(),
+                    platform: params.platform.to_string(),
+                })
+                .await;
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "failed to pair device");
+                if let Err(e) = self.disconnect(false).await {
+                    tracing::warn!(error = %e, "failed to disconnect after pairing error");
+                }
+                self.dispatch_event(Event::PairError {
+                    id: params.jid.clone(),
+                    lid: params.lid.clone(),
+                    business_name: params.business_name.to_string(),
+                    platform: params.platform.to_string(),
+                    error: e.to_string(),
+                })
+                .await;
+            }
+        }
+        result
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
 mod tests {
     use super::*;
     use std::sync::Arc;
@@ -272,7 +348,10 @@ mod tests {
     #[tokio::test]
     async fn connect_emits_qr_when_no_session() {
         let store = Arc::new(MemoryStore::new());
-        let client = Client::new(store);
+        let server = mock::MockServer::pairing(&["ref-1"]);
+        let client = Client::builder(store)
+            .transport_factory(server.factory())
+            .build();
         let qr_received = Arc::new(std::sync::atomic::AtomicBool::new(false));
         let qr_received_clone = Arc::clone(&qr_received);
         client
@@ -287,6 +366,25 @@ mod tests {
         assert!(!client.is_logged_in());
     }
 
+    #[tokio::test]
+    async fn events_stream_receives_dispatched_events() {
+        use futures::StreamExt;
+
+        let store = Arc::new(MemoryStore::new());
+        let server = mock::MockServer::pairing(&["ref-1"]);
+        let client = Client::builder(store)
+            .transport_factory(server.factory())
+            .build();
+        let mut events = client.events();
+        client.connect().await.unwrap();
+        assert!(matches!(
+            events.next().await,
+            Some(Event::ConnectionStateChanged {
+                state: ConnectionState::Connecting
+            })
+        ));
+    }
+
     #[tokio::test]
     async fn connect_emits_connected_when_session_exists() {
         let store = Arc::new(MemoryStore::new());
@@ -294,7 +392,10 @@ mod tests {
         dev.id = Some(Jid::new("123", "s.whatsapp.net"));
         store.save(&dev).await.unwrap();
 
-        let client = Client::new(store);
+        let server = mock::MockServer::accepting();
+        let client = Client::builder(store)
+            .transport_factory(server.factory())
+            .build();
         let connected_received = Arc::new(std::sync::atomic::AtomicBool::new(false));
         let connected_received_clone = Arc::clone(&connected_received);
         client
@@ -310,6 +411,38 @@ mod tests {
         assert!(client.is_connected());
     }
 
+    #[tokio::test]
+    async fn connect_uses_configured_transport_factory() {
+        use crate::proto::wa_wa6::ClientPayload;
+        use prost::Message;
+
+        let store = Arc::new(MemoryStore::new());
+        let dev = crate::store::Device {
+            id: Some(Jid::new_ad("5511999", 0, 3, "s.whatsapp.net")),
+            ..Default::default()
+        };
+        store.save(&dev).await.unwrap();
+
+        let server = mock::MockServer::accepting();
+        let client = Client::builder(store)
+            .transport_factory(server.factory())
+            .ws_url("wss://mock.test/ws")
+            .version(ClientVersion::new(2, 2412, 54))
+            .build();
+        client.connect().await.unwrap();
+
+        let dials = server.dials();
+        assert_eq!(dials.len(), 1);
+        assert_eq!(dials[0].url, "wss://mock.test/ws");
+        let handshakes = server.handshakes();
+        assert_eq!(handshakes.len(), 1);
+        let payload = ClientPayload::decode(handshakes[0].client_payload.as_slice()).unwrap();
+        assert_eq!(payload.username, Some(5_511_999));
+        assert_eq!(payload.device, Some(3));
+        let version = payload.user_agent.unwrap().app_version.unwrap();
+        assert_eq!(version.secondary, Some(2412));
+    }
+
     #[tokio::test]
     async fn disconnect_clears_state_on_logout() {
         let store = Arc::new(MemoryStore::new());
@@ -317,12 +450,45 @@ mod tests {
         dev.id = Some(Jid::new("123", "s.whatsapp.net"));
         store.save(&dev).await.unwrap();
 
-        let client = Client::new(store);
+        let server = mock::MockServer::accepting();
+        let client = Client::builder(store)
+            .transport_factory(server.factory())
+            .build();
         client.connect().await.unwrap();
         assert!(client.is_logged_in());
         client.disconnect(true).await.unwrap();
         assert!(!client.is_logged_in());
         assert!(!client.is_connected());
+        assert_eq!(client.connection_state(), ConnectionState::Disconnected);
+    }
+
+    #[tokio::test]
+    async fn send_node_writes_to_transport() {
+        let store = Arc::new(MemoryStore::new());
+        let dev = crate::store::Device {
+            id: Some(Jid::new("123", "s.whatsapp.net")),
+            ..Default::default()
+        };
+        store.save(&dev).await.unwrap();
+
+        let server = mock::MockServer::accepting();
+        let client = Client::builder(store)
+            .transport_factory(server.factory())
+            .build();
+        client.connect().await.unwrap();
+        client
+            .send_node(&Node::new("presence").with_attr("type", "available"))
+            .await
+            .unwrap();
+        let sent = server.next_sent().await;
+        assert_eq!(sent.tag, "presence");
+        assert_eq!(
+            sent.attrs.get("type").map(String::as_str),
+            Some("available")
+        );
+
+        client.disconnect(false).await.unwrap();
+        assert!(client.send_node(&Node::new("presence")).await.is_err());
     }
 
     #[tokio::test]
@@ -335,33 +501,119 @@ mod tests {
         assert!(matches!(res.unwrap_err(), crate::Error::NotConnected));
     }
 
+    /// Run logout(), answering its remove-companion-device IQ with `response` (built from the
+    /// request id); returns the result and whether LoggedOut was emitted.
+    async fn logout_with(
+        client: &Client,
+        server: &mock::MockServer,
+        response: impl FnOnce(String) -> Node,
+    ) -> (crate::Result<()>, bool) {
+        use futures::StreamExt;
+
+        let mut events = client.events();
+        let logout = tokio::spawn({
+            let client = client.clone();
+            async move { client.logout().await }
+        });
+        let request = server.next_sent().await;
+        assert_eq!(request.attrs["xmlns"], "md");
+        assert_eq!(request.attrs["type"], "set");
+        let remove = request.get_child_by_tag("remove-companion-device").unwrap();
+        assert_eq!(remove.attrs["jid"], "123:4@s.whatsapp.net");
+        assert_eq!(remove.attrs["reason"], "user_initiated");
+        server.push(response(request.attrs["id"].clone()));
+        let result = logout.await.unwrap();
+
+        let mut logged_out = false;
+        while let Ok(Some(evt)) =
+            tokio::time::timeout(std::time::Duration::from_millis(50), events.next()).await
+        {
+            logged_out |= matches!(
+                evt,
+                Event::LoggedOut {
+                    on_connect: false,
+                    ..
+                }
+            );
+        }
+        (result, logged_out)
+    }
+
     #[tokio::test]
-    async fn complete_pairing_persists_keys_and_account() {
-        let store = Arc::new(MemoryStore::new());
-        let client = Client::new(store.clone());
-        let payload = b"device-identity-payload";
-        client
-            .complete_pairing(CompletePairingParams {
-                device_identity_bytes: payload,
-                req_id: "req1",
-                business_name: "Biz",
-                platform: "Rust",
-                jid: Jid::new("123", "s.whatsapp.net"),
-                lid: Jid::new("0", "lid.whatsapp.net"),
-                hmac_key: None,
-            })
-            .await
-            .unwrap();
+    async fn logout_removes_companion_device() {
+        let (client, server, store) = mock::connected_client().await;
+        let (result, logged_out) = logout_with(&client, &server, |id| {
+            Node::new("iq")
+                .with_attr("type", "result")
+                .with_attr("id", id)
+        })
+        .await;
+        result.unwrap();
+        assert!(logged_out);
+        assert!(!client.is_logged_in());
+        assert_eq!(client.connection_state(), ConnectionState::Disconnected);
+        assert!(store.get_first_device().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn logout_when_server_already_logged_out() {
+        let (client, server, store) = mock::connected_client().await;
+        let (result, logged_out) = logout_with(&client, &server, |id| {
+            Node::new("iq")
+                .with_attr("type", "error")
+                .with_attr("id", id)
+                .with_children(vec![Node::new("error")
+                    .with_attr("code", "401")
+                    .with_attr("text", "not-authorized")])
+        })
+        .await;
+        result.unwrap();
+        assert!(logged_out);
+        assert!(store.get_first_device().await.unwrap().is_none());
+
+        // Or the server drops the device and closes the stream instead of answering.
+        let (client, server, store) = mock::connected_client().await;
+        let (result, logged_out) = logout_with(&client, &server, |_| {
+            Node::new("stream:error")
+                .with_attr("code", "401")
+                .with_children(vec![
+                    Node::new("conflict").with_attr("type", "device_removed")
+                ])
+        })
+        .await;
+        result.unwrap();
+        assert!(logged_out);
+        assert!(store.get_first_device().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn logout_keeps_session_when_server_refuses() {
+        let (client, server, store) = mock::connected_client().await;
+        let (result, logged_out) = logout_with(&client, &server, |id| {
+            Node::new("iq")
+                .with_attr("type", "error")
+                .with_attr("id", id)
+                .with_children(vec![Node::new("error").with_attr("code", "500")])
+        })
+        .await;
+        assert!(matches!(
+            result,
+            Err(Error::Send(SendError::Iq { code: 500, .. }))
+        ));
+        assert!(!logged_out);
+        assert!(client.is_logged_in());
+        assert!(client.is_connected());
+        assert!(store.get_first_device().await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn logout_requires_session_and_connection() {
+        let client = Client::new(Arc::new(MemoryStore::new()));
+        assert!(matches!(client.logout().await, Err(Error::NotLoggedIn)));
+
+        let (client, _server, _store) = mock::connected_client().await;
+        client.disconnect(false).await.unwrap();
+        assert!(matches!(client.logout().await, Err(Error::NotConnected)));
         assert!(client.is_logged_in());
-        let device = store.get_first_device().await.unwrap().unwrap();
-        assert!(device.identity_key_pub.is_some());
-        assert!(device.identity_key_priv.is_some());
-        assert!(device.noise_key_pub.is_some());
-        assert!(device.adv_secret_key.is_some());
-        assert!(device.account.is_some());
-        let account = device.account.as_ref().unwrap();
-        assert!(account.len() >= 32 + 64);
-        let verified = crate::pairing::verify_signed_identity(account).unwrap();
-        assert_eq!(verified, payload);
     }
 }
@@ -0,0 +1,21 @@
+//! `<notification>` stanzas from the server, dispatched by their `type`.
+
+use super::Client;
+use crate::binary::Node;
+
+impl Client {
+    pub(super) async fn handle_notification(&self, node: &Node) {
+        let kind = node.attrs.get("type").map(String::as_str).unwrap_or("");
+        let result = match kind {
+            "link_code_companion_reg" => self.handle_link_code_notification(node).await,
+            "encrypt" => self.handle_encrypt_notification(node),
+            _ => {
+                tracing::debug!(kind, "unhandled notification");
+                Ok(())
+            }
+        };
+        if let Err(e) = result {
+            tracing::warn!(kind, error = %e, "failed to handle notification");
+        }
+    }
+}
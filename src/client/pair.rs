@@ -0,0 +1,614 @@
+//! Pairing flow: device keys, the server's `pair-device` IQ, QR code rotation and
+//! `pair-success`.
+
+use super::{Client, CompletePairingParams};
+use crate::binary::{Node, NodeContent};
+use crate::crypto::KeyPair;
+use crate::error::{Error, PairingError, StoreError};
+use crate::events::Event;
+use crate::pairing::{
+    generate_pairing_keys, make_qr_code, sign_device_identity, verify_device_identity,
+};
+use crate::store::Device;
+use crate::types::Jid;
+use prost::Message;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+/// How long the first QR code is shown before moving to the next.
+pub const QR_FIRST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long each following QR code is shown.
+pub const QR_NEXT_TIMEOUT: Duration = Duration::from_secs(20);
+
+impl Client {
+    /// Device to connect with. An unpaired client gets fresh pairing keys, saved to the store
+    /// so that the QR codes, pair-success and later logins all use the same keys.
+    pub(super) async fn prepare_device(&self) -> crate::Result<Device> {
+        let mut guard = self.device.write().await;
+        match guard.as_ref() {
+            Some(device) if device.id.is_some() || device.has_keys() => Ok(device.clone()),
+            _ => {
+                let device = Device::with_keys(&generate_pairing_keys());
+                self.store.save(&device).await?;
+                if let Some(signed_prekey) = device.signed_prekey() {
+                    self.store.put_signed_prekey(&signed_prekey).await?;
+                }
+                *guard = Some(device.clone());
+                Ok(device)
+            }
+        }
+    }
+
+    /// `<iq type="set"><pair-device><ref>…</ref>…</pair-device></iq>`: acknowledge it, then
+    /// emit one QR code per ref and start rotating them.
+    pub(super) async fn handle_pair_device(&self, iq: &Node, pair: &Node) {
+        if let Err(e) = self.send_node(&iq_result(iq)).await {
+            tracing::warn!(error = %e, "failed to acknowledge pair-device");
+        }
+        let device = self.device.read().await.clone().unwrap_or_default();
+        let (Some(noise), Some(identity), Some(adv)) = (
+            device.noise_key_pub,
+            device.identity_key_pub,
+            device.adv_secret_key,
+        ) else {
+            tracing::error!("got pair-device but the device has no pairing keys");
+            return;
+        };
+        let codes: Vec<String> = pair
+            .get_children()
+            .iter()
+            .filter(|n| n.tag == "ref")
+            .filter_map(|n| match &n.content {
+                NodeContent::Bytes(b) => String::from_utf8(b.clone()).ok(),
+                _ => None,
+            })
+            .map(|reference| make_qr_code(&reference, &noise, &identity, &adv))
+            .collect();
+        self.finish_auth(Ok(()));
+        self.dispatch_event(Event::Qr {
+            codes: codes.clone(),
+        })
+        .await;
+        self.start_qr_rotation(codes);
+    }
+
+    /// Emit [Event::QrCode] for each code in turn; once the last one expires, disconnect and
+    /// emit [Event::QrTimeout].
+    fn start_qr_rotation(&self, codes: Vec<String>) {
+        let client = self.clone();
+        let task = tokio::spawn(async move {
+            for (i, code) in codes.into_iter().enumerate() {
+                let timeout = if i == 0 {
+                    QR_FIRST_TIMEOUT
+                } else {
+                    QR_NEXT_TIMEOUT
+                };
+                client.dispatch_event(Event::QrCode { code, timeout }).await;
+                tokio::time::sleep(timeout).await;
+            }
+            // Detach so closing the connection below does not abort this task.
+            client.qr_task.lock().unwrap().take();
+            client.reconnect_epoch.fetch_add(1, Ordering::SeqCst);
+            client.close_connection(true).await;
+            client.dispatch_event(Event::QrTimeout).await;
+        });
+        if let Some(previous) = self.qr_task.lock().unwrap().replace(task) {
+            previous.abort();
+        }
+    }
+
+    pub(super) fn stop_qr_rotation(&self) {
+        if let Some(task) = self.qr_task.lock().unwrap().take() {
+            task.abort();
+        }
+    }
+
+    /// `<iq type="set"><pair-success>…</pair-success></iq>`: the phone linked us. Completed
+    /// on its own task, since a failed pairing disconnects (which stops the receive loop).
+    pub(super) fn handle_pair_success(&self, iq: &Node, success: &Node) {
+        let attr = |tag: &str, key: &str| {
+            success
+                .get_child_by_tag(tag)
+                .and_then(|n| n.attrs.get(key))
+                .cloned()
+                .unwrap_or_default()
+        };
+        let jid = |key: &str| {
+            attr("device", key)
+                .parse::<Jid>()
+                .unwrap_or_else(|_| Jid::server(""))
+        };
+        let req_id = iq.attrs.get("id").cloned().unwrap_or_default();
+        let identity = child_bytes(success, "device-identity")
+            .unwrap_or_default()
+            .to_vec();
+        let (business_name, platform) = (attr("biz", "name"), attr("platform", "name"));
+        let (jid, lid) = (jid("jid"), jid("lid"));
+        let client = self.clone();
+        tokio::spawn(async move {
+            let _ = client
+                .complete_pairing(CompletePairingParams {
+                    device_identity_bytes: &identity,
+                    req_id: &req_id,
+                    business_name: &business_name,
+                    platform: &platform,
+                    jid,
+                    lid,
+                })
+                .await;
+        });
+    }
+
+    /// Verify and sign the device identity, save the paired device and confirm with
+    /// `pair-device-sign`. Verification or save failures are reported to the server.
+    pub(super) async fn pair_device(
+        &self,
+        params: &CompletePairingParams<'_>,
+    ) -> crate::Result<()> {
+        let unpaired = self.device.read().await.clone();
+        let (device, reply) = match self.verify_pairing(params, unpaired.clone()).await {
+            Ok(paired) => paired,
+            Err(e) => {
+                if let Err(send) = self.send_node(&pair_error(params.req_id, &e)).await {
+                    tracing::warn!(error = %send, "failed to reject pairing");
+                }
+                return Err(e);
+            }
+        };
+        if let Err(e) = self.send_node(&reply).await {
+            // The phone never got our signature: forget the half-finished pairing.
+            self.store.delete(&params.jid).await?;
+            if let Some(unpaired) = &unpaired {
+                self.store.save(unpaired).await?;
+            }
+            *self.device.write().await = unpaired;
+            return Err(e);
+        }
+        *self.device.write().await = Some(device);
+        Ok(())
+    }
+
+    /// Check the pair-success against our keys and save the paired device; returns it with
+    /// the `pair-device-sign` reply to send.
+    async fn verify_pairing(
+        &self,
+        params: &CompletePairingParams<'_>,
+        device: Option<Device>,
+    ) -> crate::Result<(Device, Node)> {
+        if params.jid.is_empty() {
+            return Err(PairingError::Protocol("pair-success without device jid".into()).into());
+        }
+        let mut device = device
+            .filter(Device::has_keys)
+            .ok_or(StoreError::IdentityNotFound)?;
+        let (Some(identity_private), Some(adv_secret)) =
+            (device.identity_key_priv, device.adv_secret_key)
+        else {
+            return Err(StoreError::IdentityNotFound.into());
+        };
+        let identity = KeyPair::from_private(identity_private);
+        let verified =
+            verify_device_identity(params.device_identity_bytes, &adv_secret, &identity.public)?;
+        if let Some(accept) = &self.config.pre_pair_callback {
+            if !accept(&params.jid, params.platform, params.business_name) {
+                return Err(PairingError::RejectedLocally.into());
+            }
+        }
+        let account = sign_device_identity(&verified, &identity);
+        let self_signed = crate::proto::wa_adv::AdvSignedDeviceIdentity {
+            account_signature_key: None,
+            ..account.clone()
+        };
+
+        device.id = Some(params.jid.clone());
+        device.lid = Some(params.lid.clone());
+        device.business_name = Some(params.business_name.to_string());
+        device.platform = Some(params.platform.to_string());
+        device.account = Some(account.encode_to_vec());
+        self.store.save(&device).await?;
+
+        let reply = Node::new("iq")
+            .with_attr("to", Jid::default_server().to_string())
+            .with_attr("type", "result")
+            .with_attr("id", params.req_id)
+            .with_attr("xmlns", "md")
+            .with_children(vec![Node::new("pair-device-sign").with_children(vec![
+                Node::new("device-identity")
+                    .with_attr("key-index", verified.details.key_index().to_string())
+                    .with_content(self_signed.encode_to_vec()),
+            ])]);
+        Ok((device, reply))
+    }
+}
+
+/// `<iq type="error">` rejecting a pair-success that failed with `error`.
+fn pair_error(req_id: &str, error: &Error) -> Node {
+    let (code, text) = match error {
+        Error::Pairing(PairingError::InvalidDeviceIdentityHmac) => ("401", "hmac-mismatch"),
+        Error::Pairing(PairingError::InvalidDeviceSignature) => ("401", "signature-mismatch"),
+        _ => ("500", "internal-error"),
+    };
+    Node::new("iq")
+        .with_attr("to", Jid::default_server().to_string())
+        .with_attr("type", "error")
+        .with_attr("id", req_id)
+        .with_children(vec![Node::new("error")
+            .with_attr("code", code)
+            .with_attr("text", text)])
+}
+
+/// Binary content of the first child with `tag`.
+pub(super) fn child_bytes<'a>(node: &'a Node, tag: &str) -> Option<&'a [u8]> {
+    match &node.get_child_by_tag(tag)?.content {
+        NodeContent::Bytes(b) => Some(b),
+        _ => None,
+    }
+}
+
+/// Empty `result` reply to an IQ from the server.
+pub(super) fn iq_result(iq: &Node) -> Node {
+    let mut reply = Node::new("iq").with_attr("type", "result");
+    if let Some(id) = iq.attrs.get("id") {
+        reply = reply.with_attr("id", id.clone());
+    }
+    if let Some(from) = iq.attrs.get("from") {
+        reply = reply.with_attr("to", from.clone());
+    }
+    reply
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::MockServer;
+    use crate::client::ClientBuilder;
+    use crate::events::ConnectionState;
+    use crate::proto::wa_wa6::ClientPayload;
+    use crate::store::{DeviceStore, MemoryStore};
+    use futures::StreamExt;
+    use prost::Message;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn pair_device_emits_qr_codes_and_acks() {
+        let store = Arc::new(MemoryStore::new());
+        let server = MockServer::pairing(&["ref-1", "ref-2"]);
+        let client = Client::builder(store.clone())
+            .transport_factory(server.factory())
+            .build();
+        let mut events = client.events();
+        client.connect().await.unwrap();
+        assert_eq!(client.connection_state(), ConnectionState::Authenticating);
+        assert!(!client.is_logged_in());
+
+        let ack = server.next_sent().await;
+        assert_eq!(ack.tag, "iq");
+        assert_eq!(ack.attrs.get("type").map(String::as_str), Some("result"));
+        assert_eq!(ack.attrs.get("id").map(String::as_str), Some("pair-1"));
+        assert_eq!(
+            ack.attrs.get("to").map(String::as_str),
+            Some("s.whatsapp.net")
+        );
+
+        let device = store.get_first_device().await.unwrap().unwrap();
+        assert!(device.has_keys());
+        let handshake = &server.handshakes()[0];
+        assert_eq!(handshake.noise_key, device.noise_key_priv);
+        let payload = ClientPayload::decode(handshake.client_payload.as_slice()).unwrap();
+        assert!(payload.device_pairing_data.is_some());
+
+        let expected: Vec<String> = ["ref-1", "ref-2"]
+            .iter()
+            .map(|r| {
+                make_qr_code(
+                    r,
+                    &device.noise_key_pub.unwrap(),
+                    &device.identity_key_pub.unwrap(),
+                    &device.adv_secret_key.unwrap(),
+                )
+            })
+            .collect();
+        let codes = loop {
+            if let Some(Event::Qr { codes }) = events.next().await {
+                break codes;
+            }
+        };
+        assert_eq!(codes, expected);
+        assert!(matches!(
+            events.next().await,
+            Some(Event::QrCode { code, timeout }) if code == expected[0] && timeout == QR_FIRST_TIMEOUT
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn qr_codes_rotate_then_time_out() {
+        let store = Arc::new(MemoryStore::new());
+        let server = MockServer::pairing(&["ref-1", "ref-2", "ref-3"]);
+        let client = Client::builder(store)
+            .transport_factory(server.factory())
+            .build();
+        let mut events = client.events();
+        client.connect().await.unwrap();
+
+        let mut shown = Vec::new();
+        loop {
+            match events.next().await.unwrap() {
+                Event::QrCode { code, timeout } => {
+                    shown.push((code.split(',').next().unwrap().to_string(), timeout))
+                }
+                Event::QrTimeout => break,
+                _ => {}
+            }
+        }
+        assert_eq!(
+            shown,
+            vec![
+                ("ref-1".to_string(), QR_FIRST_TIMEOUT),
+                ("ref-2".to_string(), QR_NEXT_TIMEOUT),
+                ("ref-3".to_string(), QR_NEXT_TIMEOUT),
+            ]
+        );
+        assert_eq!(client.connection_state(), ConnectionState::Disconnected);
+
+        // Connecting again advertises the same keys.
+        client.connect().await.unwrap();
+        let handshakes = server.handshakes();
+        assert_eq!(handshakes.len(), 2);
+        assert_eq!(handshakes[0].noise_key, handshakes[1].noise_key);
+    }
+
+    #[tokio::test]
+    async fn disconnect_stops_qr_rotation() {
+        let server = MockServer::pairing(&["ref-1", "ref-2"]);
+        let client = Client::builder(Arc::new(MemoryStore::new()))
+            .transport_factory(server.factory())
+            .build();
+        client.connect().await.unwrap();
+        client.disconnect(false).await.unwrap();
+        assert!(client.qr_task.lock().unwrap().is_none());
+    }
+
+    /// Connect as an unpaired client and get past the pair-device ack.
+    async fn scanning_client() -> (Client, MockServer, Arc<MemoryStore>, Device) {
+        scanning_client_with(|builder| builder).await
+    }
+
+    async fn scanning_client_with(
+        configure: impl FnOnce(ClientBuilder) -> ClientBuilder,
+    ) -> (Client, MockServer, Arc<MemoryStore>, Device) {
+        let store = Arc::new(MemoryStore::new());
+        let server = MockServer::pairing(&["ref-1"]);
+        let client =
+            configure(Client::builder(store.clone()).transport_factory(server.factory())).build();
+        client.connect().await.unwrap();
+        server.next_sent().await;
+        let device = store.get_first_device().await.unwrap().unwrap();
+        (client, server, store, device)
+    }
+
+    fn pair_success(identity: Vec<u8>) -> Node {
+        Node::new("iq")
+            .with_attr("type", "set")
+            .with_attr("id", "pair-2")
+            .with_attr("from", "s.whatsapp.net")
+            .with_children(vec![Node::new("pair-success").with_children(vec![
+                Node::new("device-identity").with_content(identity),
+                Node::new("device")
+                    .with_attr("jid", "5511999990000:3@s.whatsapp.net")
+                    .with_attr("lid", "98765:3@lid"),
+                Node::new("biz").with_attr("name", "Biz"),
+                Node::new("platform").with_attr("name", "android"),
+            ])])
+    }
+
+    #[tokio::test]
+    async fn pair_success_signs_and_confirms() {
+        use crate::proto::wa_adv::AdvSignedDeviceIdentity;
+
+        let (client, server, store, device) = scanning_client().await;
+        let mut events = client.events();
+        let primary = KeyPair::generate();
+        let identity_public = device.identity_key_pub.unwrap();
+        server.push(pair_success(crate::pairing::primary_device_identity(
+            &primary,
+            &identity_public,
+            &device.adv_secret_key.unwrap(),
+            7,
+            false,
+        )));
+
+        let reply = server.next_sent().await;
+        assert_eq!(reply.attrs["type"], "result");
+        assert_eq!(reply.attrs["id"], "pair-2");
+        let signed = reply
+            .get_child_by_tag("pair-device-sign")
+            .and_then(|n| n.get_child_by_tag("device-identity"))
+            .unwrap();
+        assert_eq!(signed.attrs["key-index"], "7");
+        let NodeContent::Bytes(bytes) = &signed.content else {
+            panic!("device-identity without content");
+        };
+        let sent = AdvSignedDeviceIdentity::decode(bytes.as_slice()).unwrap();
+        assert!(sent.account_signature_key.is_none());
+        let message = [
+            &crate::pairing::ADV_DEVICE_SIGNATURE_PREFIX[..],
+            sent.details(),
+            &identity_public[..],
+            &primary.public[..],
+        ]
+        .concat();
+        let signature: [u8; 64] = sent.device_signature().try_into().unwrap();
+        assert!(crate::crypto::verify(
+            &identity_public,
+            &message,
+            &signature
+        ));
+
+        let (id, platform) = loop {
+            if let Some(Event::PairSuccess { id, platform, .. }) = events.next().await {
+                break (id, platform);
+            }
+        };
+        assert_eq!(id.to_string(), "5511999990000:3@s.whatsapp.net");
+        assert_eq!(platform, "android");
+        assert!(client.is_logged_in());
+        assert!(client.qr_task.lock().unwrap().is_none());
+        let paired = store.get_first_device().await.unwrap().unwrap();
+        assert_eq!(paired.id, Some(id));
+        assert_eq!(paired.business_name.as_deref(), Some("Biz"));
+        assert_eq!(paired.identity_key_priv, device.identity_key_priv);
+        let account = AdvSignedDeviceIdentity::decode(paired.account.unwrap().as_slice()).unwrap();
+        assert_eq!(account.account_signature_key(), primary.public);
+        assert_eq!(account.device_signature(), sent.device_signature());
+    }
+
+    #[tokio::test]
+    async fn pair_success_with_bad_hmac_is_rejected() {
+        let (client, server, store, device) = scanning_client().await;
+        let mut events = client.events();
+        server.push(pair_success(crate::pairing::primary_device_identity(
+            &KeyPair::generate(),
+            &device.identity_key_pub.unwrap(),
+            &[0; 32],
+            1,
+            false,
+        )));
+
+        let reply = server.next_sent().await;
+        assert_eq!(reply.attrs["type"], "error");
+        assert_eq!(reply.attrs["id"], "pair-2");
+        let error = reply.get_child_by_tag("error").unwrap();
+        assert_eq!(error.attrs["code"], "401");
+        assert_eq!(error.attrs["text"], "hmac-mismatch");
+
+        let error = loop {
+            if let Some(Event::PairError { error, .. }) = events.next().await {
+                break error;
+            }
+        };
+        assert_eq!(
+            error,
+            Error::from(PairingError::InvalidDeviceIdentityHmac).to_string()
+        );
+        assert!(!client.is_logged_in());
+        assert_eq!(client.connection_state(), ConnectionState::Disconnected);
+        assert!(store
+            .get_first_device()
+            .await
+            .unwrap()
+            .unwrap()
+            .id
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn pair_success_with_foreign_signature_is_rejected() {
+        let (client, server, _store, device) = scanning_client().await;
+        let mut events = client.events();
+        server.push(pair_success(crate::pairing::primary_device_identity(
+            &KeyPair::generate(),
+            &KeyPair::generate().public,
+            &device.adv_secret_key.unwrap(),
+            1,
+            false,
+        )));
+
+        let reply = server.next_sent().await;
+        let error = reply.get_child_by_tag("error").unwrap();
+        assert_eq!(error.attrs["code"], "401");
+        assert_eq!(error.attrs["text"], "signature-mismatch");
+        loop {
+            if let Some(Event::PairError { .. }) = events.next().await {
+                break;
+            }
+        }
+        assert!(!client.is_logged_in());
+    }
+
+    #[tokio::test]
+    async fn pre_pair_callback_can_reject_pairing() {
+        let seen = Arc::new(std::sync::Mutex::new(None));
+        let seen_by_callback = seen.clone();
+        let (client, server, store, device) = scanning_client_with(|builder| {
+            builder.pre_pair_callback(move |jid, platform, business_name| {
+                *seen_by_callback.lock().unwrap() =
+                    Some((jid.clone(), platform.to_string(), business_name.to_string()));
+                false
+            })
+        })
+        .await;
+        let mut events = client.events();
+        server.push(pair_success(crate::pairing::primary_device_identity(
+            &KeyPair::generate(),
+            &device.identity_key_pub.unwrap(),
+            &device.adv_secret_key.unwrap(),
+            1,
+            false,
+        )));
+
+        let reply = server.next_sent().await;
+        assert_eq!(reply.attrs["type"], "error");
+        assert_eq!(reply.attrs["id"], "pair-2");
+        let error = reply.get_child_by_tag("error").unwrap();
+        assert_eq!(error.attrs["code"], "500");
+        assert_eq!(error.attrs["text"], "internal-error");
+
+        let error = loop {
+            if let Some(Event::PairError { error, .. }) = events.next().await {
+                break error;
+            }
+        };
+        assert_eq!(
+            error,
+            Error::from(PairingError::RejectedLocally).to_string()
+        );
+        let (jid, platform, business_name) = seen.lock().unwrap().clone().unwrap();
+        assert_eq!(jid.to_string(), "5511999990000:3@s.whatsapp.net");
+        assert_eq!(platform, "android");
+        assert_eq!(business_name, "Biz");
+        assert!(!client.is_logged_in());
+        assert!(store
+            .get_first_device()
+            .await
+            .unwrap()
+            .unwrap()
+            .id
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn pre_pair_callback_can_accept_pairing() {
+        let (client, server, _store, device) =
+            scanning_client_with(|builder| builder.pre_pair_callback(|_, _, _| true)).await;
+        let mut events = client.events();
+        server.push(pair_success(crate::pairing::primary_device_identity(
+            &KeyPair::generate(),
+            &device.identity_key_pub.unwrap(),
+            &device.adv_secret_key.unwrap(),
+            1,
+            false,
+        )));
+        assert_eq!(server.next_sent().await.attrs["type"], "result");
+        loop {
+            if let Some(Event::PairSuccess { .. }) = events.next().await {
+                break;
+            }
+        }
+        assert!(client.is_logged_in());
+    }
+
+    #[tokio::test]
+    async fn scan_without_multidevice_keeps_qr_codes() {
+        let (client, server, _store, _device) = scanning_client().await;
+        let mut events = client.events();
+        server.push(Node::new("ib").with_children(vec![Node::new("downgrade_webclient")]));
+        loop {
+            if let Some(Event::QrScannedWithoutMultidevice) = events.next().await {
+                break;
+            }
+        }
+        assert!(client.qr_task.lock().unwrap().is_some());
+        assert_eq!(client.connection_state(), ConnectionState::Authenticating);
+    }
+}
@@ -0,0 +1,394 @@
+//! Pair-code linking: instead of scanning a QR code, the user types an 8-character code
+//! shown by [Client::pair_phone] into WhatsApp on their phone.
+
+use super::pair::child_bytes;
+use super::request::{InfoQuery, IqType};
+use super::Client;
+use crate::binary::Node;
+use crate::crypto::KeyPair;
+use crate::error::{Error, PairingError};
+use crate::pairing::{
+    generate_link_code, link_code_adv_secret, unwrap_ephemeral_key, wrap_ephemeral_key,
+    wrap_key_bundle,
+};
+use crate::types::{Jid, DEFAULT_USER_SERVER};
+use rand::RngCore;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+/// Browser shown on the phone for a device linked with a pairing code.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PairClientType {
+    #[default]
+    Unknown = 0,
+    Chrome = 1,
+    Edge = 2,
+    Firefox = 3,
+    Ie = 4,
+    Opera = 5,
+    Safari = 6,
+    Electron = 7,
+    Uwp = 8,
+    OtherWebClient = 9,
+}
+
+impl PairClientType {
+    fn display_name(self) -> &'static str {
+        match self {
+            Self::Unknown => "Unknown",
+            Self::Chrome => "Chrome",
+            Self::Edge => "Edge",
+            Self::Firefox => "Firefox",
+            Self::Ie => "IE",
+            Self::Opera => "Opera",
+            Self::Safari => "Safari",
+            Self::Electron => "Electron",
+            Self::Uwp => "UWP",
+            Self::OtherWebClient => "Other",
+        }
+    }
+}
+
+/// State kept between `companion_hello` and the phone's `primary_hello`.
+pub(super) struct PhoneLinking {
+    jid: Jid,
+    code: String,
+    ephemeral: StaticSecret,
+    pairing_ref: Vec<u8>,
+}
+
+impl Client {
+    /// Start linking this device to `phone` (international format, digits only; other
+    /// characters are ignored) with a pairing code. Returns the code to show the user, as
+    /// `XXXX-XXXX`; WhatsApp on the phone asks for it (with a push notification if
+    /// `show_push_notification`). Pairing then completes like a QR scan, with
+    /// [Event::PairSuccess](crate::Event::PairSuccess).
+    ///
+    /// Call after [connect](Client::connect) on an unpaired client, e.g. on the first
+    /// [Event::Qr](crate::Event::Qr); QR code rotation stops once the code is issued.
+    pub async fn pair_phone(
+        &self,
+        phone: &str,
+        show_push_notification: bool,
+        client_type: PairClientType,
+    ) -> crate::Result<String> {
+        let phone: String = phone.chars().filter(char::is_ascii_digit).collect();
+        if phone.len() <= 6 {
+            return Err(PairingError::InvalidPhoneNumber("too short".into()).into());
+        }
+        if phone.starts_with('0') {
+            return Err(
+                PairingError::InvalidPhoneNumber("must be in international format".into()).into(),
+            );
+        }
+        if self.is_logged_in() {
+            return Err(PairingError::Protocol("already paired".into()).into());
+        }
+        let noise_public = self
+            .device
+            .read()
+            .await
+            .as_ref()
+            .and_then(|d| d.noise_key_pub)
+            .ok_or(Error::NotConnected)?;
+
+        let ephemeral = StaticSecret::random_from_rng(rand::thread_rng());
+        let code = generate_link_code();
+        let wrapped = wrap_ephemeral_key(&code, PublicKey::from(&ephemeral).as_bytes());
+        let jid = Jid::new(phone, DEFAULT_USER_SERVER);
+        let display = format!(
+            "{} ({})",
+            client_type.display_name(),
+            self.config.device_props.os
+        );
+        let response = self
+            .send_iq(InfoQuery {
+                namespace: "md",
+                iq_type: IqType::Set,
+                to: Jid::default_server(),
+                content: vec![Node::new("link_code_companion_reg")
+                    .with_attr("jid", jid.to_string())
+                    .with_attr("stage", "companion_hello")
+                    .with_attr(
+                        "should_show_push_notification",
+                        show_push_notification.to_string(),
+                    )
+                    .with_children(vec![
+                        Node::new("link_code_pairing_wrapped_companion_ephemeral_pub")
+                            .with_content(wrapped),
+                        Node::new("companion_server_auth_key_pub")
+                            .with_content(noise_public.to_vec()),
+                        Node::new("companion_platform_id")
+                            .with_content((client_type as i32).to_string().into_bytes()),
+                        Node::new("companion_platform_display").with_content(display.into_bytes()),
+                        Node::new("link_code_pairing_nonce").with_content(vec![0]),
+                    ])],
+            })
+            .await?;
+        let pairing_ref = response
+            .get_child_by_tag("link_code_companion_reg")
+            .and_then(|reg| child_bytes(reg, "link_code_pairing_ref"))
+            .ok_or_else(|| PairingError::Protocol("missing link_code_pairing_ref".into()))?
+            .to_vec();
+
+        self.stop_qr_rotation();
+        *self.phone_linking.lock().unwrap() = Some(PhoneLinking {
+            jid,
+            code: code.clone(),
+            ephemeral,
+            pairing_ref,
+        });
+        Ok(format!("{}-{}", &code[..4], &code[4..]))
+    }
+
+    /// `primary_hello`: the phone accepted the code. Derive the shared secrets, store the
+    /// new adv secret (checked on pair-success) and answer with `companion_finish`.
+    pub(super) async fn handle_link_code_notification(&self, node: &Node) -> crate::Result<()> {
+        let reg = node
+            .get_child_by_tag("link_code_companion_reg")
+            .ok_or_else(|| PairingError::Protocol("missing link_code_companion_reg".into()))?;
+        let field = |tag: &str| {
+            child_bytes(reg, tag)
+                .ok_or_else(|| Error::from(PairingError::Protocol(format!("missing {}", tag))))
+        };
+        let pairing_ref = field("link_code_pairing_ref")?;
+        let wrapped_primary_ephemeral = field("link_code_pairing_wrapped_primary_ephemeral_pub")?;
+        let primary_identity: [u8; 32] = field("primary_identity_pub")?
+            .try_into()
+            .map_err(|_| PairingError::Protocol("invalid primary_identity_pub".into()))?;
+
+        let (jid, ephemeral_shared) = {
+            let linking = self.phone_linking.lock().unwrap();
+            let linking = linking
+                .as_ref()
+                .ok_or_else(|| PairingError::Protocol("no pair-code link in progress".into()))?;
+            if linking.pairing_ref != pairing_ref {
+                return Err(PairingError::Protocol("pairing ref mismatch".into()).into());
+            }
+            let primary_ephemeral = unwrap_ephemeral_key(&linking.code, wrapped_primary_ephemeral)?;
+            let shared = linking
+                .ephemeral
+                .diffie_hellman(&PublicKey::from(primary_ephemeral));
+            (linking.jid.clone(), shared.to_bytes())
+        };
+
+        let mut device = self.device.read().await.clone().unwrap_or_default();
+        let identity_private = device
+            .identity_key_priv
+            .ok_or(crate::error::StoreError::IdentityNotFound)?;
+        let identity = KeyPair::from_private(identity_private);
+        let identity_shared = identity.agree(&primary_identity);
+
+        let mut adv_random = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut adv_random);
+        let bundle = wrap_key_bundle(
+            &ephemeral_shared,
+            &identity.public,
+            &primary_identity,
+            &adv_random,
+        )?;
+        device.adv_secret_key = Some(link_code_adv_secret(
+            &ephemeral_shared,
+            &identity_shared,
+            &adv_random,
+        ));
+        self.store.save(&device).await?;
+        *self.device.write().await = Some(device);
+
+        let finish = InfoQuery {
+            namespace: "md",
+            iq_type: IqType::Set,
+            to: Jid::default_server(),
+            content: vec![Node::new("link_code_companion_reg")
+                .with_attr("jid", jid.to_string())
+                .with_attr("stage", "companion_finish")
+                .with_children(vec![
+                    Node::new("link_code_pairing_wrapped_key_bundle").with_content(bundle),
+                    Node::new("companion_identity_public").with_content(identity.public.to_vec()),
+                    Node::new("link_code_pairing_ref").with_content(pairing_ref.to_vec()),
+                ])],
+        };
+        // The response is read by the receive loop this runs on.
+        let client = self.clone();
+        tokio::spawn(async move {
+            match client.send_iq(finish).await {
+                Ok(_) => *client.phone_linking.lock().unwrap() = None,
+                Err(e) => tracing::warn!(error = %e, "failed to finish pair-code link"),
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::MockServer;
+    use crate::store::{DeviceStore, MemoryStore};
+    use aes_gcm::aead::Aead;
+    use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+    use hkdf::Hkdf;
+    use sha2::Sha256;
+    use std::sync::Arc;
+
+    fn bytes_of<'a>(node: &'a Node, tag: &str) -> &'a [u8] {
+        child_bytes(node, tag).unwrap_or_else(|| panic!("missing {}", tag))
+    }
+
+    async fn pairing_client() -> (Client, MockServer, Arc<MemoryStore>) {
+        let store = Arc::new(MemoryStore::new());
+        let server = MockServer::pairing(&["ref-1"]);
+        let client = Client::builder(store.clone())
+            .transport_factory(server.factory())
+            .os_name("Ops Bot")
+            .build();
+        client.connect().await.unwrap();
+        assert_eq!(server.next_sent().await.attrs["type"], "result");
+        (client, server, store)
+    }
+
+    /// Run `pair_phone` against the mock server; returns the code and the hello request.
+    async fn start_pairing(client: &Client, server: &MockServer) -> (String, Node) {
+        let pairing = {
+            let client = client.clone();
+            tokio::spawn(async move {
+                client
+                    .pair_phone("+55 (11) 99999-0000", true, PairClientType::Chrome)
+                    .await
+            })
+        };
+        let hello = server.next_sent().await;
+        server.push(
+            Node::new("iq")
+                .with_attr("type", "result")
+                .with_attr("id", hello.attrs["id"].clone())
+                .with_children(vec![Node::new("link_code_companion_reg").with_children(
+                    vec![Node::new("link_code_pairing_ref").with_content(b"pair-ref".to_vec())],
+                )]),
+        );
+        (pairing.await.unwrap().unwrap(), hello)
+    }
+
+    #[tokio::test]
+    async fn pair_phone_rejects_invalid_numbers() {
+        let client = Client::new(Arc::new(MemoryStore::new()));
+        for phone in ["123", "0612345678"] {
+            let err = client
+                .pair_phone(phone, false, PairClientType::Chrome)
+                .await
+                .unwrap_err();
+            assert!(matches!(
+                err,
+                Error::Pairing(PairingError::InvalidPhoneNumber(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn pair_phone_sends_companion_hello() {
+        let (client, server, store) = pairing_client().await;
+        let (code, hello) = start_pairing(&client, &server).await;
+
+        assert_eq!(code.len(), 9);
+        assert_eq!(&code[4..5], "-");
+        assert_eq!(hello.attrs["xmlns"], "md");
+        assert_eq!(hello.attrs["type"], "set");
+        let reg = hello.get_child_by_tag("link_code_companion_reg").unwrap();
+        assert_eq!(reg.attrs["jid"], "5511999990000@s.whatsapp.net");
+        assert_eq!(reg.attrs["stage"], "companion_hello");
+        assert_eq!(reg.attrs["should_show_push_notification"], "true");
+        let device = store.get_first_device().await.unwrap().unwrap();
+        assert_eq!(
+            bytes_of(reg, "companion_server_auth_key_pub"),
+            device.noise_key_pub.unwrap()
+        );
+        assert_eq!(bytes_of(reg, "companion_platform_id"), b"1");
+        assert_eq!(
+            bytes_of(reg, "companion_platform_display"),
+            b"Chrome (Ops Bot)"
+        );
+        let wrapped = bytes_of(reg, "link_code_pairing_wrapped_companion_ephemeral_pub");
+        assert!(unwrap_ephemeral_key(&code.replace('-', ""), wrapped).is_ok());
+        assert!(client.qr_task.lock().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn primary_hello_completes_key_exchange() {
+        let (client, server, store) = pairing_client().await;
+        let (code, hello) = start_pairing(&client, &server).await;
+        let code = code.replace('-', "");
+        let reg = hello.get_child_by_tag("link_code_companion_reg").unwrap();
+        let companion_ephemeral = unwrap_ephemeral_key(
+            &code,
+            bytes_of(reg, "link_code_pairing_wrapped_companion_ephemeral_pub"),
+        )
+        .unwrap();
+
+        // Play the phone: send our ephemeral key wrapped with the code, plus our identity.
+        let primary_ephemeral = StaticSecret::random_from_rng(rand::thread_rng());
+        let primary_identity = StaticSecret::random_from_rng(rand::thread_rng());
+        let primary_identity_pub = PublicKey::from(&primary_identity).to_bytes();
+        server.push(
+            Node::new("notification")
+                .with_attr("type", "link_code_companion_reg")
+                .with_attr("id", "n1")
+                .with_children(vec![Node::new("link_code_companion_reg")
+                    .with_attr("stage", "primary_hello")
+                    .with_children(vec![
+                        Node::new("link_code_pairing_ref").with_content(b"pair-ref".to_vec()),
+                        Node::new("link_code_pairing_wrapped_primary_ephemeral_pub").with_content(
+                            wrap_ephemeral_key(
+                                &code,
+                                PublicKey::from(&primary_ephemeral).as_bytes(),
+                            ),
+                        ),
+                        Node::new("primary_identity_pub")
+                            .with_content(primary_identity_pub.to_vec()),
+                    ])]),
+        );
+
+        let finish = server.next_sent_skipping_acks().await;
+        let reg = finish.get_child_by_tag("link_code_companion_reg").unwrap();
+        assert_eq!(reg.attrs["stage"], "companion_finish");
+        assert_eq!(reg.attrs["jid"], "5511999990000@s.whatsapp.net");
+        assert_eq!(bytes_of(reg, "link_code_pairing_ref"), b"pair-ref");
+        let companion_identity: [u8; 32] = bytes_of(reg, "companion_identity_public")
+            .try_into()
+            .unwrap();
+
+        let ephemeral_shared = primary_ephemeral
+            .diffie_hellman(&PublicKey::from(companion_ephemeral))
+            .to_bytes();
+        let bundle = bytes_of(reg, "link_code_pairing_wrapped_key_bundle");
+        let (salt, rest) = bundle.split_at(32);
+        let (nonce, encrypted) = rest.split_at(12);
+        let mut key = [0u8; 32];
+        Hkdf::<Sha256>::new(Some(salt), &ephemeral_shared)
+            .expand(b"link_code_pairing_key_bundle_encryption_key", &mut key)
+            .unwrap();
+        let plaintext = Aes256Gcm::new(&key.into())
+            .decrypt(Nonce::from_slice(nonce), encrypted)
+            .unwrap();
+        assert_eq!(plaintext[..32], companion_identity);
+        assert_eq!(plaintext[32..64], primary_identity_pub);
+        let adv_random: [u8; 32] = plaintext[64..].try_into().unwrap();
+
+        server.push(
+            Node::new("iq")
+                .with_attr("type", "result")
+                .with_attr("id", finish.attrs["id"].clone()),
+        );
+        let identity_shared = primary_identity
+            .diffie_hellman(&PublicKey::from(companion_identity))
+            .to_bytes();
+        let expected = link_code_adv_secret(&ephemeral_shared, &identity_shared, &adv_random);
+        for _ in 0..50 {
+            if client.phone_linking.lock().unwrap().is_none() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(client.phone_linking.lock().unwrap().is_none());
+        let device = store.get_first_device().await.unwrap().unwrap();
+        assert_eq!(device.adv_secret_key, Some(expected));
+    }
+}
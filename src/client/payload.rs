@@ -0,0 +1,169 @@
+//! Client payload sent in the final Noise handshake message (login or registration).
+
+use super::builder::{ClientConfig, DeviceProps, UserAgent};
+use crate::proto::wa_companion_reg;
+use crate::proto::wa_wa6::client_payload::{self, user_agent, web_info};
+use crate::proto::wa_wa6::ClientPayload;
+use crate::store::Device;
+use crate::types::Jid;
+use md5::{Digest, Md5};
+use prost::Message;
+
+impl UserAgent {
+    fn to_proto(&self) -> client_payload::UserAgent {
+        client_payload::UserAgent {
+            platform: Some(self.platform as i32),
+            release_channel: Some(user_agent::ReleaseChannel::Release as i32),
+            app_version: Some(user_agent::AppVersion {
+                primary: Some(self.version.primary),
+                secondary: Some(self.version.secondary),
+                tertiary: Some(self.version.tertiary),
+                ..Default::default()
+            }),
+            mcc: Some(self.mcc.clone()),
+            mnc: Some(self.mnc.clone()),
+            os_version: Some(self.os_version.clone()),
+            manufacturer: Some(self.manufacturer.clone()),
+            device: Some(self.device.clone()),
+            os_build_number: Some(self.os_build_number.clone()),
+            locale_language_iso6391: Some(self.locale_language.clone()),
+            locale_country_iso31661_alpha2: Some(self.locale_country.clone()),
+            ..Default::default()
+        }
+    }
+}
+
+impl DeviceProps {
+    pub(crate) fn to_proto(&self) -> wa_companion_reg::DeviceProps {
+        wa_companion_reg::DeviceProps {
+            os: Some(self.os.clone()),
+            version: Some(wa_companion_reg::device_props::AppVersion {
+                primary: Some(0),
+                secondary: Some(1),
+                tertiary: Some(0),
+                ..Default::default()
+            }),
+            platform_type: Some(self.platform_type as i32),
+            require_full_sync: Some(self.require_full_sync),
+            history_sync_config: self.history_sync.as_ref().map(|h| {
+                wa_companion_reg::device_props::HistorySyncConfig {
+                    full_sync_days_limit: h.full_sync_days_limit,
+                    full_sync_size_mb_limit: h.full_sync_size_mb_limit,
+                    storage_quota_mb: h.storage_quota_mb,
+                    ..Default::default()
+                }
+            }),
+        }
+    }
+}
+
+impl ClientConfig {
+    /// Fields shared by login and registration payloads.
+    fn base_payload(&self) -> ClientPayload {
+        ClientPayload {
+            user_agent: Some(self.user_agent.to_proto()),
+            web_info: Some(client_payload::WebInfo {
+                web_sub_platform: Some(web_info::WebSubPlatform::WebBrowser as i32),
+                ..Default::default()
+            }),
+            connect_type: Some(client_payload::ConnectType::WifiUnknown as i32),
+            connect_reason: Some(client_payload::ConnectReason::UserActivated as i32),
+            ..Default::default()
+        }
+    }
+
+    /// Payload for logging in with an already paired device.
+    pub(crate) fn login_payload(&self, jid: &Jid) -> ClientPayload {
+        ClientPayload {
+            username: Some(jid.user_int()),
+            device: Some(jid.device as u32),
+            passive: Some(true),
+            pull: Some(true),
+            ..self.base_payload()
+        }
+    }
+
+    /// Payload for registering a new device; the server answers with a `pair-device` IQ.
+    /// `device` must hold the keys generated for pairing.
+    pub(crate) fn registration_payload(&self, device: &Device) -> ClientPayload {
+        let build_hash = Md5::digest(self.user_agent.version.to_string().as_bytes());
+        let registration = client_payload::DevicePairingRegistrationData {
+            e_regid: Some(device.registration_id.to_be_bytes().to_vec()),
+            e_keytype: Some(vec![crate::pairing::DJB_KEY_TYPE]),
+            e_ident: device.identity_key_pub.map(|k| k.to_vec()),
+            e_skey_id: Some(device.signed_prekey_id.to_be_bytes()[1..].to_vec()),
+            e_skey_val: device.signed_prekey_pub.map(|k| k.to_vec()),
+            e_skey_sig: device.signed_prekey_sig.map(|s| s.to_vec()),
+            build_hash: Some(build_hash.to_vec()),
+            device_props: Some(self.device_props.to_proto().encode_to_vec()),
+        };
+        ClientPayload {
+            passive: Some(false),
+            pull: Some(false),
+            device_pairing_data: Some(registration),
+            ..self.base_payload()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::builder::ClientVersion;
+
+    #[test]
+    fn login_payload_identifies_device() {
+        let mut config = ClientConfig::default();
+        config.user_agent.version = ClientVersion::new(2, 2413, 51);
+        let payload = config.login_payload(&Jid::new_ad("5511999", 0, 7, "s.whatsapp.net"));
+        assert_eq!(payload.username, Some(5_511_999));
+        assert_eq!(payload.device, Some(7));
+        assert_eq!(payload.passive, Some(true));
+        let ua = payload.user_agent.unwrap();
+        assert_eq!(ua.platform, Some(user_agent::Platform::Web as i32));
+        let version = ua.app_version.unwrap();
+        assert_eq!(
+            (version.primary, version.secondary, version.tertiary),
+            (Some(2), Some(2413), Some(51))
+        );
+    }
+
+    #[test]
+    fn registration_payload_carries_keys() {
+        let keys = crate::pairing::generate_pairing_keys();
+        let device = Device::with_keys(&keys);
+        let mut config = ClientConfig::default();
+        config.device_props.os = "Ops Bot".into();
+        let payload = config.registration_payload(&device);
+        assert_eq!(payload.username, None);
+        assert_eq!(payload.passive, Some(false));
+        let reg = payload.device_pairing_data.unwrap();
+        assert_eq!(reg.e_regid.unwrap(), keys.registration_id.to_be_bytes());
+        assert_eq!(reg.e_keytype.unwrap(), [5]);
+        assert_eq!(reg.e_ident.unwrap(), keys.identity_public);
+        assert_eq!(reg.e_skey_id.unwrap(), [0, 0, 1]);
+        assert_eq!(reg.e_skey_val.unwrap(), keys.signed_prekey_public);
+        assert_eq!(reg.e_skey_sig.unwrap(), keys.signed_prekey_signature);
+        assert_eq!(reg.build_hash.unwrap().len(), 16);
+        let props =
+            wa_companion_reg::DeviceProps::decode(reg.device_props.unwrap().as_slice()).unwrap();
+        assert_eq!(props.os.as_deref(), Some("Ops Bot"));
+    }
+
+    #[test]
+    fn device_props_to_proto() {
+        let props = DeviceProps {
+            os: "Ops Bot".into(),
+            platform_type: wa_companion_reg::device_props::PlatformType::Desktop,
+            require_full_sync: true,
+            history_sync: None,
+        };
+        let proto = props.to_proto();
+        assert_eq!(proto.os.as_deref(), Some("Ops Bot"));
+        assert_eq!(
+            proto.platform_type,
+            Some(wa_companion_reg::device_props::PlatformType::Desktop as i32)
+        );
+        assert_eq!(proto.require_full_sync, Some(true));
+    }
+}
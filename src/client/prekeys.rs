@@ -0,0 +1,368 @@
+//! Prekeys: ours are generated in batches, uploaded with the `encrypt` IQ and topped up
+//! whenever the server says it is running low; other devices' bundles are fetched with the
+//! same IQ to start sessions with them.
+
+use super::pair::child_bytes;
+use super::request::{InfoQuery, IqType};
+use super::Client;
+use crate::binary::Node;
+use crate::crypto::DJB_KEY_TYPE;
+use crate::error::{Error, SendError, StoreError};
+use crate::signal::{PreKey, PreKeyBundle, SignedPreKey, MAX_PREKEY_ID};
+use crate::types::Jid;
+use std::collections::HashMap;
+
+/// Upload more prekeys when the server has fewer than this many left.
+pub const MIN_PREKEY_COUNT: usize = 5;
+
+/// Prekeys uploaded per batch.
+pub const WANTED_PREKEY_COUNT: usize = 50;
+
+impl Client {
+    /// Number of our one-time prekeys the server still has.
+    pub(super) async fn get_server_prekey_count(&self) -> crate::Result<usize> {
+        let response = self
+            .send_iq(InfoQuery {
+                namespace: "encrypt",
+                iq_type: IqType::Get,
+                to: Jid::default_server(),
+                content: vec![Node::new("count")],
+            })
+            .await?;
+        response
+            .get_child_by_tag("count")
+            .and_then(|c| c.attrs.get("value"))
+            .and_then(|v| v.parse().ok())
+            .ok_or_else(|| SendError::Server("prekey count response without value".into()).into())
+    }
+
+    /// Upload a batch of one-time prekeys (generating what is missing) with our registration
+    /// ID, identity key and signed prekey.
+    pub(super) async fn upload_prekeys(&self) -> crate::Result<()> {
+        let _uploading = self.prekey_upload.lock().await;
+        let device = self.device.read().await.clone().ok_or(Error::NotLoggedIn)?;
+        let (Some(identity), Some(signed_prekey)) =
+            (device.identity_key_pub, device.signed_prekey())
+        else {
+            return Err(StoreError::IdentityNotFound.into());
+        };
+        let prekeys = self.get_or_generate_prekeys(WANTED_PREKEY_COUNT).await?;
+        let Some(last_id) = prekeys.last().map(|p| p.id) else {
+            return Ok(());
+        };
+        tracing::info!(count = prekeys.len(), "uploading prekeys");
+        self.send_iq(InfoQuery {
+            namespace: "encrypt",
+            iq_type: IqType::Set,
+            to: Jid::default_server(),
+            content: vec![
+                Node::new("registration")
+                    .with_content(device.registration_id.to_be_bytes().to_vec()),
+                Node::new("type").with_content(vec![DJB_KEY_TYPE]),
+                Node::new("identity").with_content(identity.to_vec()),
+                Node::new("list").with_children(prekeys.iter().map(prekey_node).collect()),
+                signed_prekey_node(&signed_prekey),
+            ],
+        })
+        .await?;
+        self.store.mark_prekeys_uploaded(last_id).await
+    }
+
+    /// Upload prekeys if the server is running low.
+    pub(super) async fn ensure_prekeys(&self) -> crate::Result<()> {
+        let count = self.get_server_prekey_count().await?;
+        if count < MIN_PREKEY_COUNT {
+            tracing::info!(count, "server is low on prekeys");
+            self.upload_prekeys().await?;
+        }
+        Ok(())
+    }
+
+    /// Whether we have the identity key and signed prekey an upload needs.
+    pub(super) async fn has_prekey_keys(&self) -> bool {
+        self.device
+            .read()
+            .await
+            .as_ref()
+            .is_some_and(|d| d.identity_key_pub.is_some() && d.signed_prekey().is_some())
+    }
+
+    /// Run [Client::ensure_prekeys] in the background (it waits for IQ responses, so it
+    /// cannot run on the receive loop).
+    pub(super) fn spawn_ensure_prekeys(&self) {
+        let client = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = client.ensure_prekeys().await {
+                tracing::warn!(error = %e, "failed to check or upload prekeys");
+            }
+        });
+    }
+
+    /// `<notification type="encrypt">`: a `<count value>` child is the number of prekeys the
+    /// server has left.
+    pub(super) fn handle_encrypt_notification(&self, node: &Node) -> crate::Result<()> {
+        let count = node
+            .get_child_by_tag("count")
+            .and_then(|c| c.attrs.get("value"))
+            .and_then(|v| v.parse::<usize>().ok());
+        let Some(count) = count else {
+            tracing::debug!("unhandled encrypt notification");
+            return Ok(());
+        };
+        if count < MIN_PREKEY_COUNT {
+            tracing::info!(count, "server is low on prekeys");
+            let client = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = client.upload_prekeys().await {
+                    tracing::warn!(error = %e, "failed to upload prekeys");
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Prekey bundles of remote devices, to start sessions with them. Devices the server
+    /// has no bundle for are logged and left out.
+    pub(super) async fn fetch_prekey_bundles(
+        &self,
+        devices: &[Jid],
+    ) -> crate::Result<HashMap<Jid, PreKeyBundle>> {
+        let users = devices
+            .iter()
+            .map(|d| {
+                Node::new("user")
+                    .with_attr("jid", d.to_string())
+                    .with_attr("reason", "identity")
+            })
+            .collect();
+        let response = self
+            .send_iq(InfoQuery {
+                namespace: "encrypt",
+                iq_type: IqType::Get,
+                to: Jid::default_server(),
+                content: vec![Node::new("key").with_children(users)],
+            })
+            .await?;
+        let users = response
+            .get_child_by_tag("list")
+            .map(Node::get_children)
+            .unwrap_or_default();
+        let mut bundles = HashMap::new();
+        for user in users.iter().filter(|u| u.tag == "user") {
+            let Some(jid) = user.attrs.get("jid").and_then(|j| j.parse::<Jid>().ok()) else {
+                continue;
+            };
+            match parse_bundle(&jid, user) {
+                Some(bundle) => {
+                    bundles.insert(jid, bundle);
+                }
+                None => tracing::warn!(%jid, "no usable prekey bundle"),
+            }
+        }
+        Ok(bundles)
+    }
+
+    /// A new one-time prekey handed out directly (in a retry receipt) rather than uploaded,
+    /// so it is saved as already uploaded.
+    pub(super) async fn generate_direct_prekey(&self) -> crate::Result<PreKey> {
+        let _uploading = self.prekey_upload.lock().await;
+        let prekey = PreKey::generate(self.store.last_prekey_id().await? % MAX_PREKEY_ID + 1);
+        self.store.put_prekey(&prekey).await?;
+        self.store.mark_prekeys_uploaded(prekey.id).await?;
+        Ok(prekey)
+    }
+
+    /// Prekeys saved but not uploaded yet, topped up with new ones to `count`.
+    async fn get_or_generate_prekeys(&self, count: usize) -> crate::Result<Vec<PreKey>> {
+        let mut prekeys = self.store.unuploaded_prekeys().await?;
+        let mut id = self.store.last_prekey_id().await?;
+        while prekeys.len() < count {
+            id = id % MAX_PREKEY_ID + 1;
+            let prekey = PreKey::generate(id);
+            self.store.put_prekey(&prekey).await?;
+            prekeys.push(prekey);
+        }
+        prekeys.truncate(count);
+        Ok(prekeys)
+    }
+}
+
+/// `<user>` of a prekey fetch response: registration ID, identity, signed prekey and
+/// (while the server has any left) a one-time prekey. None if anything is missing or the
+/// server answered with an `<error>`.
+pub(super) fn parse_bundle(jid: &Jid, user: &Node) -> Option<PreKeyBundle> {
+    let key = |node: &Node| -> Option<(u32, [u8; 32])> {
+        Some((
+            parse_prekey_id(child_bytes(node, "id")?)?,
+            child_bytes(node, "value")?.try_into().ok()?,
+        ))
+    };
+    let skey = user.get_child_by_tag("skey")?;
+    let (signed_prekey_id, signed_prekey) = key(skey)?;
+    let prekey = match user.get_child_by_tag("key") {
+        Some(node) => Some(key(node)?),
+        None => None,
+    };
+    Some(PreKeyBundle {
+        registration_id: u32::from_be_bytes(child_bytes(user, "registration")?.try_into().ok()?),
+        device_id: jid.device.into(),
+        prekey,
+        signed_prekey_id,
+        signed_prekey,
+        signed_prekey_signature: child_bytes(skey, "signature")?.try_into().ok()?,
+        identity_key: child_bytes(user, "identity")?.try_into().ok()?,
+    })
+}
+
+fn parse_prekey_id(bytes: &[u8]) -> Option<u32> {
+    let bytes: [u8; 3] = bytes.try_into().ok()?;
+    Some(u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]))
+}
+
+/// Prekey IDs go on the wire as 3 big-endian bytes.
+fn prekey_id_bytes(id: u32) -> Vec<u8> {
+    id.to_be_bytes()[1..].to_vec()
+}
+
+pub(super) fn prekey_node(prekey: &PreKey) -> Node {
+    Node::new("key").with_children(vec![
+        Node::new("id").with_content(prekey_id_bytes(prekey.id)),
+        Node::new("value").with_content(prekey.key_pair.public.to_vec()),
+    ])
+}
+
+pub(super) fn signed_prekey_node(prekey: &SignedPreKey) -> Node {
+    Node::new("skey").with_children(vec![
+        Node::new("id").with_content(prekey_id_bytes(prekey.id)),
+        Node::new("value").with_content(prekey.key_pair.public.to_vec()),
+        Node::new("signature").with_content(prekey.signature.to_vec()),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::NodeContent;
+    use crate::client::mock::{connected_client_with, paired_device, MockServer};
+    use crate::client::ReconnectPolicy;
+    use crate::crypto;
+    use crate::pairing::generate_pairing_keys;
+    use crate::signal::serialize_public_key;
+    use crate::store::{Device, MemoryStore, PreKeyStore};
+    use std::sync::Arc;
+
+    /// Connected client whose device has a signed prekey, so the login checks the prekey count.
+    async fn keyed_client() -> (Client, MockServer, Arc<MemoryStore>) {
+        let device = Device {
+            id: paired_device().id,
+            ..Device::with_keys(&generate_pairing_keys())
+        };
+        connected_client_with(device, |builder| {
+            builder.reconnect_policy(ReconnectPolicy::disabled())
+        })
+        .await
+    }
+
+    fn reply(iq: &Node, content: Vec<Node>) -> Node {
+        Node::new("iq")
+            .with_attr("type", "result")
+            .with_attr("id", iq.attrs["id"].clone())
+            .with_children(content)
+    }
+
+    fn bytes<'a>(node: &'a Node, tag: &str) -> &'a [u8] {
+        match &node.get_child_by_tag(tag).expect(tag).content {
+            NodeContent::Bytes(b) => b,
+            other => panic!("{tag}: {other:?}"),
+        }
+    }
+
+    /// Answer the upload IQ and wait for the store to mark the batch uploaded.
+    async fn accept_upload(server: &MockServer, store: &MemoryStore, upload: &Node) {
+        server.push(reply(upload, vec![]));
+        for _ in 0..50 {
+            if store.unuploaded_prekeys().await.unwrap().is_empty() {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("prekeys were not marked uploaded");
+    }
+
+    #[tokio::test]
+    async fn login_uploads_prekeys_when_server_is_low() {
+        let (client, server, store) = keyed_client().await;
+        let count = server.next_sent().await;
+        assert_eq!(count.attrs["xmlns"], "encrypt");
+        assert_eq!(count.attrs["type"], "get");
+        assert!(count.get_child_by_tag("count").is_some());
+        server.push(reply(
+            &count,
+            vec![Node::new("count").with_attr("value", "2")],
+        ));
+
+        let upload = server.next_sent().await;
+        assert_eq!(upload.attrs["xmlns"], "encrypt");
+        assert_eq!(upload.attrs["type"], "set");
+        let device = client.device.read().await.clone().unwrap();
+        assert_eq!(
+            bytes(&upload, "registration"),
+            device.registration_id.to_be_bytes()
+        );
+        assert_eq!(bytes(&upload, "type"), [DJB_KEY_TYPE]);
+        assert_eq!(bytes(&upload, "identity"), device.identity_key_pub.unwrap());
+        let keys = upload.get_child_by_tag("list").unwrap().get_children();
+        assert_eq!(keys.len(), WANTED_PREKEY_COUNT);
+        assert_eq!(bytes(&keys[0], "id"), [0, 0, 1]);
+        let first = store.get_prekey(1).await.unwrap().unwrap();
+        assert_eq!(bytes(&keys[0], "value"), first.key_pair.public);
+
+        let skey = upload.get_child_by_tag("skey").unwrap();
+        let value = bytes(skey, "value");
+        assert_eq!(value, device.signed_prekey_pub.unwrap());
+        assert!(crypto::verify(
+            &device.identity_key_pub.unwrap(),
+            &serialize_public_key(value.try_into().unwrap()),
+            bytes(skey, "signature").try_into().unwrap()
+        ));
+
+        accept_upload(&server, &store, &upload).await;
+        assert_eq!(
+            store.last_prekey_id().await.unwrap(),
+            WANTED_PREKEY_COUNT as u32
+        );
+    }
+
+    #[tokio::test]
+    async fn low_count_notification_tops_up_prekeys() {
+        let (_client, server, store) = keyed_client().await;
+        let count = server.next_sent().await;
+        server.push(reply(
+            &count,
+            vec![Node::new("count").with_attr("value", "50")],
+        ));
+
+        server.push(
+            Node::new("notification")
+                .with_attr("type", "encrypt")
+                .with_attr("id", "n-1")
+                .with_children(vec![Node::new("count").with_attr("value", "1")]),
+        );
+        let upload = server.next_sent_skipping_acks().await;
+        assert_eq!(upload.attrs["xmlns"], "encrypt");
+        let keys = upload.get_child_by_tag("list").unwrap().get_children();
+        assert_eq!(bytes(&keys[0], "id"), [0, 0, 1]);
+        accept_upload(&server, &store, &upload).await;
+
+        // The next batch continues after the uploaded IDs.
+        server.push(
+            Node::new("notification")
+                .with_attr("type", "encrypt")
+                .with_attr("id", "n-2")
+                .with_children(vec![Node::new("count").with_attr("value", "0")]),
+        );
+        let upload = server.next_sent_skipping_acks().await;
+        let keys = upload.get_child_by_tag("list").unwrap().get_children();
+        assert_eq!(bytes(&keys[0], "id"), [0, 0, 51]);
+    }
+}
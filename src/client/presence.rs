@@ -0,0 +1,287 @@
+//! Presence (whether we and our contacts are online) and chat state (typing and recording
+//! indicators).
+
+use super::Client;
+use crate::binary::Node;
+use crate::error::Error;
+use crate::events::{ChatPresenceEvent, Event, PresenceEvent};
+use crate::types::{
+    ChatPresence, ChatPresenceMedia, Jid, Presence, BROADCAST_SERVER, GROUP_SERVER,
+};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, UNIX_EPOCH};
+
+impl Client {
+    /// Go online or offline. Our contacts see it, and while we're available the messages we
+    /// receive are reported as delivered rather than `inactive`; the phone also stops
+    /// showing its own notifications. Fails with [Error::NoPushName] until a push name is
+    /// set with [Client::set_push_name].
+    pub async fn send_presence(&self, presence: Presence) -> crate::Result<()> {
+        let push_name = self
+            .device
+            .read()
+            .await
+            .as_ref()
+            .and_then(|d| d.push_name.clone())
+            .filter(|name| !name.is_empty())
+            .ok_or(Error::NoPushName)?;
+        let node = Node::new("presence")
+            .with_attr("name", push_name)
+            .with_attr("type", presence.as_str());
+        self.send_node(&node).await?;
+        self.send_active_receipts
+            .store(presence == Presence::Available, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Ask for `jid`'s online status: the server sends it as [Event::Presence] now and
+    /// whenever it changes, for as long as we're connected. The server only answers once our
+    /// own presence is available (see [Client::send_presence]).
+    pub async fn subscribe_presence(&self, jid: &Jid) -> crate::Result<()> {
+        let node = Node::new("presence")
+            .with_attr("type", "subscribe")
+            .with_attr("to", jid.to_string());
+        self.send_node(&node).await
+    }
+
+    /// Emit a `<presence>` as [Event::Presence].
+    pub(super) async fn handle_presence(&self, node: &Node) {
+        let Some(from) = node.attrs.get("from").and_then(|v| v.parse::<Jid>().ok()) else {
+            tracing::warn!("presence without sender");
+            return;
+        };
+        let unavailable = match node.attrs.get("type").map(String::as_str) {
+            None | Some("available") => false,
+            Some("unavailable") => true,
+            Some(kind) => {
+                tracing::debug!(kind, "unhandled presence");
+                return;
+            }
+        };
+        // `deny` when they hide their last seen.
+        let last_seen = node
+            .attrs
+            .get("last")
+            .and_then(|t| t.parse().ok())
+            .map(|t| UNIX_EPOCH + Duration::from_secs(t));
+        self.dispatch_event(Event::Presence(PresenceEvent {
+            from,
+            unavailable,
+            last_seen,
+        }))
+        .await;
+    }
+
+    /// Tell `jid` (a user or a group) that we're typing, or recording a voice message with
+    /// [ChatPresenceMedia::Audio], or that we stopped. `media` only matters for
+    /// [ChatPresence::Composing].
+    ///
+    /// ```ignore
+    /// client.send_chat_presence(&chat, ChatPresence::Composing, ChatPresenceMedia::Text).await?;
+    /// ```
+    pub async fn send_chat_presence(
+        &self,
+        jid: &Jid,
+        state: ChatPresence,
+        media: ChatPresenceMedia,
+    ) -> crate::Result<()> {
+        let own_id = self.get_own_id().await.ok_or(Error::NotLoggedIn)?;
+        let mut child = Node::new(state.as_str());
+        if state == ChatPresence::Composing && media != ChatPresenceMedia::Text {
+            child = child.with_attr("media", media.as_str());
+        }
+        let node = Node::new("chatstate")
+            .with_attr("from", own_id.to_string())
+            .with_attr("to", jid.to_string())
+            .with_children(vec![child]);
+        self.send_node(&node).await
+    }
+
+    /// Emit a `<chatstate>` as [Event::ChatPresence].
+    pub(super) async fn handle_chat_state(&self, node: &Node) {
+        let jid_attr = |key: &str| node.attrs.get(key).and_then(|v| v.parse::<Jid>().ok());
+        let Some(from) = jid_attr("from") else {
+            tracing::warn!("chatstate without sender");
+            return;
+        };
+        let is_group = from.server == GROUP_SERVER || from.server == BROADCAST_SERVER;
+        let sender = match jid_attr("participant") {
+            Some(participant) if is_group => participant,
+            _ if is_group => {
+                tracing::warn!(%from, "group chatstate without participant");
+                return;
+            }
+            _ => from.clone(),
+        };
+        let Some(child) = node.get_children().first() else {
+            return;
+        };
+        let Some(state) = ChatPresence::from_tag(&child.tag) else {
+            tracing::debug!(tag = %child.tag, "unhandled chatstate");
+            return;
+        };
+        let media = child.attrs.get("media").map(String::as_str).unwrap_or("");
+        self.dispatch_event(Event::ChatPresence(ChatPresenceEvent {
+            chat: from.to_non_ad(),
+            sender,
+            is_group,
+            state,
+            media: ChatPresenceMedia::from_attr(media),
+        }))
+        .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::{connected_client, jid};
+    use crate::store::MemoryStore;
+    use futures::StreamExt;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn chat_presence_is_sent() {
+        let (client, server, _) = connected_client().await;
+        let chat = jid("5511999@s.whatsapp.net");
+        client
+            .send_chat_presence(&chat, ChatPresence::Composing, ChatPresenceMedia::Audio)
+            .await
+            .unwrap();
+        let node = server.next_sent().await;
+        assert_eq!(node.tag, "chatstate");
+        assert_eq!(node.attrs["from"], "123:4@s.whatsapp.net");
+        assert_eq!(node.attrs["to"], chat.to_string());
+        let composing = &node.get_children()[0];
+        assert_eq!(composing.tag, "composing");
+        assert_eq!(composing.attrs["media"], "audio");
+
+        client
+            .send_chat_presence(&chat, ChatPresence::Paused, ChatPresenceMedia::Audio)
+            .await
+            .unwrap();
+        let node = server.next_sent().await;
+        let paused = &node.get_children()[0];
+        assert_eq!(paused.tag, "paused");
+        assert!(paused.attrs.is_empty());
+
+        let logged_out = Client::new(Arc::new(MemoryStore::new()));
+        assert!(matches!(
+            logged_out
+                .send_chat_presence(&chat, ChatPresence::Paused, ChatPresenceMedia::Text)
+                .await,
+            Err(Error::NotLoggedIn)
+        ));
+    }
+
+    #[tokio::test]
+    async fn incoming_chat_states_are_emitted() {
+        let (client, server, _) = connected_client().await;
+        let mut events = client.events();
+        let group = jid("120363001@g.us");
+        server.push(
+            Node::new("chatstate")
+                .with_attr("from", "5511999:2@s.whatsapp.net")
+                .with_children(vec![Node::new("composing")]),
+        );
+        server.push(
+            Node::new("chatstate")
+                .with_attr("from", group.to_string())
+                .with_attr("participant", "5522222@s.whatsapp.net")
+                .with_children(vec![Node::new("composing").with_attr("media", "audio")]),
+        );
+        let mut received = Vec::new();
+        while received.len() < 2 {
+            match tokio::time::timeout(Duration::from_secs(1), events.next()).await {
+                Ok(Some(Event::ChatPresence(evt))) => received.push(evt),
+                Ok(Some(_)) => continue,
+                _ => panic!("no chat presence event"),
+            }
+        }
+        assert_eq!(received[0].chat, jid("5511999@s.whatsapp.net"));
+        assert_eq!(received[0].sender, jid("5511999:2@s.whatsapp.net"));
+        assert!(!received[0].is_group);
+        assert_eq!(received[0].state, ChatPresence::Composing);
+        assert_eq!(received[0].media, ChatPresenceMedia::Text);
+        assert_eq!(received[1].chat, group);
+        assert_eq!(received[1].sender, jid("5522222@s.whatsapp.net"));
+        assert!(received[1].is_group);
+        assert_eq!(received[1].media, ChatPresenceMedia::Audio);
+    }
+
+    #[tokio::test]
+    async fn presence_needs_a_push_name() {
+        let (client, server, _) = connected_client().await;
+        assert!(matches!(
+            client.send_presence(Presence::Available).await,
+            Err(Error::NoPushName)
+        ));
+
+        client.set_push_name("Me").await.unwrap();
+        client.send_presence(Presence::Available).await.unwrap();
+        let node = server.next_sent().await;
+        assert_eq!(node.tag, "presence");
+        assert_eq!(node.attrs["name"], "Me");
+        assert_eq!(node.attrs["type"], "available");
+        assert!(client.send_active_receipts.load(Ordering::SeqCst));
+        assert_eq!(
+            client
+                .store
+                .get_first_device()
+                .await
+                .unwrap()
+                .unwrap()
+                .push_name
+                .as_deref(),
+            Some("Me")
+        );
+
+        client.send_presence(Presence::Unavailable).await.unwrap();
+        assert_eq!(server.next_sent().await.attrs["type"], "unavailable");
+        assert!(!client.send_active_receipts.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn presence_subscriptions() {
+        let (client, server, _) = connected_client().await;
+        let mut events = client.events();
+        let contact = jid("5511999@s.whatsapp.net");
+        client.subscribe_presence(&contact).await.unwrap();
+        let node = server.next_sent().await;
+        assert_eq!(node.tag, "presence");
+        assert_eq!(node.attrs["type"], "subscribe");
+        assert_eq!(node.attrs["to"], contact.to_string());
+
+        server.push(Node::new("presence").with_attr("from", contact.to_string()));
+        server.push(
+            Node::new("presence")
+                .with_attr("from", contact.to_string())
+                .with_attr("type", "unavailable")
+                .with_attr("last", "1700000000"),
+        );
+        server.push(
+            Node::new("presence")
+                .with_attr("from", contact.to_string())
+                .with_attr("type", "unavailable")
+                .with_attr("last", "deny"),
+        );
+        let mut received = Vec::new();
+        while received.len() < 3 {
+            match tokio::time::timeout(Duration::from_secs(1), events.next()).await {
+                Ok(Some(Event::Presence(evt))) => received.push(evt),
+                Ok(Some(_)) => continue,
+                _ => panic!("no presence event"),
+            }
+        }
+        assert_eq!(received[0].from, contact);
+        assert!(!received[0].unavailable);
+        assert_eq!(received[0].last_seen, None);
+        assert!(received[1].unavailable);
+        assert_eq!(
+            received[1].last_seen,
+            Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        );
+        assert!(received[2].unavailable);
+        assert_eq!(received[2].last_seen, None);
+    }
+}
@@ -0,0 +1,279 @@
+//! Receipts: `<receipt>` stanzas from the server, dispatched by their `type`, and the ones
+//! we send for messages we received.
+
+use super::Client;
+use crate::binary::Node;
+use crate::events::{Event, ReceiptEvent};
+use crate::types::{
+    Jid, MessageId, ReceiptType, BROADCAST_SERVER, DEFAULT_USER_SERVER, GROUP_SERVER,
+    HIDDEN_USER_SERVER, NEWSLETTER_SERVER,
+};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+impl Client {
+    /// Mark messages in `chat` as read at `timestamp`. `sender` is who sent them in a group
+    /// (ignored for direct chats). Newsletter messages get `read-self` receipts.
+    ///
+    /// ```ignore
+    /// client.mark_read(&[evt.id.clone()], &evt.chat, Some(&evt.sender), SystemTime::now()).await?;
+    /// ```
+    pub async fn mark_read(
+        &self,
+        ids: &[MessageId],
+        chat: &Jid,
+        sender: Option<&Jid>,
+        timestamp: SystemTime,
+    ) -> crate::Result<()> {
+        let receipt_type = if chat.server == NEWSLETTER_SERVER {
+            ReceiptType::ReadSelf
+        } else {
+            ReceiptType::Read
+        };
+        self.send_receipt(ids, chat, sender, timestamp, receipt_type)
+            .await
+    }
+
+    /// Send a receipt of any type for messages we received, e.g. [ReceiptType::Played] for
+    /// a voice message or [ReceiptType::ReadSelf] to mark messages read on our devices only.
+    /// Does nothing for an empty `ids`.
+    pub async fn send_receipt(
+        &self,
+        ids: &[MessageId],
+        chat: &Jid,
+        sender: Option<&Jid>,
+        timestamp: SystemTime,
+        receipt_type: ReceiptType,
+    ) -> crate::Result<()> {
+        let Some((first, rest)) = ids.split_first() else {
+            return Ok(());
+        };
+        let t = timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mut node = Node::new("receipt")
+            .with_attr("id", first.as_str())
+            .with_attr("to", chat.to_string())
+            .with_attr("t", t.to_string());
+        if receipt_type != ReceiptType::Delivered {
+            node = node.with_attr("type", receipt_type.as_str());
+        }
+        let is_user_chat = chat.server == DEFAULT_USER_SERVER || chat.server == HIDDEN_USER_SERVER;
+        if let Some(sender) = sender.filter(|_| !is_user_chat) {
+            node = node.with_attr("participant", sender.to_non_ad().to_string());
+        }
+        if !rest.is_empty() {
+            let items = rest
+                .iter()
+                .map(|id| Node::new("item").with_attr("id", id.as_str()))
+                .collect();
+            node = node.with_children(vec![Node::new("list").with_children(items)]);
+        }
+        self.send_node(&node).await
+    }
+
+    /// Emit a `<receipt>` as [Event::Receipt], resending the message for retry receipts.
+    pub(super) async fn handle_receipt(&self, node: &Node) {
+        match self.parse_receipt(node).await {
+            Some(receipt) => {
+                if receipt.receipt_type == ReceiptType::Retry {
+                    // Resending may fetch prekeys, which waits for an IQ response from the
+                    // receive loop.
+                    let client = self.clone();
+                    let node = node.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = client.handle_retry_receipt(&node).await {
+                            tracing::warn!(id = ?node.attrs.get("id"), error = %e, "failed to handle retry receipt");
+                        }
+                    });
+                }
+                self.dispatch_event(Event::Receipt(receipt)).await;
+            }
+            None => tracing::warn!(id = ?node.attrs.get("id"), "malformed receipt"),
+        }
+    }
+
+    async fn parse_receipt(&self, node: &Node) -> Option<ReceiptEvent> {
+        let jid_attr = |key: &str| node.attrs.get(key).and_then(|v| v.parse::<Jid>().ok());
+        let from = jid_attr("from")?;
+        let participant = jid_attr("participant");
+        let is_group = from.server == GROUP_SERVER || from.server == BROADCAST_SERVER;
+        let device = participant.as_ref().filter(|_| is_group).unwrap_or(&from);
+        let is_from_me = self.is_own_user(device).await;
+        let chat = match jid_attr("recipient") {
+            _ if is_group => from.clone(),
+            Some(recipient) if is_from_me => recipient.to_non_ad(),
+            _ => from.to_non_ad(),
+        };
+        let mut ids = vec![node.attrs.get("id")?.clone()];
+        if let Some(list) = node.get_child_by_tag("list") {
+            ids.extend(
+                list.get_children()
+                    .iter()
+                    .filter(|item| item.tag == "item")
+                    .filter_map(|item| item.attrs.get("id").cloned()),
+            );
+        }
+        Some(ReceiptEvent {
+            chat,
+            from,
+            participant,
+            ids,
+            timestamp: node
+                .attrs
+                .get("t")
+                .and_then(|t| t.parse().ok())
+                .map(|t| UNIX_EPOCH + Duration::from_secs(t))
+                .unwrap_or_else(SystemTime::now),
+            receipt_type: ReceiptType::from_attr(
+                node.attrs.get("type").map(String::as_str).unwrap_or(""),
+            ),
+            is_group,
+            is_from_me,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::{connected_client, jid};
+    use crate::events::EventStream;
+    use futures::StreamExt;
+
+    async fn next_receipt(events: &mut EventStream) -> ReceiptEvent {
+        loop {
+            match tokio::time::timeout(Duration::from_secs(1), events.next()).await {
+                Ok(Some(Event::Receipt(evt))) => return evt,
+                Ok(Some(_)) => continue,
+                _ => panic!("no receipt event"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn read_receipts_batch_message_ids() {
+        let (client, server, _) = connected_client().await;
+        let read_at = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let ids = ["3EB0AA".to_string(), "3EB0BB".into(), "3EB0CC".into()];
+        let group = jid("120363001@g.us");
+        client
+            .mark_read(
+                &ids,
+                &group,
+                Some(&jid("5511999:2@s.whatsapp.net")),
+                read_at,
+            )
+            .await
+            .unwrap();
+        let receipt = server.next_sent().await;
+        assert_eq!(receipt.tag, "receipt");
+        assert_eq!(receipt.attrs["id"], "3EB0AA");
+        assert_eq!(receipt.attrs["type"], "read");
+        assert_eq!(receipt.attrs["to"], group.to_string());
+        assert_eq!(receipt.attrs["t"], "1700000000");
+        assert_eq!(receipt.attrs["participant"], "5511999@s.whatsapp.net");
+        let items: Vec<_> = receipt
+            .get_child_by_tag("list")
+            .unwrap()
+            .get_children()
+            .iter()
+            .map(|item| item.attrs["id"].as_str())
+            .collect();
+        assert_eq!(items, ["3EB0BB", "3EB0CC"]);
+
+        let chat = jid("5511999@s.whatsapp.net");
+        let sender = jid("5511999:2@s.whatsapp.net");
+        client
+            .send_receipt(
+                &ids[..1],
+                &chat,
+                Some(&sender),
+                read_at,
+                ReceiptType::Played,
+            )
+            .await
+            .unwrap();
+        let receipt = server.next_sent().await;
+        assert_eq!(receipt.attrs["type"], "played");
+        assert!(!receipt.attrs.contains_key("participant"));
+        assert!(receipt.get_child_by_tag("list").is_none());
+
+        client
+            .mark_read(&ids[..1], &jid("120363002@newsletter"), None, read_at)
+            .await
+            .unwrap();
+        assert_eq!(server.next_sent().await.attrs["type"], "read-self");
+    }
+
+    #[tokio::test]
+    async fn incoming_receipts_are_parsed_and_acked() {
+        let (client, server, _) = connected_client().await;
+        let mut events = client.events();
+        let group = jid("120363001@g.us");
+        let participant = jid("5511999:2@s.whatsapp.net");
+        server.push(
+            Node::new("receipt")
+                .with_attr("id", "3EB0AA")
+                .with_attr("from", group.to_string())
+                .with_attr("participant", participant.to_string())
+                .with_attr("type", "read")
+                .with_attr("t", "1700000000")
+                .with_children(vec![Node::new("list").with_children(vec![
+                    Node::new("item").with_attr("id", "3EB0BB"),
+                    Node::new("item").with_attr("id", "3EB0CC"),
+                ])]),
+        );
+        let receipt = next_receipt(&mut events).await;
+        assert_eq!(receipt.chat, group);
+        assert_eq!(receipt.from, group);
+        assert_eq!(receipt.participant, Some(participant.clone()));
+        assert_eq!(receipt.ids, ["3EB0AA", "3EB0BB", "3EB0CC"]);
+        assert_eq!(receipt.receipt_type, ReceiptType::Read);
+        assert_eq!(
+            receipt.timestamp,
+            UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+        );
+        assert!(receipt.is_group);
+        assert!(!receipt.is_from_me);
+        let ack = server.next_sent().await;
+        assert_eq!(ack.tag, "ack");
+        assert_eq!(ack.attrs["class"], "receipt");
+        assert_eq!(ack.attrs["id"], "3EB0AA");
+        assert_eq!(ack.attrs["to"], group.to_string());
+        assert_eq!(ack.attrs["participant"], participant.to_string());
+        assert_eq!(ack.attrs["type"], "read");
+
+        // Plain delivery receipt for a direct chat.
+        server.push(
+            Node::new("receipt")
+                .with_attr("id", "3EB0DD")
+                .with_attr("from", participant.to_string()),
+        );
+        let receipt = next_receipt(&mut events).await;
+        assert_eq!(receipt.chat, jid("5511999@s.whatsapp.net"));
+        assert_eq!(receipt.participant, None);
+        assert_eq!(receipt.receipt_type, ReceiptType::Delivered);
+        assert!(!receipt.is_group);
+        let ack = server.next_sent().await;
+        assert_eq!(ack.attrs["to"], participant.to_string());
+        assert!(!ack.attrs.contains_key("type"));
+
+        // Our phone read messages from someone else.
+        server.push(
+            Node::new("receipt")
+                .with_attr("id", "3EB0EE")
+                .with_attr("from", "123@s.whatsapp.net")
+                .with_attr("recipient", "5522222@s.whatsapp.net")
+                .with_attr("type", "read-self"),
+        );
+        let receipt = next_receipt(&mut events).await;
+        assert!(receipt.is_from_me);
+        assert_eq!(receipt.chat, jid("5522222@s.whatsapp.net"));
+        assert_eq!(receipt.receipt_type, ReceiptType::ReadSelf);
+        assert_eq!(
+            server.next_sent().await.attrs["recipient"],
+            "5522222@s.whatsapp.net"
+        );
+    }
+}
@@ -0,0 +1,793 @@
+//! Receiving messages: every `<enc>` of a `<message>` node is decrypted (pairwise `pkmsg` /
+//! `msg`, or with the sender's key for group `skmsg`), unpadded, decoded into a [Message]
+//! and emitted as [Event::Message].
+
+use super::Client;
+use crate::binary::{Node, NodeContent};
+use crate::error::{Error, SignalError, StoreError};
+use crate::events::{Event, MessageEvent, UndecryptableMessageEvent};
+use crate::proto::wa_e2e::{self, Message};
+use crate::signal::{
+    PreKeySignalMessage, ProtocolAddress, SenderKeyDistributionMessage, SenderKeyMessage,
+    SenderKeyName, SenderKeyRecord, SessionRecord, SignalMessage,
+};
+use crate::types::{
+    DeviceSentMeta, EditAttribute, Jid, MessageId, ReceiptType, BROADCAST_SERVER, GROUP_SERVER,
+};
+use prost::Message as _;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, SystemTime};
+
+/// Who sent a message and where, from the attributes of its `<message>` node.
+#[derive(Clone, Debug)]
+struct MessageInfo {
+    chat: Jid,
+    sender: Jid,
+    sender_alt: Option<Jid>,
+    id: MessageId,
+    timestamp: SystemTime,
+    push_name: Option<String>,
+    is_group: bool,
+    is_from_me: bool,
+    category: Option<String>,
+    edit: EditAttribute,
+}
+
+impl Client {
+    pub(super) async fn handle_encrypted_message(&self, node: &Node) {
+        let Some(info) = self.parse_message_info(node).await else {
+            tracing::warn!(id = ?node.attrs.get("id"), "malformed message node");
+            return;
+        };
+        let encs: Vec<&Node> = node
+            .get_children()
+            .iter()
+            .filter(|c| c.tag == "enc")
+            .collect();
+        if encs.is_empty() && node.get_child_by_tag("unavailable").is_some() {
+            self.dispatch_undecryptable(info, true, String::new()).await;
+            return;
+        }
+        if encs.is_empty() {
+            return;
+        }
+        let mut failure = None;
+        for enc in encs {
+            match self.decrypt_enc(&info, enc).await {
+                Ok(raw) => {
+                    if let Err(e) = self.handle_plaintext(&info, raw).await {
+                        tracing::warn!(id = %info.id, error = %e, "failed to handle decrypted message");
+                    }
+                }
+                Err(Error::Signal(SignalError::DuplicateMessage(_))) => {
+                    tracing::debug!(id = %info.id, "ignoring duplicate message");
+                }
+                Err(e) => {
+                    tracing::warn!(id = %info.id, sender = %info.sender, error = %e, "failed to decrypt message");
+                    failure.get_or_insert(e);
+                }
+            }
+        }
+        match failure {
+            Some(e) => {
+                self.dispatch_undecryptable(info, false, e.to_string())
+                    .await;
+                // Generating keys for the receipt waits for prekey uploads, which wait for
+                // IQ responses from the receive loop.
+                let client = self.clone();
+                let node = node.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = client.send_retry_receipt(&node).await {
+                        tracing::warn!(id = ?node.attrs.get("id"), error = %e, "failed to send retry receipt");
+                    }
+                });
+            }
+            None => {
+                self.clear_retry_count(&info.id);
+                if self.config.automatic_receipts {
+                    if let Err(e) = self.send_delivery_receipt(&info).await {
+                        tracing::warn!(id = %info.id, error = %e, "failed to send delivery receipt");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Tell the sender we got a message: `sender` receipts for messages from our other
+    /// devices, `peer_msg` between our devices, otherwise plain delivery receipts while our
+    /// presence is available and `inactive` ones (delivered, but not seen by anyone) before.
+    async fn send_delivery_receipt(&self, info: &MessageInfo) -> crate::Result<()> {
+        let receipt_type = if info.category.as_deref() == Some("peer") {
+            ReceiptType::PeerMsg
+        } else if info.is_from_me {
+            ReceiptType::Sender
+        } else if self.send_active_receipts.load(Ordering::SeqCst) {
+            ReceiptType::Delivered
+        } else {
+            ReceiptType::Inactive
+        };
+        let mut receipt = Node::new("receipt").with_attr("id", info.id.as_str());
+        if receipt_type != ReceiptType::Delivered {
+            receipt = receipt.with_attr("type", receipt_type.as_str());
+        }
+        if info.is_group {
+            receipt = receipt
+                .with_attr("to", info.chat.to_string())
+                .with_attr("participant", info.sender.to_string());
+        } else if info.is_from_me && receipt_type != ReceiptType::PeerMsg {
+            receipt = receipt
+                .with_attr("to", info.sender.to_string())
+                .with_attr("recipient", info.chat.to_string());
+        } else {
+            receipt = receipt.with_attr("to", info.chat.to_string());
+        }
+        self.send_node(&receipt).await
+    }
+
+    async fn dispatch_undecryptable(&self, info: MessageInfo, is_unavailable: bool, error: String) {
+        self.dispatch_event(Event::UndecryptableMessage(UndecryptableMessageEvent {
+            chat: info.chat,
+            sender: info.sender,
+            sender_alt: info.sender_alt,
+            id: info.id,
+            timestamp: info.timestamp,
+            push_name: info.push_name,
+            is_group: info.is_group,
+            is_from_me: info.is_from_me,
+            is_unavailable,
+            error,
+        }))
+        .await;
+    }
+
+    async fn parse_message_info(&self, node: &Node) -> Option<MessageInfo> {
+        let attr = |key: &str| node.attrs.get(key).filter(|v| !v.is_empty());
+        let jid_attr = |key: &str| attr(key).and_then(|v| v.parse::<Jid>().ok());
+        let from = jid_attr("from")?;
+        self.get_own_id().await?;
+        let from_me = self.is_own_user(&from).await;
+
+        let is_group = from.server == GROUP_SERVER || from.server == BROADCAST_SERVER;
+        let (chat, sender, sender_alt) = if is_group {
+            let alt = jid_attr("participant_pn").or_else(|| jid_attr("participant_lid"));
+            (from, jid_attr("participant")?, alt)
+        } else {
+            let alt = jid_attr("sender_pn").or_else(|| jid_attr("sender_lid"));
+            let chat = match jid_attr("recipient") {
+                Some(recipient) if from_me => recipient,
+                _ => from.to_non_ad(),
+            };
+            (chat, from, alt)
+        };
+        Some(MessageInfo {
+            is_from_me: self.is_own_user(&sender).await,
+            chat,
+            sender,
+            sender_alt,
+            id: attr("id")?.clone(),
+            timestamp: attr("t")
+                .and_then(|t| t.parse().ok())
+                .map(|t| SystemTime::UNIX_EPOCH + Duration::from_secs(t))
+                .unwrap_or_else(SystemTime::now),
+            push_name: attr("notify").cloned(),
+            is_group,
+            category: attr("category").cloned(),
+            edit: attr("edit")
+                .map(|e| EditAttribute::from_attr(e))
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Decrypt and unpad the payload of an `<enc>`.
+    async fn decrypt_enc(&self, info: &MessageInfo, enc: &Node) -> crate::Result<Vec<u8>> {
+        let NodeContent::Bytes(ciphertext) = &enc.content else {
+            return Err(SignalError::InvalidMessage("enc without content".into()).into());
+        };
+        let enc_type = enc.attrs.get("type").map(String::as_str).unwrap_or("");
+        let signal = self.signal_lock.lock().await;
+        let padded = match enc_type {
+            "pkmsg" => {
+                self.decrypt_prekey_message(&info.sender, ciphertext)
+                    .await?
+            }
+            "msg" => {
+                self.decrypt_signal_message(&info.sender, ciphertext)
+                    .await?
+            }
+            "skmsg" => {
+                self.decrypt_group_message(&info.chat, &info.sender, ciphertext)
+                    .await?
+            }
+            other => {
+                return Err(
+                    SignalError::InvalidMessage(format!("unknown enc type {other:?}")).into(),
+                )
+            }
+        };
+        drop(signal);
+        if enc.attrs.get("v").is_some_and(|v| v == "3") {
+            Ok(padded)
+        } else {
+            unpad_message(padded)
+        }
+    }
+
+    /// Decode a decrypted payload, save the sender keys it carries and emit the message.
+    async fn handle_plaintext(&self, info: &MessageInfo, raw: Vec<u8>) -> crate::Result<()> {
+        let message = Message::decode(raw.as_slice())
+            .map_err(|e| SignalError::InvalidMessage(format!("message: {}", e)))?;
+
+        for distribution in [
+            &message.sender_key_distribution_message,
+            &message.fast_ratchet_key_sender_key_distribution_message,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            self.process_sender_key_distribution(&info.sender, distribution)
+                .await?;
+        }
+        // Payloads that only hand out a sender key aren't messages of their own.
+        let distribution_only = Message {
+            sender_key_distribution_message: message.sender_key_distribution_message.clone(),
+            fast_ratchet_key_sender_key_distribution_message: message
+                .fast_ratchet_key_sender_key_distribution_message
+                .clone(),
+            message_context_info: message.message_context_info.clone(),
+            ..Default::default()
+        };
+        if message == distribution_only {
+            return Ok(());
+        }
+
+        let (message, device_sent) = match message.device_sent_message {
+            Some(sent) if info.is_from_me => {
+                let meta = DeviceSentMeta {
+                    destination_jid: sent.destination_jid().to_string(),
+                    phash: sent.phash.clone(),
+                };
+                (sent.message.unwrap_or_default(), Some(meta))
+            }
+            _ => (Box::new(message), None),
+        };
+        let info = info.clone();
+        self.dispatch_event(Event::Message(MessageEvent {
+            chat: info.chat,
+            sender: info.sender,
+            sender_alt: info.sender_alt,
+            id: info.id,
+            timestamp: info.timestamp,
+            push_name: info.push_name,
+            is_group: info.is_group,
+            is_from_me: info.is_from_me,
+            category: info.category,
+            edit: info.edit,
+            device_sent,
+            message,
+            raw,
+        }))
+        .await;
+        Ok(())
+    }
+
+    /// Decrypt the first message(s) of a session the sender started with our prekeys.
+    async fn decrypt_prekey_message(
+        &self,
+        sender: &Jid,
+        ciphertext: &[u8],
+    ) -> crate::Result<Vec<u8>> {
+        let message = PreKeySignalMessage::deserialize(ciphertext)?;
+        let address = ProtocolAddress::from(sender);
+        let device = self.device.read().await.clone().ok_or(Error::NotLoggedIn)?;
+        let identity = device
+            .identity_key_pair()
+            .ok_or(StoreError::IdentityNotFound)?;
+        if !self
+            .store
+            .is_trusted_identity(&address, &message.identity_key)
+            .await?
+        {
+            // The device was reinstalled: its old sessions are useless.
+            tracing::info!(%address, "identity key changed, replacing session");
+            self.store.delete_session(&address).await?;
+        }
+
+        let signed_prekey = match self
+            .store
+            .get_signed_prekey(message.signed_prekey_id)
+            .await?
+        {
+            Some(key) => key,
+            // Devices paired before signed prekeys were stored keep theirs on the device only.
+            None => device
+                .signed_prekey()
+                .filter(|key| key.id == message.signed_prekey_id)
+                .ok_or_else(|| {
+                    SignalError::InvalidKey(format!(
+                        "unknown signed prekey {}",
+                        message.signed_prekey_id
+                    ))
+                })?,
+        };
+        let mut record = self.load_session(&address).await?.unwrap_or_default();
+        // The one-time prekey is gone once a session was built with it, which is fine for
+        // later messages of that session.
+        let one_time_prekey = match message.prekey_id {
+            Some(id) if !record.has_session_with_base_key(&message.base_key) => Some(
+                self.store
+                    .get_prekey(id)
+                    .await?
+                    .ok_or_else(|| SignalError::InvalidKey(format!("unknown prekey {id}")))?,
+            ),
+            _ => None,
+        };
+        let plaintext = record.decrypt_prekey_message(
+            &identity,
+            device.registration_id,
+            &message,
+            &signed_prekey.key_pair,
+            one_time_prekey.as_ref().map(|p| &p.key_pair),
+        )?;
+        self.store
+            .put_session(&address, &record.serialize())
+            .await?;
+        self.store
+            .put_identity(&address, message.identity_key)
+            .await?;
+        if let Some(prekey) = one_time_prekey {
+            self.store.remove_prekey(prekey.id).await?;
+        }
+        Ok(plaintext)
+    }
+
+    async fn decrypt_signal_message(
+        &self,
+        sender: &Jid,
+        ciphertext: &[u8],
+    ) -> crate::Result<Vec<u8>> {
+        let message = SignalMessage::deserialize(ciphertext)?;
+        let address = ProtocolAddress::from(sender);
+        let mut record = self
+            .load_session(&address)
+            .await?
+            .ok_or(SignalError::NoSession)?;
+        let plaintext = record.decrypt(&message)?;
+        self.store
+            .put_session(&address, &record.serialize())
+            .await?;
+        Ok(plaintext)
+    }
+
+    async fn decrypt_group_message(
+        &self,
+        group: &Jid,
+        sender: &Jid,
+        ciphertext: &[u8],
+    ) -> crate::Result<Vec<u8>> {
+        let message = SenderKeyMessage::deserialize(ciphertext)?;
+        let name = SenderKeyName::new(group, sender);
+        let mut record = match self.store.get_sender_key(&name).await? {
+            Some(bytes) => SenderKeyRecord::deserialize(&bytes)?,
+            None => return Err(SignalError::NoSession.into()),
+        };
+        let plaintext = record.decrypt(&message)?;
+        self.store
+            .put_sender_key(&name, &record.serialize())
+            .await?;
+        Ok(plaintext)
+    }
+
+    /// Save a group member's sender key, so their `skmsg`s in that group can be decrypted.
+    async fn process_sender_key_distribution(
+        &self,
+        sender: &Jid,
+        distribution: &wa_e2e::SenderKeyDistributionMessage,
+    ) -> crate::Result<()> {
+        let group: Jid = distribution.group_id().parse().map_err(|_| {
+            SignalError::InvalidMessage(format!(
+                "sender key distribution for {:?}",
+                distribution.group_id()
+            ))
+        })?;
+        let message = SenderKeyDistributionMessage::deserialize(
+            distribution.axolotl_sender_key_distribution_message(),
+        )?;
+        let name = SenderKeyName::new(&group, sender);
+        let _signal = self.signal_lock.lock().await;
+        let mut record = match self.store.get_sender_key(&name).await? {
+            Some(bytes) => SenderKeyRecord::deserialize(&bytes)?,
+            None => SenderKeyRecord::new(),
+        };
+        record.process_distribution(&message);
+        self.store.put_sender_key(&name, &record.serialize()).await
+    }
+
+    async fn load_session(
+        &self,
+        address: &ProtocolAddress,
+    ) -> crate::Result<Option<SessionRecord>> {
+        self.store
+            .get_session(address)
+            .await?
+            .map(|bytes| SessionRecord::deserialize(&bytes))
+            .transpose()
+    }
+}
+
+/// Strip the padding added by the sender ([super::send::pad_message]).
+pub(super) fn unpad_message(mut plaintext: Vec<u8>) -> crate::Result<Vec<u8>> {
+    let invalid = || SignalError::InvalidMessage("invalid padding".into());
+    let pad = *plaintext.last().ok_or_else(invalid)? as usize;
+    let len = plaintext.len();
+    if pad == 0 || pad > len {
+        return Err(invalid().into());
+    }
+    plaintext.truncate(len - pad);
+    Ok(plaintext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::{connect_client, jid, paired_device, store_with, text, MockServer};
+    use crate::client::send::pad_message;
+    use crate::crypto::KeyPair;
+    use crate::events::EventStream;
+    use crate::proto::wa_e2e::{
+        ContextInfo, DeviceSentMessage, ExtendedTextMessage,
+        SenderKeyDistributionMessage as DistributionProto,
+    };
+    use crate::signal::{CiphertextMessage, PreKey, PreKeyBundle, SignedPreKey};
+    use crate::store::{Device, MemoryStore, PreKeyStore, SessionStore};
+    use futures::StreamExt;
+    use std::sync::Arc;
+
+    async fn receiving_client() -> (Client, MockServer, Arc<MemoryStore>, PreKeyBundle) {
+        receiving_client_with(true).await
+    }
+
+    /// Connected client (123:4, LID 9876) with a signed prekey and one one-time prekey in its
+    /// store, and the bundle remote devices start sessions from.
+    async fn receiving_client_with(
+        automatic_receipts: bool,
+    ) -> (Client, MockServer, Arc<MemoryStore>, PreKeyBundle) {
+        let identity = KeyPair::generate();
+        let signed_prekey = SignedPreKey::generate(1, &identity);
+        let prekey = PreKey::generate(7);
+        let store = store_with(Device {
+            lid: Some(jid("9876:4@lid")),
+            identity_key_pub: Some(identity.public),
+            identity_key_priv: Some(identity.private),
+            registration_id: 1234,
+            ..paired_device()
+        })
+        .await;
+        store.put_signed_prekey(&signed_prekey).await.unwrap();
+        store.put_prekey(&prekey).await.unwrap();
+        let bundle = PreKeyBundle {
+            registration_id: 1234,
+            device_id: 4,
+            prekey: Some((prekey.id, prekey.key_pair.public)),
+            signed_prekey_id: signed_prekey.id,
+            signed_prekey: signed_prekey.key_pair.public,
+            signed_prekey_signature: signed_prekey.signature,
+            identity_key: identity.public,
+        };
+        let (client, server) = connect_client(store.clone(), |builder| {
+            builder.automatic_receipts(automatic_receipts)
+        })
+        .await;
+        (client, server, store, bundle)
+    }
+
+    /// Another device, sending to the client over a session it started from its bundle.
+    struct Sender {
+        jid: Jid,
+        session: SessionRecord,
+    }
+
+    impl Sender {
+        fn new(jid: &str, bundle: &PreKeyBundle) -> Self {
+            let mut session = SessionRecord::new();
+            session
+                .process_bundle(&KeyPair::generate(), 55, bundle)
+                .unwrap();
+            Self {
+                jid: jid.parse().unwrap(),
+                session,
+            }
+        }
+
+        fn enc(&mut self, message: &Message) -> Node {
+            let ciphertext = self
+                .session
+                .encrypt(&pad_message(message.encode_to_vec()))
+                .unwrap();
+            enc(ciphertext.enc_type(), ciphertext.serialize())
+        }
+    }
+
+    fn enc(enc_type: &str, ciphertext: &[u8]) -> Node {
+        Node::new("enc")
+            .with_attr("v", "2")
+            .with_attr("type", enc_type)
+            .with_content(ciphertext.to_vec())
+    }
+
+    fn message_node(from: &Jid, encs: Vec<Node>) -> Node {
+        Node::new("message")
+            .with_attr("from", from.to_string())
+            .with_attr("id", "3EB0AA")
+            .with_attr("t", "1700000000")
+            .with_attr("type", "text")
+            .with_children(encs)
+    }
+
+    async fn next_message(events: &mut EventStream) -> MessageEvent {
+        loop {
+            match tokio::time::timeout(Duration::from_secs(1), events.next()).await {
+                Ok(Some(Event::Message(evt))) => return evt,
+                Ok(Some(_)) => continue,
+                _ => panic!("no message event"),
+            }
+        }
+    }
+
+    async fn next_receipt(server: &MockServer) -> Node {
+        let receipt = server.next_sent_skipping_acks().await;
+        assert_eq!(receipt.tag, "receipt");
+        receipt
+    }
+
+    async fn no_message(events: &mut EventStream) {
+        while let Ok(Some(evt)) =
+            tokio::time::timeout(Duration::from_millis(50), events.next()).await
+        {
+            assert!(!matches!(evt, Event::Message(_)), "unexpected {evt:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn direct_messages_are_decrypted_and_emitted() {
+        let (client, server, store, bundle) = receiving_client().await;
+        let mut events = client.events();
+        let mut alice = Sender::new("5511999:2@s.whatsapp.net", &bundle);
+
+        let reply = Message {
+            extended_text_message: Some(Box::new(ExtendedTextMessage {
+                text: Some("see above".into()),
+                context_info: Some(Box::new(ContextInfo {
+                    stanza_id: Some("3EB0A1".into()),
+                    participant: Some("123@s.whatsapp.net".into()),
+                    quoted_message: Some(Box::new(text("hi"))),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let first = alice.enc(&reply);
+        assert_eq!(first.attrs["type"], "pkmsg");
+        server.push(
+            message_node(&alice.jid, vec![first])
+                .with_attr("notify", "Alice")
+                .with_attr("sender_lid", "777@lid"),
+        );
+        let evt = next_message(&mut events).await;
+        assert_eq!(evt.chat, jid("5511999@s.whatsapp.net"));
+        assert_eq!(evt.sender, alice.jid);
+        assert_eq!(evt.sender_alt, Some(jid("777@lid")));
+        assert_eq!(evt.id, "3EB0AA");
+        assert_eq!(
+            evt.timestamp,
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+        );
+        assert_eq!(evt.push_name.as_deref(), Some("Alice"));
+        assert!(!evt.is_group && !evt.is_from_me);
+        assert_eq!(evt.category, None);
+        assert_eq!(evt.edit, EditAttribute::Empty);
+        assert_eq!(evt.device_sent, None);
+        assert_eq!(*evt.message, reply);
+        assert_eq!(evt.raw, reply.encode_to_vec());
+        let quoted = evt
+            .message
+            .extended_text_message
+            .and_then(|t| t.context_info)
+            .and_then(|c| c.quoted_message)
+            .unwrap();
+        assert_eq!(quoted.conversation(), "hi");
+        let receipt = next_receipt(&server).await;
+        assert_eq!(receipt.attrs["id"], "3EB0AA");
+        assert_eq!(receipt.attrs["type"], "inactive");
+        assert_eq!(receipt.attrs["to"], "5511999@s.whatsapp.net");
+        assert!(!receipt.attrs.contains_key("participant"));
+        // Acked once handled, receipt included.
+        let ack = server.next_sent().await;
+        assert_eq!(ack.tag, "ack");
+        assert_eq!(ack.attrs["class"], "message");
+        assert_eq!(ack.attrs["id"], "3EB0AA");
+        assert_eq!(ack.attrs["to"], alice.jid.to_string());
+        assert!(!ack.attrs.contains_key("type"));
+        assert!(
+            store.get_prekey(7).await.unwrap().is_none(),
+            "used one-time prekeys are removed"
+        );
+
+        // Until the client answers, the sender keeps sending prekey messages.
+        let edit = alice.enc(&text("edited"));
+        assert_eq!(edit.attrs["type"], "pkmsg");
+        server.push(message_node(&alice.jid, vec![edit]).with_attr("edit", "1"));
+        let evt = next_message(&mut events).await;
+        assert_eq!(evt.message.conversation(), "edited");
+        assert_eq!(evt.edit, EditAttribute::MessageEdit);
+
+        // Once it has, plain `msg`s follow.
+        let address = ProtocolAddress::from(&alice.jid);
+        let mut session =
+            SessionRecord::deserialize(&store.get_session(&address).await.unwrap().unwrap())
+                .unwrap();
+        let CiphertextMessage::Whisper(answer) = session.encrypt(b"answer").unwrap() else {
+            panic!("client session should be established");
+        };
+        store
+            .put_session(&address, &session.serialize())
+            .await
+            .unwrap();
+        assert_eq!(alice.session.decrypt(&answer).unwrap(), b"answer");
+        let later = alice.enc(&text("later"));
+        assert_eq!(later.attrs["type"], "msg");
+        server.push(message_node(&alice.jid, vec![later]));
+        assert_eq!(
+            next_message(&mut events).await.message.conversation(),
+            "later"
+        );
+    }
+
+    #[tokio::test]
+    async fn group_messages_use_the_distributed_sender_key() {
+        let (client, server, _store, bundle) = receiving_client().await;
+        let mut events = client.events();
+        let group = jid("120363001@g.us");
+        let mut bob = Sender::new("8888:3@lid", &bundle);
+        let mut sender_key = SenderKeyRecord::new();
+        let distribution = sender_key.create_distribution();
+
+        let skmsg = |sender_key: &mut SenderKeyRecord, body: &str| {
+            let ciphertext = sender_key
+                .encrypt(&pad_message(text(body).encode_to_vec()))
+                .unwrap();
+            enc("skmsg", ciphertext.serialize())
+        };
+        let pkmsg = bob.enc(&Message {
+            sender_key_distribution_message: Some(DistributionProto {
+                group_id: Some(group.to_string()),
+                axolotl_sender_key_distribution_message: Some(distribution.serialize().to_vec()),
+            }),
+            ..Default::default()
+        });
+        server.push(
+            message_node(&group, vec![pkmsg, skmsg(&mut sender_key, "hello group")])
+                .with_attr("participant", bob.jid.to_string())
+                .with_attr("participant_pn", "5522222:3@s.whatsapp.net"),
+        );
+        let evt = next_message(&mut events).await;
+        assert_eq!(evt.message.conversation(), "hello group");
+        assert_eq!(evt.chat, group);
+        assert_eq!(evt.sender, bob.jid);
+        assert_eq!(evt.sender_alt, Some(jid("5522222:3@s.whatsapp.net")));
+        assert!(evt.is_group && !evt.is_from_me);
+        no_message(&mut events).await;
+        let receipt = next_receipt(&server).await;
+        assert_eq!(receipt.attrs["to"], group.to_string());
+        assert_eq!(receipt.attrs["participant"], bob.jid.to_string());
+
+        server.push(
+            message_node(&group, vec![skmsg(&mut sender_key, "again")])
+                .with_attr("participant", bob.jid.to_string()),
+        );
+        assert_eq!(
+            next_message(&mut events).await.message.conversation(),
+            "again"
+        );
+    }
+
+    #[tokio::test]
+    async fn messages_from_our_other_devices_are_unwrapped() {
+        let (client, server, _store, bundle) = receiving_client().await;
+        let mut events = client.events();
+        let mut phone = Sender::new("123:0@s.whatsapp.net", &bundle);
+        let sent = phone.enc(&Message {
+            device_sent_message: Some(Box::new(DeviceSentMessage {
+                destination_jid: Some("5511999@s.whatsapp.net".into()),
+                message: Some(Box::new(text("from my phone"))),
+                phash: Some("2:abc".into()),
+            })),
+            ..Default::default()
+        });
+        server.push(
+            message_node(&phone.jid, vec![sent]).with_attr("recipient", "5511999@s.whatsapp.net"),
+        );
+        let evt = next_message(&mut events).await;
+        assert!(evt.is_from_me);
+        assert_eq!(evt.chat, jid("5511999@s.whatsapp.net"));
+        assert_eq!(evt.sender, phone.jid);
+        assert_eq!(*evt.message, text("from my phone"));
+        assert_eq!(
+            evt.device_sent,
+            Some(DeviceSentMeta {
+                destination_jid: "5511999@s.whatsapp.net".into(),
+                phash: Some("2:abc".into()),
+            })
+        );
+        let receipt = next_receipt(&server).await;
+        assert_eq!(receipt.attrs["type"], "sender");
+        assert_eq!(receipt.attrs["to"], phone.jid.to_string());
+        assert_eq!(receipt.attrs["recipient"], "5511999@s.whatsapp.net");
+    }
+
+    #[tokio::test]
+    async fn automatic_receipts_can_be_disabled() {
+        let (client, server, _store, bundle) = receiving_client_with(false).await;
+        let mut events = client.events();
+        let mut alice = Sender::new("5511999:2@s.whatsapp.net", &bundle);
+        let hi = alice.enc(&text("hi"));
+        server.push(message_node(&alice.jid, vec![hi]));
+        let evt = next_message(&mut events).await;
+        client
+            .mark_read(&[evt.id], &evt.chat, None, SystemTime::now())
+            .await
+            .unwrap();
+        // The first receipt sent is the read one.
+        assert_eq!(next_receipt(&server).await.attrs["type"], "read");
+    }
+
+    #[tokio::test]
+    async fn delivery_receipts_are_plain_once_available() {
+        let (client, server, _store, bundle) = receiving_client().await;
+        client.set_push_name("Me").await.unwrap();
+        client
+            .send_presence(crate::types::Presence::Available)
+            .await
+            .unwrap();
+        assert_eq!(server.next_sent().await.tag, "presence");
+        let mut events = client.events();
+        let mut alice = Sender::new("5511999:2@s.whatsapp.net", &bundle);
+        let hi = alice.enc(&text("hi"));
+        server.push(message_node(&alice.jid, vec![hi]));
+        next_message(&mut events).await;
+        let receipt = next_receipt(&server).await;
+        assert_eq!(receipt.attrs["to"], "5511999@s.whatsapp.net");
+        assert!(!receipt.attrs.contains_key("type"));
+    }
+
+    #[tokio::test]
+    async fn undecryptable_messages_are_dropped() {
+        let (client, server, _store, _bundle) = receiving_client().await;
+        let mut events = client.events();
+        let group = jid("120363001@g.us");
+        let mut unknown = SenderKeyRecord::new();
+        unknown.create_distribution();
+        let ciphertext = unknown.encrypt(b"secret\x01").unwrap();
+        server.push(
+            message_node(&group, vec![enc("skmsg", ciphertext.serialize())])
+                .with_attr("participant", "5511111@s.whatsapp.net"),
+        );
+        server.push(message_node(
+            &jid("5511111@s.whatsapp.net"),
+            vec![enc("msg", b"garbage")],
+        ));
+        no_message(&mut events).await;
+        assert!(client.is_connected());
+    }
+
+    #[test]
+    fn unpadding_trusts_the_last_byte() {
+        assert_eq!(unpad_message(b"hi\x02\x02".to_vec()).unwrap(), b"hi");
+        assert_eq!(unpad_message(b"\x01".to_vec()).unwrap(), b"");
+        // Only the length byte is checked, like other clients do.
+        assert_eq!(unpad_message(b"hi\x01\x02".to_vec()).unwrap(), b"hi");
+        for invalid in [&b""[..], b"hi\x00", b"\x05\x05"] {
+            assert!(unpad_message(invalid.to_vec()).is_err(), "{invalid:?}");
+        }
+    }
+}
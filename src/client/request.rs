@@ -0,0 +1,189 @@
+//! IQ requests: send an `<iq>` and wait for the server's response with the same id.
+
+use super::Client;
+use crate::binary::Node;
+use crate::error::{ConnectionError, Error, SendError};
+use crate::types::Jid;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Type of an IQ request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum IqType {
+    Get,
+    Set,
+}
+
+impl IqType {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Get => "get",
+            Self::Set => "set",
+        }
+    }
+}
+
+/// An IQ request to the server.
+#[derive(Clone, Debug)]
+pub(crate) struct InfoQuery {
+    pub namespace: &'static str,
+    pub iq_type: IqType,
+    pub to: Jid,
+    pub content: Vec<Node>,
+}
+
+impl Client {
+    /// Unique id for a request node.
+    pub(crate) fn generate_request_id(&self) -> String {
+        format!(
+            "{}-{}",
+            self.request_id_prefix,
+            self.next_request_id.fetch_add(1, Ordering::Relaxed)
+        )
+    }
+
+    /// Send an IQ and wait (up to the configured request timeout) for its `result`.
+    /// An `error` response becomes [SendError::Iq].
+    pub(crate) async fn send_iq(&self, query: InfoQuery) -> crate::Result<Node> {
+        let id = self.generate_request_id();
+        let node = Node::new("iq")
+            .with_attr("id", id.clone())
+            .with_attr("xmlns", query.namespace)
+            .with_attr("type", query.iq_type.as_str())
+            .with_attr("to", query.to.to_string())
+            .with_children(query.content);
+        let response = self.send_node_and_wait(&node, &id, None).await?;
+        if response.attrs.get("type").map(String::as_str) == Some("error") {
+            let error = response.get_child_by_tag("error");
+            let attr = |key: &str| error.and_then(|e| e.attrs.get(key)).cloned();
+            return Err(Error::Send(SendError::Iq {
+                code: attr("code").and_then(|c| c.parse().ok()).unwrap_or(0),
+                text: attr("text").unwrap_or_default(),
+            }));
+        }
+        Ok(response)
+    }
+
+    /// Send a node and wait (up to `timeout`, or the configured request timeout) for the
+    /// response with the given id: an IQ result or error, or the server's `<ack>`.
+    pub(crate) async fn send_node_and_wait(
+        &self,
+        node: &Node,
+        id: &str,
+        timeout: Option<Duration>,
+    ) -> crate::Result<Node> {
+        let (tx, rx) = oneshot::channel();
+        self.response_waiters
+            .lock()
+            .unwrap()
+            .insert(id.to_string(), tx);
+        if let Err(e) = self.send_node(node).await {
+            self.response_waiters.lock().unwrap().remove(id);
+            return Err(e);
+        }
+        let timeout = timeout.unwrap_or(self.config.request_timeout);
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(_)) => Err(ConnectionError::Disconnected.into()),
+            Err(_) => {
+                self.response_waiters.lock().unwrap().remove(id);
+                Err(SendError::Timeout.into())
+            }
+        }
+    }
+
+    /// Hand a `result`/`error` IQ or an `<ack>` to the request waiting for it; gives the
+    /// node back if nobody is waiting.
+    pub(super) fn receive_response(&self, node: Node) -> Option<Node> {
+        let is_response = node.tag == "ack"
+            || node.tag == "iq"
+                && matches!(
+                    node.attrs.get("type").map(String::as_str),
+                    Some("result" | "error")
+                );
+        let waiter = node
+            .attrs
+            .get("id")
+            .filter(|_| is_response)
+            .and_then(|id| self.response_waiters.lock().unwrap().remove(id));
+        match waiter {
+            Some(tx) => {
+                let _ = tx.send(node);
+                None
+            }
+            None => Some(node),
+        }
+    }
+
+    /// Fail all pending requests (the connection is gone).
+    pub(super) fn clear_response_waiters(&self) {
+        self.response_waiters.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::{connect_client, connected_client, paired_device, store_with};
+
+    fn query() -> InfoQuery {
+        InfoQuery {
+            namespace: "md",
+            iq_type: IqType::Get,
+            to: Jid::default_server(),
+            content: vec![Node::new("ping")],
+        }
+    }
+
+    #[tokio::test]
+    async fn send_iq_returns_matching_result() {
+        let (client, server, _) = connected_client().await;
+        let responder = server.clone();
+        let request = tokio::spawn(async move { client.send_iq(query()).await });
+        let sent = responder.next_sent().await;
+        assert_eq!(sent.attrs.get("xmlns").map(String::as_str), Some("md"));
+        assert_eq!(sent.attrs.get("type").map(String::as_str), Some("get"));
+        let id = sent.attrs.get("id").unwrap().clone();
+        responder.push(
+            Node::new("iq")
+                .with_attr("type", "result")
+                .with_attr("id", id)
+                .with_children(vec![Node::new("pong")]),
+        );
+        let response = request.await.unwrap().unwrap();
+        assert!(response.get_child_by_tag("pong").is_some());
+    }
+
+    #[tokio::test]
+    async fn send_iq_maps_error_response() {
+        let (client, server, _) = connected_client().await;
+        let responder = server.clone();
+        let request = tokio::spawn(async move { client.send_iq(query()).await });
+        let id = responder.next_sent().await.attrs["id"].clone();
+        responder.push(
+            Node::new("iq")
+                .with_attr("type", "error")
+                .with_attr("id", id)
+                .with_children(vec![Node::new("error")
+                    .with_attr("code", "404")
+                    .with_attr("text", "item-not-found")]),
+        );
+        let err = request.await.unwrap().unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Send(SendError::Iq { code: 404, ref text }) if text == "item-not-found"
+        ));
+    }
+
+    #[tokio::test]
+    async fn send_iq_times_out() {
+        let (client, _server) = connect_client(store_with(paired_device()).await, |builder| {
+            builder.request_timeout(std::time::Duration::from_millis(200))
+        })
+        .await;
+        let err = client.send_iq(query()).await.unwrap_err();
+        assert!(matches!(err, Error::Send(SendError::Timeout)));
+        assert!(client.response_waiters.lock().unwrap().is_empty());
+    }
+}
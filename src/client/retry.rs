@@ -0,0 +1,596 @@
+//! Retry receipts: a device that can't decrypt a message asks for it again with a
+//! `<receipt type="retry">` carrying its registration ID and fresh keys, and the sender
+//! encrypts it once more from the recent messages it keeps.
+
+use super::prekeys::{parse_bundle, prekey_node, signed_prekey_node};
+use super::send::{enc_node, is_prekey_message, media_type, message_type, pad_message};
+use super::Client;
+use crate::binary::Node;
+use crate::crypto::DJB_KEY_TYPE;
+use crate::error::{Error, SendError, StoreError};
+use crate::proto::wa_e2e::{DeviceSentMessage, Message, SenderKeyDistributionMessage};
+use crate::signal::{ProtocolAddress, SenderKeyName, SenderKeyRecord};
+use crate::types::{EditAttribute, Jid, MessageId, GROUP_SERVER};
+use prost::Message as _;
+use std::collections::{HashMap, VecDeque};
+
+/// Sent messages kept for answering retry receipts.
+pub const RECENT_MESSAGES_SIZE: usize = 256;
+
+/// Retry receipts sent for one message before giving up on it.
+pub const MAX_RETRY_COUNT: u32 = 5;
+
+/// Undecryptable messages whose retry count is kept.
+pub const MESSAGE_RETRIES_SIZE: usize = 256;
+
+/// The last [RECENT_MESSAGES_SIZE] messages we sent, by chat and ID.
+#[derive(Debug, Default)]
+pub(super) struct RecentMessages {
+    messages: HashMap<(Jid, MessageId), Message>,
+    /// Oldest first.
+    order: VecDeque<(Jid, MessageId)>,
+}
+
+impl RecentMessages {
+    fn insert(&mut self, chat: Jid, id: MessageId, message: Message) {
+        let key = (chat, id);
+        if self.messages.insert(key.clone(), message).is_none() {
+            self.order.push_back(key);
+        }
+        while self.order.len() > RECENT_MESSAGES_SIZE {
+            if let Some(oldest) = self.order.pop_front() {
+                self.messages.remove(&oldest);
+            }
+        }
+    }
+
+    fn get(&self, chat: &Jid, id: &str) -> Option<&Message> {
+        self.messages.get(&(chat.clone(), id.to_string()))
+    }
+}
+
+/// Retry receipts sent per message, for the last [MESSAGE_RETRIES_SIZE] messages we couldn't
+/// decrypt.
+#[derive(Debug, Default)]
+pub(super) struct MessageRetries {
+    counts: HashMap<MessageId, u32>,
+    /// Oldest first.
+    order: VecDeque<MessageId>,
+}
+
+impl MessageRetries {
+    /// Count one more retry for `id`, returning the new count.
+    fn increment(&mut self, id: &str) -> u32 {
+        let count = match self.counts.get_mut(id) {
+            Some(count) => count,
+            None => {
+                self.order.push_back(id.to_string());
+                self.counts.entry(id.to_string()).or_default()
+            }
+        };
+        *count += 1;
+        let count = *count;
+        while self.order.len() > MESSAGE_RETRIES_SIZE {
+            if let Some(oldest) = self.order.pop_front() {
+                self.counts.remove(&oldest);
+            }
+        }
+        count
+    }
+
+    fn remove(&mut self, id: &str) {
+        if self.counts.remove(id).is_some() {
+            self.order.retain(|kept| kept != id);
+        }
+    }
+}
+
+impl Client {
+    /// Remember a message we are sending, to encrypt it again if a recipient asks.
+    pub(super) fn add_recent_message(&self, to: &Jid, id: &str, message: &Message) {
+        self.recent_messages.lock().unwrap().insert(
+            to.to_non_ad(),
+            id.to_string(),
+            message.clone(),
+        );
+    }
+
+    /// Ask the sender of a `<message>` we couldn't decrypt to send it again, with our
+    /// registration ID and keys to start a new session from. Gives up after
+    /// [MAX_RETRY_COUNT] receipts.
+    pub(super) async fn send_retry_receipt(&self, node: &Node) -> crate::Result<()> {
+        let id = node.attrs.get("id").cloned().unwrap_or_default();
+        let count = self.message_retries.lock().unwrap().increment(&id);
+        if count > MAX_RETRY_COUNT {
+            tracing::warn!(%id, "not asking for the message again after too many retries");
+            return Ok(());
+        }
+        let device = self.device.read().await.clone().ok_or(Error::NotLoggedIn)?;
+        let (Some(identity), Some(signed_prekey), Some(account)) = (
+            device.identity_key_pub,
+            device.signed_prekey(),
+            device.account.clone(),
+        ) else {
+            return Err(StoreError::IdentityNotFound.into());
+        };
+        let prekey = self.generate_direct_prekey().await?;
+
+        let mut receipt = Node::new("receipt")
+            .with_attr("id", id.as_str())
+            .with_attr("type", "retry")
+            .with_attr("to", node.attrs.get("from").cloned().unwrap_or_default());
+        for key in ["participant", "recipient"] {
+            if let Some(value) = node.attrs.get(key) {
+                receipt = receipt.with_attr(key, value.as_str());
+            }
+        }
+        let mut retry = Node::new("retry")
+            .with_attr("count", count.to_string())
+            .with_attr("id", id.as_str())
+            .with_attr("v", "1");
+        if let Some(t) = node.attrs.get("t") {
+            retry = retry.with_attr("t", t.as_str());
+        }
+        let receipt = receipt.with_children(vec![
+            retry,
+            Node::new("registration").with_content(device.registration_id.to_be_bytes().to_vec()),
+            Node::new("keys").with_children(vec![
+                Node::new("type").with_content(vec![DJB_KEY_TYPE]),
+                Node::new("identity").with_content(identity.to_vec()),
+                prekey_node(&prekey),
+                signed_prekey_node(&signed_prekey),
+                Node::new("device-identity").with_content(account),
+            ]),
+        ]);
+        self.send_node(&receipt).await
+    }
+
+    /// Forget the retries of a message once it was decrypted.
+    pub(super) fn clear_retry_count(&self, id: &str) {
+        self.message_retries.lock().unwrap().remove(id);
+    }
+
+    /// `<receipt type="retry">`: a device couldn't decrypt one of our recent messages.
+    /// Encrypt it again for that device alone (over a new session if it sent keys, with our
+    /// sender key for groups) and resend it.
+    pub(super) async fn handle_retry_receipt(&self, node: &Node) -> crate::Result<()> {
+        let jid_attr = |key: &str| node.attrs.get(key).and_then(|v| v.parse::<Jid>().ok());
+        let from = jid_attr("from")
+            .ok_or_else(|| SendError::Server("retry receipt without sender".into()))?;
+        let id = node.attrs.get("id").cloned().unwrap_or_default();
+        let count: u32 = node
+            .get_child_by_tag("retry")
+            .and_then(|r| r.attrs.get("count"))
+            .and_then(|c| c.parse().ok())
+            .unwrap_or(1);
+        let (chat, requester) = match jid_attr("participant") {
+            Some(participant) => (from.clone(), participant),
+            None => (
+                jid_attr("recipient").unwrap_or_else(|| from.to_non_ad()),
+                from.clone(),
+            ),
+        };
+        let message = self
+            .recent_messages
+            .lock()
+            .unwrap()
+            .get(&chat, &id)
+            .cloned()
+            .ok_or(SendError::MessageNotFoundForRetry)?;
+        let device = self.device.read().await.clone().ok_or(Error::NotLoggedIn)?;
+        let own_id = device.id.clone().ok_or(Error::NotLoggedIn)?;
+        let identity = device
+            .identity_key_pair()
+            .ok_or(StoreError::IdentityNotFound)?;
+
+        let is_group = chat.server == GROUP_SERVER;
+        let plaintext = if is_group {
+            Message {
+                sender_key_distribution_message: Some(SenderKeyDistributionMessage {
+                    group_id: Some(chat.to_string()),
+                    axolotl_sender_key_distribution_message: Some(
+                        self.own_sender_key_distribution(&chat, &own_id).await?,
+                    ),
+                }),
+                ..message.clone()
+            }
+        } else if requester.user == own_id.user && chat.user != own_id.user {
+            Message {
+                device_sent_message: Some(Box::new(DeviceSentMessage {
+                    destination_jid: Some(chat.to_string()),
+                    message: Some(Box::new(message.clone())),
+                    phash: None,
+                })),
+                message_context_info: message.message_context_info.clone(),
+                ..Default::default()
+            }
+        } else {
+            message.clone()
+        };
+
+        let bundle = match node.get_child_by_tag("keys") {
+            Some(keys) => {
+                let mut user = keys.get_children().to_vec();
+                user.extend(node.get_child_by_tag("registration").cloned());
+                let bundle = parse_bundle(&requester, &Node::new("user").with_children(user))
+                    .ok_or_else(|| SendError::Server("retry receipt with invalid keys".into()))?;
+                Some(bundle)
+            }
+            // The device didn't send keys again; ours may be the broken session.
+            None if count >= 2 => self
+                .fetch_prekey_bundles(std::slice::from_ref(&requester))
+                .await?
+                .remove(&requester),
+            None => None,
+        };
+        let ciphertext = {
+            let _signal = self.signal_lock.lock().await;
+            if bundle.is_some() {
+                self.store
+                    .delete_session(&ProtocolAddress::from(&requester))
+                    .await?;
+            }
+            self.encrypt_for_device_locked(
+                &identity,
+                device.registration_id,
+                &requester,
+                &pad_message(plaintext.encode_to_vec()),
+                bundle.as_ref(),
+            )
+            .await?
+        };
+        let enc = enc_node(
+            ciphertext.enc_type(),
+            ciphertext.serialize().to_vec(),
+            media_type(&message),
+        )
+        .with_attr("count", count.to_string());
+        let include_identity = is_prekey_message(&enc);
+        let mut children = vec![enc];
+        if include_identity {
+            children.push(self.device_identity_node().await?);
+        }
+
+        let mut resend = Node::new("message")
+            .with_attr("to", from.to_string())
+            .with_attr("type", message_type(&message))
+            .with_attr("id", id.as_str());
+        if !is_group {
+            resend = resend.with_attr("device_fanout", "false");
+        }
+        for key in ["participant", "recipient"] {
+            if let Some(value) = node.attrs.get(key) {
+                resend = resend.with_attr(key, value.as_str());
+            }
+        }
+        let edit = EditAttribute::for_message(&message);
+        if edit != EditAttribute::Empty {
+            resend = resend.with_attr("edit", edit.as_str());
+        }
+        tracing::info!(%id, to = %requester, count, "resending message for retry receipt");
+        self.send_node(&resend.with_children(children)).await?;
+        if is_group {
+            self.mark_sender_key_distributed(&chat, &[requester]);
+        }
+        Ok(())
+    }
+
+    /// Distribution message for our sender key in `group`, creating the key if needed.
+    async fn own_sender_key_distribution(
+        &self,
+        group: &Jid,
+        own_id: &Jid,
+    ) -> crate::Result<Vec<u8>> {
+        let name = SenderKeyName::new(group, own_id);
+        let _signal = self.signal_lock.lock().await;
+        let mut record = match self.store.get_sender_key(&name).await? {
+            Some(bytes) => SenderKeyRecord::deserialize(&bytes)?,
+            None => SenderKeyRecord::new(),
+        };
+        let distribution = record.create_distribution();
+        self.store
+            .put_sender_key(&name, &record.serialize())
+            .await?;
+        Ok(distribution.serialize().to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::NodeContent;
+    use crate::client::mock::{connected_client_with, jid, paired_device, text, MockServer};
+    use crate::client::receive::unpad_message;
+    use crate::crypto::KeyPair;
+    use crate::events::{Event, EventStream, UndecryptableMessageEvent};
+    use crate::pairing::generate_pairing_keys;
+    use crate::signal::{
+        PreKey, PreKeySignalMessage, SenderKeyDistributionMessage as DistributionMessage,
+        SessionRecord, SignedPreKey,
+    };
+    use crate::store::{Device, MemoryStore, PreKeyStore};
+    use futures::StreamExt;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const ACCOUNT: &[u8] = b"signed device identity";
+
+    fn bytes<'a>(node: &'a Node, tag: &str) -> &'a [u8] {
+        match &node.get_child_by_tag(tag).expect(tag).content {
+            NodeContent::Bytes(b) => b,
+            other => panic!("{tag}: {other:?}"),
+        }
+    }
+
+    /// Connected client with all its keys, past the prekey count check of the login.
+    async fn keyed_client() -> (Client, MockServer, Arc<MemoryStore>) {
+        let device = Device {
+            id: paired_device().id,
+            account: Some(ACCOUNT.to_vec()),
+            ..Device::with_keys(&generate_pairing_keys())
+        };
+        let (client, server, store) = connected_client_with(device, |builder| builder).await;
+        let count = server.next_sent().await;
+        assert_eq!(count.attrs["xmlns"], "encrypt");
+        server.push(
+            Node::new("iq")
+                .with_attr("type", "result")
+                .with_attr("id", count.attrs["id"].clone())
+                .with_children(vec![Node::new("count").with_attr("value", "50")]),
+        );
+        (client, server, store)
+    }
+
+    async fn next_undecryptable(events: &mut EventStream) -> UndecryptableMessageEvent {
+        loop {
+            match tokio::time::timeout(Duration::from_secs(1), events.next()).await {
+                Ok(Some(Event::UndecryptableMessage(evt))) => return evt,
+                Ok(Some(_)) => continue,
+                _ => panic!("no undecryptable message event"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn undecryptable_messages_are_reported_and_requested_again() {
+        let (client, server, store) = keyed_client().await;
+        let mut events = client.events();
+        let device = client.device.read().await.clone().unwrap();
+        let message = |id: &str, child: Node| {
+            Node::new("message")
+                .with_attr("from", "5511999:2@s.whatsapp.net")
+                .with_attr("id", id)
+                .with_attr("t", "1700000000")
+                .with_attr("type", "text")
+                .with_children(vec![child])
+        };
+
+        server.push(message(
+            "3EB0AA",
+            Node::new("unavailable").with_attr("type", "view_once"),
+        ));
+        let evt = next_undecryptable(&mut events).await;
+        assert!(evt.is_unavailable);
+        assert_eq!(evt.error, "");
+        assert_eq!(evt.chat, jid("5511999@s.whatsapp.net"));
+
+        let broken = message(
+            "3EB0BB",
+            Node::new("enc")
+                .with_attr("v", "2")
+                .with_attr("type", "msg")
+                .with_content(b"garbage".to_vec()),
+        );
+        server.push(broken.clone());
+        let evt = next_undecryptable(&mut events).await;
+        assert!(!evt.is_unavailable);
+        assert_eq!(evt.id, "3EB0BB");
+        assert_eq!(evt.sender, jid("5511999:2@s.whatsapp.net"));
+        assert!(!evt.error.is_empty());
+
+        // Nothing was requested for the unavailable message.
+        let receipt = server.next_sent_skipping_acks().await;
+        assert_eq!(receipt.tag, "receipt");
+        assert_eq!(receipt.attrs["type"], "retry");
+        assert_eq!(receipt.attrs["id"], "3EB0BB");
+        assert_eq!(receipt.attrs["to"], "5511999:2@s.whatsapp.net");
+        let retry = receipt.get_child_by_tag("retry").unwrap();
+        assert_eq!(retry.attrs["count"], "1");
+        assert_eq!(retry.attrs["id"], "3EB0BB");
+        assert_eq!(retry.attrs["t"], "1700000000");
+        assert_eq!(
+            bytes(&receipt, "registration"),
+            device.registration_id.to_be_bytes()
+        );
+        let keys = receipt.get_child_by_tag("keys").unwrap();
+        assert_eq!(bytes(keys, "type"), [DJB_KEY_TYPE]);
+        assert_eq!(bytes(keys, "identity"), device.identity_key_pub.unwrap());
+        assert_eq!(bytes(keys, "device-identity"), ACCOUNT);
+        let skey = keys.get_child_by_tag("skey").unwrap();
+        assert_eq!(bytes(skey, "value"), device.signed_prekey_pub.unwrap());
+        let key = keys.get_child_by_tag("key").unwrap();
+        let prekey = store.get_prekey(1).await.unwrap().expect("fresh prekey");
+        assert_eq!(bytes(key, "id"), [0, 0, 1]);
+        assert_eq!(bytes(key, "value"), prekey.key_pair.public);
+        assert!(
+            store.unuploaded_prekeys().await.unwrap().is_empty(),
+            "prekeys handed out in receipts are not uploaded"
+        );
+
+        server.push(broken);
+        let receipt = server.next_sent_skipping_acks().await;
+        let retry = receipt.get_child_by_tag("retry").unwrap();
+        assert_eq!(retry.attrs["count"], "2");
+        let key = receipt
+            .get_child_by_tag("keys")
+            .unwrap()
+            .get_child_by_tag("key");
+        assert_eq!(bytes(key.unwrap(), "id"), [0, 0, 2]);
+    }
+
+    /// A device of a recipient that couldn't decrypt our message.
+    struct Requester {
+        identity: KeyPair,
+        signed_prekey: SignedPreKey,
+        prekey: PreKey,
+    }
+
+    impl Requester {
+        fn new() -> Self {
+            let identity = KeyPair::generate();
+            Self {
+                signed_prekey: SignedPreKey::generate(3, &identity),
+                identity,
+                prekey: PreKey::generate(9),
+            }
+        }
+
+        /// Its retry receipt for message `id` with fresh keys; `from` and the other
+        /// addressing attributes are up to the caller.
+        fn receipt(&self, id: &str) -> Node {
+            Node::new("receipt")
+                .with_attr("id", id)
+                .with_attr("type", "retry")
+                .with_children(vec![
+                    Node::new("retry")
+                        .with_attr("count", "1")
+                        .with_attr("id", id)
+                        .with_attr("v", "1"),
+                    Node::new("registration").with_content(99u32.to_be_bytes().to_vec()),
+                    Node::new("keys").with_children(vec![
+                        Node::new("type").with_content(vec![DJB_KEY_TYPE]),
+                        Node::new("identity").with_content(self.identity.public.to_vec()),
+                        prekey_node(&self.prekey),
+                        signed_prekey_node(&self.signed_prekey),
+                    ]),
+                ])
+        }
+
+        fn decrypt(&self, resend: &Node) -> Message {
+            let enc = resend.get_child_by_tag("enc").unwrap();
+            assert_eq!(enc.attrs["type"], "pkmsg");
+            let NodeContent::Bytes(ciphertext) = &enc.content else {
+                panic!("enc without content");
+            };
+            let plaintext = SessionRecord::new()
+                .decrypt_prekey_message(
+                    &self.identity,
+                    99,
+                    &PreKeySignalMessage::deserialize(ciphertext).unwrap(),
+                    &self.signed_prekey.key_pair,
+                    Some(&self.prekey.key_pair),
+                )
+                .unwrap();
+            Message::decode(unpad_message(plaintext).unwrap().as_slice()).unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn retry_receipts_resend_recent_messages_over_a_new_session() {
+        let (client, server, _store) = keyed_client().await;
+        let chat = jid("5511999@s.whatsapp.net");
+        client.add_recent_message(&chat, "3EB0AA", &text("hello"));
+
+        let requester = Requester::new();
+        server.push(
+            requester
+                .receipt("3EB0AA")
+                .with_attr("from", "5511999:2@s.whatsapp.net"),
+        );
+        let resend = server.next_sent_skipping_acks().await;
+        assert_eq!(resend.tag, "message");
+        assert_eq!(resend.attrs["to"], "5511999:2@s.whatsapp.net");
+        assert_eq!(resend.attrs["id"], "3EB0AA");
+        assert_eq!(resend.attrs["type"], "text");
+        assert_eq!(resend.attrs["device_fanout"], "false");
+        assert_eq!(resend.get_child_by_tag("enc").unwrap().attrs["count"], "1");
+        assert_eq!(bytes(&resend, "device-identity"), ACCOUNT);
+        assert_eq!(requester.decrypt(&resend), text("hello"));
+
+        // Our own devices get it as sent from another device.
+        let own = Requester::new();
+        server.push(
+            own.receipt("3EB0AA")
+                .with_attr("from", "123:1@s.whatsapp.net")
+                .with_attr("recipient", chat.to_string()),
+        );
+        let resend = server.next_sent_skipping_acks().await;
+        assert_eq!(resend.attrs["to"], "123:1@s.whatsapp.net");
+        assert_eq!(resend.attrs["recipient"], chat.to_string());
+        let sent = own.decrypt(&resend).device_sent_message.unwrap();
+        assert_eq!(sent.destination_jid(), chat.to_string());
+        assert_eq!(sent.message.as_deref(), Some(&text("hello")));
+    }
+
+    #[tokio::test]
+    async fn group_retries_carry_our_sender_key() {
+        let (client, server, _store) = keyed_client().await;
+        let group = jid("120363001@g.us");
+        let participant = jid("5522222:3@s.whatsapp.net");
+        client.add_recent_message(&group, "3EB0CC", &text("hi group"));
+
+        let requester = Requester::new();
+        server.push(
+            requester
+                .receipt("3EB0CC")
+                .with_attr("from", group.to_string())
+                .with_attr("participant", participant.to_string()),
+        );
+        let resend = server.next_sent_skipping_acks().await;
+        assert_eq!(resend.attrs["to"], group.to_string());
+        assert_eq!(resend.attrs["participant"], participant.to_string());
+        assert!(!resend.attrs.contains_key("device_fanout"));
+        let message = requester.decrypt(&resend);
+        assert_eq!(message.conversation(), "hi group");
+        let distribution = message.sender_key_distribution_message.unwrap();
+        assert_eq!(distribution.group_id(), group.to_string());
+        assert!(DistributionMessage::deserialize(
+            distribution.axolotl_sender_key_distribution_message()
+        )
+        .is_ok());
+        assert!(client.sender_key_distributed.lock().unwrap()[&group].contains(&participant));
+    }
+
+    #[tokio::test]
+    async fn retries_for_unknown_messages_fail() {
+        let (client, _server, _store) = keyed_client().await;
+        let receipt = Requester::new()
+            .receipt("3EB0DD")
+            .with_attr("from", "5511999:2@s.whatsapp.net");
+        assert!(matches!(
+            client.handle_retry_receipt(&receipt).await,
+            Err(Error::Send(SendError::MessageNotFoundForRetry))
+        ));
+    }
+
+    #[test]
+    fn recent_messages_keep_the_newest() {
+        let chat = jid("5511999@s.whatsapp.net");
+        let mut recent = RecentMessages::default();
+        for i in 0..=RECENT_MESSAGES_SIZE {
+            recent.insert(chat.clone(), format!("ID{i}"), text(&i.to_string()));
+        }
+        assert!(recent.get(&chat, "ID0").is_none());
+        assert_eq!(recent.get(&chat, "ID1"), Some(&text("1")));
+        let newest = format!("ID{RECENT_MESSAGES_SIZE}");
+        assert!(recent.get(&chat, &newest).is_some());
+        assert!(recent.get(&jid("5522222@s.whatsapp.net"), "ID1").is_none());
+    }
+
+    #[test]
+    fn message_retries_keep_the_newest() {
+        let mut retries = MessageRetries::default();
+        assert_eq!(retries.increment("ID0"), 1);
+        assert_eq!(retries.increment("ID0"), 2);
+        for i in 1..=MESSAGE_RETRIES_SIZE {
+            retries.increment(&format!("ID{i}"));
+        }
+        assert_eq!(retries.counts.len(), MESSAGE_RETRIES_SIZE);
+        assert_eq!(retries.increment("ID1"), 2);
+        assert_eq!(retries.increment("ID0"), 1, "oldest count was dropped");
+
+        retries.remove("ID0");
+        assert!(!retries.counts.contains_key("ID0"));
+        assert_eq!(retries.order.len(), retries.counts.len());
+    }
+}
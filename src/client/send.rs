@@ -1,21 +1,1290 @@
-//! Send message types.
+//! Sending messages: a [Message] is encrypted pairwise for every device of the recipient
+//! (our own other devices get it wrapped in a DeviceSentMessage), or once with our sender
+//! key for groups, then sent as a `<message>` node and acknowledged by the server.
 
-use crate::types::{Jid, MessageId};
-use std::time::SystemTime;
+use super::Client;
+use crate::binary::{Node, NodeContent};
+use crate::crypto::KeyPair;
+use crate::error::{Error, SendError, StoreError};
+use crate::proto::wa_e2e::{DeviceSentMessage, Message, SenderKeyDistributionMessage};
+use crate::signal::{
+    CiphertextMessage, PreKeyBundle, ProtocolAddress, SenderKeyName, SenderKeyRecord, SessionRecord,
+};
+use crate::types::{EditAttribute, Jid, MessageId, GROUP_SERVER};
+use prost::Message as _;
+use std::time::{Duration, SystemTime};
 
 /// Response from sending a message.
 #[derive(Clone, Debug)]
 pub struct SendResponse {
+    /// Server timestamp of the message (from its ack).
     pub timestamp: SystemTime,
     pub id: MessageId,
+    /// Set by the server for newsletter messages.
     pub server_id: Option<i32>,
     pub sender: Option<Jid>,
 }
 
-/// Optional parameters for SendMessage.
+/// Optional parameters for [Client::send] and [Client::send_message].
 #[derive(Clone, Debug, Default)]
 pub struct SendRequestExtra {
     pub id: Option<MessageId>,
+    /// Send to one of our own devices (`category="peer"`), e.g. app state key requests:
+    /// `to` is that device's JID and the message is encrypted for it alone.
     pub peer: bool,
+    /// How long to wait for the server's ack (default: the client's request timeout).
     pub timeout: Option<std::time::Duration>,
+    /// `edit` attribute; by default it is derived from the message
+    /// ([EditAttribute::for_message]).
+    pub edit: Option<EditAttribute>,
+    /// Handle of media uploaded for a newsletter message (`media_id` attribute).
+    pub media_handle: Option<String>,
+    /// Extra children of the `<message>` node (such as `<meta>`), sent as given.
+    pub meta_nodes: Vec<Node>,
+}
+
+/// A `<message>` node ready to send, and the devices it gives our group sender key to.
+struct PreparedMessage {
+    node: Node,
+    distributed_to: Vec<Jid>,
+}
+
+/// A group message encrypted once with our sender key (`<enc type="skmsg">`), and our
+/// sender key distribution message for the participant devices that don't have it yet.
+#[derive(Clone, Debug)]
+pub(crate) struct GroupCiphertext {
+    pub skmsg: Vec<u8>,
+    pub distribution: Vec<u8>,
+    pub needs_distribution: Vec<Jid>,
+}
+
+impl Client {
+    /// Send a text message and wait for the server to acknowledge it; shorthand for
+    /// [Client::send] with a `conversation` message.
+    pub async fn send_message(
+        &self,
+        to: &Jid,
+        body: &str,
+        extra: Option<SendRequestExtra>,
+    ) -> crate::Result<SendResponse> {
+        let message = Message {
+            conversation: Some(body.to_string()),
+            ..Default::default()
+        };
+        self.send(to, message, extra).await
+    }
+
+    /// Encrypt and send any message to a user or group (or, with [SendRequestExtra::peer],
+    /// one of our devices) and wait for the server to acknowledge it.
+    ///
+    /// ```ignore
+    /// use whatsapp_pkg::proto::wa_e2e::{Message, ReactionMessage};
+    /// let reaction = ReactionMessage {
+    ///     key: Some(key),
+    ///     text: Some("👍".into()),
+    ///     ..Default::default()
+    /// };
+    /// let message = Message {
+    ///     reaction_message: Some(reaction),
+    ///     ..Default::default()
+    /// };
+    /// client.send(&chat, message, None).await?;
+    /// ```
+    pub async fn send(
+        &self,
+        to: &Jid,
+        message: Message,
+        extra: Option<SendRequestExtra>,
+    ) -> crate::Result<SendResponse> {
+        if !self.is_connected() {
+            return Err(Error::NotConnected);
+        }
+        let extra = extra.unwrap_or_default();
+        let own_id = self.get_own_id().await.ok_or(Error::NotLoggedIn)?;
+        let id = extra
+            .id
+            .clone()
+            .unwrap_or_else(|| self.generate_message_id());
+        self.add_recent_message(to, &id, &message);
+        let prepared = if extra.peer {
+            self.prepare_peer_message(to, &id, &message).await?
+        } else if to.server == GROUP_SERVER {
+            self.prepare_group_message(to, &id, &message).await?
+        } else {
+            self.prepare_direct_message(to, &own_id, &id, &message)
+                .await?
+        };
+        let mut node = prepared.node;
+        let edit = extra
+            .edit
+            .unwrap_or_else(|| EditAttribute::for_message(&message));
+        if edit != EditAttribute::Empty {
+            node = node.with_attr("edit", edit.as_str());
+        }
+        if let Some(handle) = extra.media_handle {
+            node = node.with_attr("media_id", handle);
+        }
+        if let NodeContent::Nodes(children) = &mut node.content {
+            children.extend(extra.meta_nodes);
+        }
+        let ack = self.send_node_and_wait(&node, &id, extra.timeout).await?;
+        if let Some(error) = ack.attrs.get("error") {
+            return Err(SendError::Server(format!("message rejected with error {}", error)).into());
+        }
+        if !prepared.distributed_to.is_empty() {
+            self.mark_sender_key_distributed(to, &prepared.distributed_to);
+        }
+        let timestamp = ack
+            .attrs
+            .get("t")
+            .and_then(|t| t.parse().ok())
+            .map(|t| SystemTime::UNIX_EPOCH + Duration::from_secs(t))
+            .unwrap_or_else(SystemTime::now);
+        Ok(SendResponse {
+            timestamp,
+            id,
+            server_id: ack.attrs.get("server_id").and_then(|s| s.parse().ok()),
+            sender: Some(own_id),
+        })
+    }
+
+    /// Encrypt for every device of `to` and, as a DeviceSentMessage, for our other devices.
+    async fn prepare_direct_message(
+        &self,
+        to: &Jid,
+        own_id: &Jid,
+        id: &str,
+        message: &Message,
+    ) -> crate::Result<PreparedMessage> {
+        let devices = self.get_user_devices(&[to.clone(), own_id.clone()]).await?;
+        let plaintext = pad_message(message.encode_to_vec());
+        let sent_to_self = pad_message(
+            Message {
+                device_sent_message: Some(Box::new(DeviceSentMessage {
+                    destination_jid: Some(to.to_non_ad().to_string()),
+                    message: Some(Box::new(message.clone())),
+                    phash: None,
+                })),
+                message_context_info: message.message_context_info.clone(),
+                ..Default::default()
+            }
+            .encode_to_vec(),
+        );
+        let targets = devices
+            .into_iter()
+            .filter(|d| d != own_id)
+            .map(|d| {
+                let own = d.user == own_id.user && d.server == own_id.server;
+                let plaintext = if own { &sent_to_self } else { &plaintext };
+                (d, plaintext.as_slice())
+            })
+            .collect();
+        let participants = self.encrypt_pairwise(targets, media_type(message)).await?;
+        if participants.is_empty() {
+            return Err(SendError::EncryptionFailed.into());
+        }
+        let node = self
+            .message_node(to, id, message, participants, None)
+            .await?;
+        Ok(PreparedMessage {
+            node,
+            distributed_to: Vec::new(),
+        })
+    }
+
+    /// Encrypt for a single device of ours, with the `<enc>` directly in the `<message>`.
+    async fn prepare_peer_message(
+        &self,
+        to: &Jid,
+        id: &str,
+        message: &Message,
+    ) -> crate::Result<PreparedMessage> {
+        let plaintext = pad_message(message.encode_to_vec());
+        let (_, enc) = self
+            .encrypt_pairwise(vec![(to.clone(), plaintext.as_slice())], None)
+            .await?
+            .pop()
+            .ok_or(SendError::EncryptionFailed)?;
+        let include_identity = is_prekey_message(&enc);
+        let mut children = vec![enc];
+        if include_identity {
+            children.push(self.device_identity_node().await?);
+        }
+        let node = Node::new("message")
+            .with_attr("id", id)
+            .with_attr("type", message_type(message))
+            .with_attr("to", to.to_string())
+            .with_attr("category", "peer")
+            .with_children(children);
+        Ok(PreparedMessage {
+            node,
+            distributed_to: Vec::new(),
+        })
+    }
+
+    /// Encrypt once with our sender key, and give the key (pairwise) to the participant
+    /// devices that don't have it yet.
+    async fn prepare_group_message(
+        &self,
+        group: &Jid,
+        id: &str,
+        message: &Message,
+    ) -> crate::Result<PreparedMessage> {
+        let participants = self.get_group_participants(group).await?;
+        let devices = self.get_user_devices(&participants).await?;
+        let plaintext = pad_message(message.encode_to_vec());
+        let ciphertext = self.encrypt_for_group(group, &devices, &plaintext).await?;
+        let distribution = pad_message(
+            Message {
+                sender_key_distribution_message: Some(SenderKeyDistributionMessage {
+                    group_id: Some(group.to_string()),
+                    axolotl_sender_key_distribution_message: Some(ciphertext.distribution),
+                }),
+                ..Default::default()
+            }
+            .encode_to_vec(),
+        );
+        let targets = ciphertext
+            .needs_distribution
+            .into_iter()
+            .map(|d| (d, distribution.as_slice()))
+            .collect();
+        let media_type = media_type(message);
+        let participants = self.encrypt_pairwise(targets, media_type).await?;
+        let distributed_to = participants.iter().map(|(jid, _)| jid.clone()).collect();
+        let skmsg = enc_node("skmsg", ciphertext.skmsg, media_type);
+        let node = self
+            .message_node(group, id, message, participants, Some(skmsg))
+            .await?;
+        Ok(PreparedMessage {
+            node,
+            distributed_to,
+        })
+    }
+
+    /// Encrypt each plaintext for its device, starting sessions (from fetched prekey
+    /// bundles) where there are none yet. Devices that can't be encrypted for are logged and
+    /// left out.
+    async fn encrypt_pairwise(
+        &self,
+        targets: Vec<(Jid, &[u8])>,
+        media_type: Option<&'static str>,
+    ) -> crate::Result<Vec<(Jid, Node)>> {
+        let device = self.device.read().await.clone().ok_or(Error::NotLoggedIn)?;
+        let identity = device
+            .identity_key_pair()
+            .ok_or(StoreError::IdentityNotFound)?;
+        let mut missing = Vec::new();
+        for (jid, _) in &targets {
+            if !self.store.has_session(&ProtocolAddress::from(jid)).await? {
+                missing.push(jid.clone());
+            }
+        }
+        let bundles = if missing.is_empty() {
+            Default::default()
+        } else {
+            self.fetch_prekey_bundles(&missing).await?
+        };
+        let mut encrypted = Vec::with_capacity(targets.len());
+        for (jid, plaintext) in targets {
+            let result = self
+                .encrypt_for_device(
+                    &identity,
+                    device.registration_id,
+                    &jid,
+                    plaintext,
+                    bundles.get(&jid),
+                )
+                .await;
+            match result {
+                Ok(ciphertext) => {
+                    let enc = enc_node(
+                        ciphertext.enc_type(),
+                        ciphertext.serialize().to_vec(),
+                        media_type,
+                    );
+                    encrypted.push((jid, enc));
+                }
+                Err(e) => tracing::warn!(%jid, error = %e, "failed to encrypt message for device"),
+            }
+        }
+        Ok(encrypted)
+    }
+
+    /// Encrypt with the session for `jid`, first starting one from `bundle` if needed.
+    pub(super) async fn encrypt_for_device(
+        &self,
+        identity: &KeyPair,
+        registration_id: u32,
+        jid: &Jid,
+        plaintext: &[u8],
+        bundle: Option<&PreKeyBundle>,
+    ) -> crate::Result<CiphertextMessage> {
+        let _signal = self.signal_lock.lock().await;
+        self.encrypt_for_device_locked(identity, registration_id, jid, plaintext, bundle)
+            .await
+    }
+
+    /// [Client::encrypt_for_device] for callers already holding `signal_lock`.
+    pub(super) async fn encrypt_for_device_locked(
+        &self,
+        identity: &KeyPair,
+        registration_id: u32,
+        jid: &Jid,
+        plaintext: &[u8],
+        bundle: Option<&PreKeyBundle>,
+    ) -> crate::Result<CiphertextMessage> {
+        let address = ProtocolAddress::from(jid);
+        let mut record = match self.store.get_session(&address).await? {
+            Some(bytes) => SessionRecord::deserialize(&bytes)?,
+            None => SessionRecord::new(),
+        };
+        if !record.has_session() {
+            let bundle = bundle.ok_or(crate::error::SignalError::NoSession)?;
+            record.process_bundle(identity, registration_id, bundle)?;
+            self.store
+                .put_identity(&address, bundle.identity_key)
+                .await?;
+        }
+        let ciphertext = record.encrypt(plaintext)?;
+        self.store
+            .put_session(&address, &record.serialize())
+            .await?;
+        Ok(ciphertext)
+    }
+
+    /// `<message>` with one `<to>` per pairwise-encrypted device, the group `<enc>` if any,
+    /// and our signed device identity when a prekey message starts a session.
+    async fn message_node(
+        &self,
+        to: &Jid,
+        id: &str,
+        message: &Message,
+        participants: Vec<(Jid, Node)>,
+        group_enc: Option<Node>,
+    ) -> crate::Result<Node> {
+        let include_identity = participants.iter().any(|(_, enc)| is_prekey_message(enc));
+        let mut children = Vec::new();
+        if !participants.is_empty() {
+            children.push(
+                Node::new("participants").with_children(
+                    participants
+                        .into_iter()
+                        .map(|(jid, enc)| {
+                            Node::new("to")
+                                .with_attr("jid", jid.to_string())
+                                .with_children(vec![enc])
+                        })
+                        .collect(),
+                ),
+            );
+        }
+        children.extend(group_enc);
+        if include_identity {
+            children.push(self.device_identity_node().await?);
+        }
+        Ok(Node::new("message")
+            .with_attr("id", id)
+            .with_attr("type", message_type(message))
+            .with_attr("to", to.to_string())
+            .with_children(children))
+    }
+
+    /// `<device-identity>`: our signed device identity, which lets the recipients of a
+    /// prekey message verify the new session belongs to our account.
+    pub(super) async fn device_identity_node(&self) -> crate::Result<Node> {
+        let account = self
+            .device
+            .read()
+            .await
+            .as_ref()
+            .and_then(|d| d.account.clone())
+            .ok_or(Error::NotLoggedIn)?;
+        Ok(Node::new("device-identity").with_content(account))
+    }
+
+    /// Encrypt a group message with our sender key for `group`, creating the key on first use.
+    /// The distribution message must reach every device in `needs_distribution` (encrypted
+    /// pairwise) before they can decrypt; call [Client::mark_sender_key_distributed] once the
+    /// server has accepted it.
+    pub(crate) async fn encrypt_for_group(
+        &self,
+        group: &Jid,
+        participants: &[Jid],
+        plaintext: &[u8],
+    ) -> crate::Result<GroupCiphertext> {
+        let own_id = self.get_own_id().await.ok_or(Error::NotLoggedIn)?;
+        let name = SenderKeyName::new(group, &own_id);
+        let (distribution, skmsg) = {
+            let _signal = self.signal_lock.lock().await;
+            let mut record = match self.store.get_sender_key(&name).await? {
+                Some(bytes) => SenderKeyRecord::deserialize(&bytes)?,
+                None => SenderKeyRecord::new(),
+            };
+            let distribution = record.create_distribution();
+            let skmsg = record.encrypt(plaintext)?;
+            self.store
+                .put_sender_key(&name, &record.serialize())
+                .await?;
+            (distribution, skmsg)
+        };
+        let needs_distribution = {
+            let distributed = self.sender_key_distributed.lock().unwrap();
+            let has_key = distributed.get(group);
+            participants
+                .iter()
+                .filter(|p| **p != own_id && !has_key.is_some_and(|h| h.contains(*p)))
+                .cloned()
+                .collect()
+        };
+        Ok(GroupCiphertext {
+            skmsg: skmsg.serialize().to_vec(),
+            distribution: distribution.serialize().to_vec(),
+            needs_distribution,
+        })
+    }
+
+    /// Remember that these devices received our sender key for `group`.
+    pub(crate) fn mark_sender_key_distributed(&self, group: &Jid, devices: &[Jid]) {
+        self.sender_key_distributed
+            .lock()
+            .unwrap()
+            .entry(group.clone())
+            .or_default()
+            .extend(devices.iter().cloned());
+    }
+}
+
+pub(super) fn is_prekey_message(enc: &Node) -> bool {
+    enc.attrs.get("type").is_some_and(|t| t == "pkmsg")
+}
+
+pub(super) fn enc_node(enc_type: &str, ciphertext: Vec<u8>, media_type: Option<&str>) -> Node {
+    let mut enc = Node::new("enc")
+        .with_attr("v", "2")
+        .with_attr("type", enc_type);
+    if let Some(media_type) = media_type {
+        enc = enc.with_attr("mediatype", media_type);
+    }
+    enc.with_content(ciphertext)
+}
+
+/// Append 1–15 bytes of random padding, each holding the padding length.
+pub(super) fn pad_message(mut plaintext: Vec<u8>) -> Vec<u8> {
+    let pad = match rand::random::<u8>() & 0x0f {
+        0 => 0x0f,
+        n => n,
+    };
+    plaintext.resize(plaintext.len() + pad as usize, pad);
+    plaintext
+}
+
+/// The message wrapped by a view-once, ephemeral, edit or document-with-caption message.
+fn inner_message(message: &Message) -> Option<&Message> {
+    [
+        &message.view_once_message,
+        &message.view_once_message_v2,
+        &message.view_once_message_v2_extension,
+        &message.ephemeral_message,
+        &message.document_with_caption_message,
+        &message.edited_message,
+    ]
+    .into_iter()
+    .flatten()
+    .find_map(|wrapper| wrapper.message.as_deref())
+}
+
+/// `type` attribute of the `<message>` node.
+pub(super) fn message_type(message: &Message) -> &'static str {
+    if let Some(inner) = inner_message(message) {
+        return message_type(inner);
+    }
+    if message.reaction_message.is_some() || message.enc_reaction_message.is_some() {
+        "reaction"
+    } else if message.poll_creation_message.is_some()
+        || message.poll_creation_message_v2.is_some()
+        || message.poll_creation_message_v3.is_some()
+        || message.poll_update_message.is_some()
+    {
+        "poll"
+    } else if media_type(message).is_some() {
+        "media"
+    } else {
+        "text"
+    }
+}
+
+/// `mediatype` attribute of the `<enc>` nodes, for media messages.
+pub(super) fn media_type(message: &Message) -> Option<&'static str> {
+    if let Some(inner) = inner_message(message) {
+        return media_type(inner);
+    }
+    let m = message;
+    if m.image_message.is_some() {
+        Some("image")
+    } else if m.sticker_message.is_some() {
+        Some("sticker")
+    } else if m.document_message.is_some() {
+        Some("document")
+    } else if let Some(audio) = &m.audio_message {
+        Some(if audio.ptt() { "ptt" } else { "audio" })
+    } else if let Some(video) = &m.video_message {
+        Some(if video.gif_playback() { "gif" } else { "video" })
+    } else if m.ptv_message.is_some() {
+        Some("ptv")
+    } else if m.contact_message.is_some() {
+        Some("vcard")
+    } else if m.contacts_array_message.is_some() {
+        Some("contact_array")
+    } else if m.live_location_message.is_some() {
+        Some("livelocation")
+    } else if m.location_message.is_some() {
+        Some("location")
+    } else if m.group_invite_message.is_some()
+        || m.extended_text_message
+            .as_ref()
+            .is_some_and(|t| t.title.is_some())
+    {
+        Some("url")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::{
+        connect_client, connected_client, connected_client_with, jid, paired_device,
+    };
+    use crate::client::receive::unpad_message;
+    use crate::crypto::DJB_KEY_TYPE;
+    use crate::proto::wa_common::MessageKey;
+    use crate::proto::wa_e2e::{
+        protocol_message, AudioMessage, FutureProofMessage, ImageMessage, ProtocolMessage,
+        ReactionMessage,
+    };
+    use crate::signal::{
+        PreKey, PreKeySignalMessage, SenderKeyDistributionMessage, SenderKeyMessage, SignalMessage,
+        SignedPreKey,
+    };
+    use crate::store::{Device, DeviceStore, MemoryStore};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn group_messages_encrypt_once_and_distribute_to_new_devices() {
+        let (client, _server, _) = connected_client().await;
+        let group = jid("120363001@g.us");
+        let alice = jid("5511111:0@s.whatsapp.net");
+        let bob = jid("5522222:2@s.whatsapp.net");
+        let own = jid("123:4@s.whatsapp.net");
+
+        let first = client
+            .encrypt_for_group(&group, &[alice.clone(), own.clone()], b"hello group")
+            .await
+            .unwrap();
+        assert_eq!(first.needs_distribution, vec![alice.clone()]);
+        client.mark_sender_key_distributed(&group, &first.needs_distribution);
+
+        let second = client
+            .encrypt_for_group(&group, &[alice.clone(), bob.clone(), own], b"welcome bob")
+            .await
+            .unwrap();
+        assert_eq!(second.needs_distribution, vec![bob]);
+        let key_id = |d: &[u8]| SenderKeyDistributionMessage::deserialize(d).unwrap().key_id;
+        assert_eq!(key_id(&second.distribution), key_id(&first.distribution));
+
+        // A member who got the distribution decrypts both messages.
+        let mut member = SenderKeyRecord::new();
+        member.process_distribution(
+            &SenderKeyDistributionMessage::deserialize(&first.distribution).unwrap(),
+        );
+        for (sent, text) in [(&first, &b"hello group"[..]), (&second, b"welcome bob")] {
+            let message = SenderKeyMessage::deserialize(&sent.skmsg).unwrap();
+            assert_eq!(member.decrypt(&message).unwrap(), text);
+        }
+    }
+
+    #[tokio::test]
+    async fn group_encryption_requires_login() {
+        let client = Client::new(Arc::new(MemoryStore::new()));
+        assert!(matches!(
+            client
+                .encrypt_for_group(&jid("120363001@g.us"), &[], b"hi")
+                .await,
+            Err(Error::NotLoggedIn)
+        ));
+    }
+
+    const ACCOUNT: &[u8] = b"signed device identity";
+
+    /// Paired device with identity keys (but no signed prekey, so the login doesn't check the
+    /// server's prekey count).
+    fn sending_device() -> Device {
+        let identity = KeyPair::generate();
+        Device {
+            identity_key_pub: Some(identity.public),
+            identity_key_priv: Some(identity.private),
+            registration_id: 1234,
+            account: Some(ACCOUNT.to_vec()),
+            ..paired_device()
+        }
+    }
+
+    /// [MemoryStore] that takes a while to return loaded sessions and sender keys, like a
+    /// database round trip, so concurrent tasks interleave there.
+    #[derive(Default)]
+    struct SlowStore(MemoryStore);
+
+    impl SlowStore {
+        async fn pause() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl crate::store::IdentityStore for SlowStore {
+        async fn put_identity(
+            &self,
+            address: &ProtocolAddress,
+            key: [u8; 32],
+        ) -> crate::Result<()> {
+            self.0.put_identity(address, key).await
+        }
+        async fn get_identity(&self, address: &ProtocolAddress) -> crate::Result<Option<[u8; 32]>> {
+            self.0.get_identity(address).await
+        }
+        async fn delete_identity(&self, address: &ProtocolAddress) -> crate::Result<()> {
+            self.0.delete_identity(address).await
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl crate::store::SessionStore for SlowStore {
+        async fn get_session(&self, address: &ProtocolAddress) -> crate::Result<Option<Vec<u8>>> {
+            let record = self.0.get_session(address).await;
+            Self::pause().await;
+            record
+        }
+        async fn put_session(&self, address: &ProtocolAddress, record: &[u8]) -> crate::Result<()> {
+            self.0.put_session(address, record).await
+        }
+        async fn delete_session(&self, address: &ProtocolAddress) -> crate::Result<()> {
+            self.0.delete_session(address).await
+        }
+        async fn delete_all_sessions(&self, name: &str) -> crate::Result<()> {
+            self.0.delete_all_sessions(name).await
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl crate::store::PreKeyStore for SlowStore {
+        async fn put_prekey(&self, prekey: &PreKey) -> crate::Result<()> {
+            self.0.put_prekey(prekey).await
+        }
+        async fn get_prekey(&self, id: u32) -> crate::Result<Option<PreKey>> {
+            self.0.get_prekey(id).await
+        }
+        async fn remove_prekey(&self, id: u32) -> crate::Result<()> {
+            self.0.remove_prekey(id).await
+        }
+        async fn last_prekey_id(&self) -> crate::Result<u32> {
+            self.0.last_prekey_id().await
+        }
+        async fn unuploaded_prekeys(&self) -> crate::Result<Vec<PreKey>> {
+            self.0.unuploaded_prekeys().await
+        }
+        async fn mark_prekeys_uploaded(&self, up_to_id: u32) -> crate::Result<()> {
+            self.0.mark_prekeys_uploaded(up_to_id).await
+        }
+        async fn put_signed_prekey(&self, prekey: &SignedPreKey) -> crate::Result<()> {
+            self.0.put_signed_prekey(prekey).await
+        }
+        async fn get_signed_prekey(&self, id: u32) -> crate::Result<Option<SignedPreKey>> {
+            self.0.get_signed_prekey(id).await
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl crate::store::SenderKeyStore for SlowStore {
+        async fn get_sender_key(&self, name: &SenderKeyName) -> crate::Result<Option<Vec<u8>>> {
+            let record = self.0.get_sender_key(name).await;
+            Self::pause().await;
+            record
+        }
+        async fn put_sender_key(&self, name: &SenderKeyName, record: &[u8]) -> crate::Result<()> {
+            self.0.put_sender_key(name, record).await
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl DeviceStore for SlowStore {
+        async fn get_first_device(&self) -> crate::Result<Option<Device>> {
+            self.0.get_first_device().await
+        }
+        async fn get_device(&self, jid: &Jid) -> crate::Result<Option<Device>> {
+            self.0.get_device(jid).await
+        }
+        async fn save(&self, device: &Device) -> crate::Result<()> {
+            self.0.save(device).await
+        }
+        async fn delete(&self, jid: &Jid) -> crate::Result<()> {
+            self.0.delete(jid).await
+        }
+        async fn get_all_devices(&self) -> crate::Result<Vec<Device>> {
+            self.0.get_all_devices().await
+        }
+    }
+
+    /// Another device, receiving what the client sends it.
+    struct Remote {
+        jid: Jid,
+        identity: KeyPair,
+        signed_prekey: SignedPreKey,
+        prekey: PreKey,
+        session: SessionRecord,
+    }
+
+    impl Remote {
+        fn new(jid: &str) -> Self {
+            let identity = KeyPair::generate();
+            Self {
+                jid: jid.parse().unwrap(),
+                signed_prekey: SignedPreKey::generate(1, &identity),
+                identity,
+                prekey: PreKey::generate(7),
+                session: SessionRecord::new(),
+            }
+        }
+
+        /// `<user>` of the prekey fetch response.
+        fn bundle_node(&self) -> Node {
+            let key = |tag: &str, id: u32, public: &[u8; 32]| {
+                Node::new(tag).with_children(vec![
+                    Node::new("id").with_content(id.to_be_bytes()[1..].to_vec()),
+                    Node::new("value").with_content(public.to_vec()),
+                ])
+            };
+            let mut skey = key(
+                "skey",
+                self.signed_prekey.id,
+                &self.signed_prekey.key_pair.public,
+            );
+            if let NodeContent::Nodes(children) = &mut skey.content {
+                children.push(
+                    Node::new("signature").with_content(self.signed_prekey.signature.to_vec()),
+                );
+            }
+            Node::new("user")
+                .with_attr("jid", self.jid.to_string())
+                .with_children(vec![
+                    Node::new("registration").with_content(99u32.to_be_bytes().to_vec()),
+                    Node::new("type").with_content(vec![DJB_KEY_TYPE]),
+                    Node::new("identity").with_content(self.identity.public.to_vec()),
+                    skey,
+                    key("key", self.prekey.id, &self.prekey.key_pair.public),
+                ])
+        }
+
+        fn decrypt(&mut self, enc: &Node) -> Message {
+            let NodeContent::Bytes(bytes) = &enc.content else {
+                panic!("enc without content");
+            };
+            let plaintext = match enc.attrs["type"].as_str() {
+                "pkmsg" => self
+                    .session
+                    .decrypt_prekey_message(
+                        &self.identity,
+                        99,
+                        &PreKeySignalMessage::deserialize(bytes).unwrap(),
+                        &self.signed_prekey.key_pair,
+                        Some(&self.prekey.key_pair),
+                    )
+                    .unwrap(),
+                "msg" => self
+                    .session
+                    .decrypt(&SignalMessage::deserialize(bytes).unwrap())
+                    .unwrap(),
+                other => panic!("unexpected enc type {other}"),
+            };
+            let plaintext = unpad_message(plaintext).unwrap();
+            Message::decode(plaintext.as_slice()).unwrap()
+        }
+    }
+
+    fn reply(iq: &Node, content: Vec<Node>) -> Node {
+        Node::new("iq")
+            .with_attr("type", "result")
+            .with_attr("id", iq.attrs["id"].clone())
+            .with_children(content)
+    }
+
+    fn usync_reply(iq: &Node, users: &[(&str, &[u16])]) -> Node {
+        let users = users
+            .iter()
+            .map(|(jid, ids)| {
+                let devices = ids
+                    .iter()
+                    .map(|id| Node::new("device").with_attr("id", id.to_string()))
+                    .collect();
+                Node::new("user")
+                    .with_attr("jid", *jid)
+                    .with_children(vec![Node::new("devices")
+                        .with_children(vec![Node::new("device-list").with_children(devices)])])
+            })
+            .collect();
+        reply(
+            iq,
+            vec![Node::new("usync").with_children(vec![Node::new("list").with_children(users)])],
+        )
+    }
+
+    fn ack(message: &Node) -> Node {
+        Node::new("ack")
+            .with_attr("class", "message")
+            .with_attr("id", message.attrs["id"].clone())
+            .with_attr("t", "1700000000")
+    }
+
+    fn spawn_send(
+        client: &Client,
+        to: &str,
+        body: &'static str,
+    ) -> tokio::task::JoinHandle<crate::Result<SendResponse>> {
+        let client = client.clone();
+        let to = jid(to);
+        tokio::spawn(async move { client.send_message(&to, body, None).await })
+    }
+
+    fn recipients(message: &Node) -> Vec<(String, &Node)> {
+        message
+            .get_child_by_tag("participants")
+            .map(Node::get_children)
+            .unwrap_or_default()
+            .iter()
+            .map(|to| (to.attrs["jid"].clone(), to.get_child_by_tag("enc").unwrap()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn direct_message_is_encrypted_for_every_device() {
+        let (client, server, _) = connected_client_with(sending_device(), |builder| builder).await;
+        let mut remotes = [
+            Remote::new("5511999@s.whatsapp.net"),
+            Remote::new("5511999:2@s.whatsapp.net"),
+            Remote::new("123@s.whatsapp.net"),
+        ];
+
+        let send = spawn_send(&client, "5511999@s.whatsapp.net", "hello");
+        let usync = server.next_sent().await;
+        assert_eq!(usync.attrs["xmlns"], "usync");
+        server.push(usync_reply(
+            &usync,
+            &[
+                ("5511999@s.whatsapp.net", &[0, 2]),
+                ("123@s.whatsapp.net", &[0, 4]),
+            ],
+        ));
+        let keys = server.next_sent().await;
+        assert_eq!(keys.attrs["xmlns"], "encrypt");
+        let requested: Vec<_> = keys
+            .get_child_by_tag("key")
+            .unwrap()
+            .get_children()
+            .iter()
+            .map(|u| u.attrs["jid"].clone())
+            .collect();
+        assert_eq!(
+            requested,
+            [
+                "5511999@s.whatsapp.net",
+                "5511999:2@s.whatsapp.net",
+                "123@s.whatsapp.net"
+            ],
+            "every device but ours"
+        );
+        server.push(reply(
+            &keys,
+            vec![Node::new("list").with_children(remotes.iter().map(Remote::bundle_node).collect())],
+        ));
+
+        let message = server.next_sent().await;
+        assert_eq!(message.tag, "message");
+        assert_eq!(message.attrs["type"], "text");
+        assert_eq!(message.attrs["to"], "5511999@s.whatsapp.net");
+        let identity = message.get_child_by_tag("device-identity").unwrap();
+        assert!(matches!(&identity.content, NodeContent::Bytes(b) if b == ACCOUNT));
+        let sent = recipients(&message);
+        assert_eq!(sent.len(), 3);
+        for (to, enc) in sent {
+            assert_eq!(enc.attrs["type"], "pkmsg");
+            let remote = remotes
+                .iter_mut()
+                .find(|r| r.jid.to_string() == to)
+                .unwrap();
+            let received = remote.decrypt(enc);
+            if remote.jid.user == "123" {
+                let sent = received.device_sent_message.unwrap();
+                assert_eq!(sent.destination_jid(), "5511999@s.whatsapp.net");
+                assert_eq!(sent.message.unwrap().conversation(), "hello");
+            } else {
+                assert_eq!(received.conversation(), "hello");
+            }
+        }
+        server.push(ack(&message));
+        let response = send.await.unwrap().unwrap();
+        assert_eq!(response.id, message.attrs["id"]);
+        assert_eq!(
+            response.timestamp,
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+        );
+        assert_eq!(response.sender, Some(jid("123:4@s.whatsapp.net")));
+
+        // Sessions are reused: no more prekey fetches.
+        let send = spawn_send(&client, "5511999@s.whatsapp.net", "again");
+        let usync = server.next_sent().await;
+        server.push(usync_reply(&usync, &[("5511999@s.whatsapp.net", &[0])]));
+        let message = server.next_sent().await;
+        assert_eq!(message.tag, "message");
+        let (to, enc) = &recipients(&message)[0];
+        let remote = remotes
+            .iter_mut()
+            .find(|r| r.jid.to_string() == *to)
+            .unwrap();
+        assert_eq!(remote.decrypt(enc).conversation(), "again");
+        server.push(ack(&message));
+        send.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn direct_message_fails_when_no_device_can_be_encrypted_for() {
+        let (client, server, _) = connected_client_with(sending_device(), |builder| builder).await;
+        let mut remote = Remote::new("5511999@s.whatsapp.net");
+        remote.signed_prekey.signature[0] ^= 1;
+
+        let send = spawn_send(&client, "5511999@s.whatsapp.net", "hello");
+        let usync = server.next_sent().await;
+        server.push(usync_reply(
+            &usync,
+            &[
+                ("5511999@s.whatsapp.net", &[0]),
+                ("123@s.whatsapp.net", &[4]),
+            ],
+        ));
+        let keys = server.next_sent().await;
+        assert_eq!(keys.attrs["xmlns"], "encrypt");
+        server.push(reply(
+            &keys,
+            vec![Node::new("list").with_children(vec![remote.bundle_node()])],
+        ));
+
+        assert!(matches!(
+            send.await.unwrap(),
+            Err(Error::Send(SendError::EncryptionFailed))
+        ));
+    }
+
+    #[tokio::test]
+    async fn concurrent_sends_to_the_same_device_use_distinct_ratchet_steps() {
+        let store = Arc::new(SlowStore::default());
+        store.save(&sending_device()).await.unwrap();
+        let (client, server) = connect_client(store, |builder| builder).await;
+        let mut remote = Remote::new("5511999@s.whatsapp.net");
+        let only_remote: &[(&str, &[u16])] = &[("5511999@s.whatsapp.net", &[0])];
+
+        let send = spawn_send(&client, "5511999@s.whatsapp.net", "hello");
+        let usync = server.next_sent().await;
+        server.push(usync_reply(&usync, only_remote));
+        let keys = server.next_sent().await;
+        server.push(reply(
+            &keys,
+            vec![Node::new("list").with_children(vec![remote.bundle_node()])],
+        ));
+        let message = server.next_sent().await;
+        assert_eq!(
+            remote.decrypt(recipients(&message)[0].1).conversation(),
+            "hello"
+        );
+        server.push(ack(&message));
+        send.await.unwrap().unwrap();
+
+        // Each would otherwise load the session before the other stores its ratchet step.
+        let sends = [
+            spawn_send(&client, "5511999@s.whatsapp.net", "one"),
+            spawn_send(&client, "5511999@s.whatsapp.net", "two"),
+        ];
+        for _ in 0..2 {
+            let usync = server.next_sent().await;
+            server.push(usync_reply(&usync, only_remote));
+        }
+        let mut received = Vec::new();
+        for _ in 0..2 {
+            let message = server.next_sent().await;
+            assert_eq!(message.tag, "message");
+            // Fails with DuplicateMessage if both used the same message key.
+            received.push(
+                remote
+                    .decrypt(recipients(&message)[0].1)
+                    .conversation()
+                    .to_string(),
+            );
+            server.push(ack(&message));
+        }
+        received.sort();
+        assert_eq!(received, ["one", "two"]);
+        for send in sends {
+            send.await.unwrap().unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn rejected_message_is_an_error() {
+        let (client, server, _) = connected_client_with(sending_device(), |builder| builder).await;
+        let remote = Remote::new("5511999@s.whatsapp.net");
+        let send = spawn_send(&client, "5511999@s.whatsapp.net", "hello");
+        let usync = server.next_sent().await;
+        server.push(usync_reply(&usync, &[("5511999@s.whatsapp.net", &[0])]));
+        let keys = server.next_sent().await;
+        server.push(reply(
+            &keys,
+            vec![Node::new("list").with_children(vec![remote.bundle_node()])],
+        ));
+        let message = server.next_sent().await;
+        server.push(ack(&message).with_attr("error", "479"));
+        assert!(matches!(
+            send.await.unwrap(),
+            Err(Error::Send(SendError::Server(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn group_message_carries_sender_key_until_distributed() {
+        let (client, server, _) = connected_client_with(sending_device(), |builder| builder).await;
+        let mut alice = Remote::new("5511999@s.whatsapp.net");
+        let mut alice_sender_key = SenderKeyRecord::new();
+        let group = "120363001@g.us";
+
+        let send = spawn_send(&client, group, "hi group");
+        let query = server.next_sent().await;
+        assert_eq!(query.attrs["xmlns"], "w:g2");
+        assert_eq!(query.attrs["to"], group);
+        let members = vec![Node::new("group").with_children(vec![
+            Node::new("participant").with_attr("jid", "5511999@s.whatsapp.net"),
+            Node::new("participant").with_attr("jid", "123@s.whatsapp.net"),
+        ])];
+        server.push(reply(&query, members.clone()));
+        let usync = server.next_sent().await;
+        let devices: &[(&str, &[u16])] = &[
+            ("5511999@s.whatsapp.net", &[0]),
+            ("123@s.whatsapp.net", &[4]),
+        ];
+        server.push(usync_reply(&usync, devices));
+        let keys = server.next_sent().await;
+        server.push(reply(
+            &keys,
+            vec![Node::new("list").with_children(vec![alice.bundle_node()])],
+        ));
+
+        let message = server.next_sent().await;
+        assert_eq!(message.attrs["to"], group);
+        let sent = recipients(&message);
+        assert_eq!(sent.len(), 1);
+        let distribution = alice
+            .decrypt(sent[0].1)
+            .sender_key_distribution_message
+            .unwrap();
+        assert_eq!(distribution.group_id(), group);
+        alice_sender_key.process_distribution(
+            &SenderKeyDistributionMessage::deserialize(
+                distribution.axolotl_sender_key_distribution_message(),
+            )
+            .unwrap(),
+        );
+        let decrypt_group = |record: &mut SenderKeyRecord, message: &Node| {
+            let enc = message.get_child_by_tag("enc").unwrap();
+            assert_eq!(enc.attrs["type"], "skmsg");
+            let NodeContent::Bytes(bytes) = &enc.content else {
+                panic!("enc without content");
+            };
+            let plaintext = record
+                .decrypt(&SenderKeyMessage::deserialize(bytes).unwrap())
+                .unwrap();
+            let plaintext = unpad_message(plaintext).unwrap();
+            Message::decode(plaintext.as_slice()).unwrap()
+        };
+        assert_eq!(
+            decrypt_group(&mut alice_sender_key, &message).conversation(),
+            "hi group"
+        );
+        server.push(ack(&message));
+        send.await.unwrap().unwrap();
+
+        // Alice has our sender key now.
+        let send = spawn_send(&client, group, "second");
+        let query = server.next_sent().await;
+        server.push(reply(&query, members));
+        let usync = server.next_sent().await;
+        server.push(usync_reply(&usync, devices));
+        let message = server.next_sent().await;
+        assert!(message.get_child_by_tag("participants").is_none());
+        assert!(message.get_child_by_tag("device-identity").is_none());
+        assert_eq!(
+            decrypt_group(&mut alice_sender_key, &message).conversation(),
+            "second"
+        );
+        server.push(ack(&message));
+        send.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn send_applies_extra_attributes_and_nodes() {
+        let (client, server, _) = connected_client_with(sending_device(), |builder| builder).await;
+        let mut remote = Remote::new("5511999@s.whatsapp.net");
+        let edit = Message {
+            protocol_message: Some(Box::new(ProtocolMessage {
+                key: Some(MessageKey {
+                    remote_jid: Some("5511999@s.whatsapp.net".into()),
+                    from_me: Some(true),
+                    id: Some("3EB0AA".into()),
+                    ..Default::default()
+                }),
+                r#type: Some(protocol_message::Type::MessageEdit as i32),
+                edited_message: Some(Box::new(Message {
+                    conversation: Some("fixed typo".into()),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let extra = SendRequestExtra {
+            id: Some("3EB0BB".into()),
+            media_handle: Some("handle-1".into()),
+            meta_nodes: vec![Node::new("meta").with_attr("appdata", "default")],
+            ..Default::default()
+        };
+        let send = {
+            let client = client.clone();
+            let edit = edit.clone();
+            tokio::spawn(async move {
+                client
+                    .send(&jid("5511999@s.whatsapp.net"), edit, Some(extra))
+                    .await
+            })
+        };
+        let usync = server.next_sent().await;
+        server.push(usync_reply(&usync, &[("5511999@s.whatsapp.net", &[0])]));
+        let keys = server.next_sent().await;
+        server.push(reply(
+            &keys,
+            vec![Node::new("list").with_children(vec![remote.bundle_node()])],
+        ));
+
+        let message = server.next_sent().await;
+        assert_eq!(message.attrs["id"], "3EB0BB");
+        assert_eq!(message.attrs["edit"], "1");
+        assert_eq!(message.attrs["media_id"], "handle-1");
+        assert_eq!(
+            message.get_child_by_tag("meta").unwrap().attrs["appdata"],
+            "default"
+        );
+        let (_, enc) = recipients(&message).pop().unwrap();
+        assert_eq!(remote.decrypt(enc), edit);
+        server.push(ack(&message));
+        assert_eq!(send.await.unwrap().unwrap().id, "3EB0BB");
+    }
+
+    #[tokio::test]
+    async fn peer_message_is_encrypted_for_one_device() {
+        let (client, server, _) = connected_client_with(sending_device(), |builder| builder).await;
+        let mut phone = Remote::new("123@s.whatsapp.net");
+        let request = Message {
+            protocol_message: Some(Box::new(ProtocolMessage {
+                r#type: Some(protocol_message::Type::AppStateSyncKeyRequest as i32),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let send = {
+            let client = client.clone();
+            let request = request.clone();
+            let extra = SendRequestExtra {
+                peer: true,
+                ..Default::default()
+            };
+            tokio::spawn(async move {
+                client
+                    .send(&jid("123@s.whatsapp.net"), request, Some(extra))
+                    .await
+            })
+        };
+        let keys = server.next_sent().await;
+        assert_eq!(keys.attrs["xmlns"], "encrypt", "no device list query");
+        server.push(reply(
+            &keys,
+            vec![Node::new("list").with_children(vec![phone.bundle_node()])],
+        ));
+
+        let message = server.next_sent().await;
+        assert_eq!(message.attrs["category"], "peer");
+        assert_eq!(message.attrs["to"], "123@s.whatsapp.net");
+        assert!(message.get_child_by_tag("participants").is_none());
+        assert!(message.get_child_by_tag("device-identity").is_some());
+        assert_eq!(
+            phone.decrypt(message.get_child_by_tag("enc").unwrap()),
+            request
+        );
+        server.push(ack(&message));
+        send.await.unwrap().unwrap();
+    }
+
+    #[test]
+    fn message_and_media_types() {
+        let text = Message {
+            conversation: Some("hi".into()),
+            ..Default::default()
+        };
+        assert_eq!((message_type(&text), media_type(&text)), ("text", None));
+
+        let voice_note = Message {
+            audio_message: Some(Box::new(AudioMessage {
+                ptt: Some(true),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        assert_eq!(
+            (message_type(&voice_note), media_type(&voice_note)),
+            ("media", Some("ptt"))
+        );
+
+        let view_once = Message {
+            view_once_message: Some(Box::new(FutureProofMessage {
+                message: Some(Box::new(Message {
+                    image_message: Some(Box::new(ImageMessage::default())),
+                    ..Default::default()
+                })),
+            })),
+            ..Default::default()
+        };
+        assert_eq!(
+            (message_type(&view_once), media_type(&view_once)),
+            ("media", Some("image"))
+        );
+
+        let reaction = Message {
+            reaction_message: Some(ReactionMessage::default()),
+            ..Default::default()
+        };
+        assert_eq!(message_type(&reaction), "reaction");
+    }
+
+    #[test]
+    fn padding_is_one_to_fifteen_length_bytes() {
+        for _ in 0..100 {
+            let padded = pad_message(b"payload".to_vec());
+            assert!((1..=15).contains(padded.last().unwrap()));
+            assert_eq!(unpad_message(padded).unwrap(), b"payload");
+        }
+    }
 }
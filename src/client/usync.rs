@@ -0,0 +1,129 @@
+//! User sync (`usync`) queries: which devices a user has.
+
+use super::request::{InfoQuery, IqType};
+use super::Client;
+use crate::binary::Node;
+use crate::types::Jid;
+
+impl Client {
+    /// Devices of the given users (primary device included), as AD-JIDs. Users the server
+    /// knows nothing about are left out.
+    pub async fn get_user_devices(&self, users: &[Jid]) -> crate::Result<Vec<Jid>> {
+        let list = users
+            .iter()
+            .map(|u| Node::new("user").with_attr("jid", u.to_non_ad().to_string()))
+            .collect();
+        let response = self
+            .send_iq(InfoQuery {
+                namespace: "usync",
+                iq_type: IqType::Get,
+                to: Jid::default_server(),
+                content: vec![Node::new("usync")
+                    .with_attr("sid", self.generate_request_id())
+                    .with_attr("mode", "query")
+                    .with_attr("last", "true")
+                    .with_attr("index", "0")
+                    .with_attr("context", "message")
+                    .with_children(vec![
+                        Node::new("query")
+                            .with_children(vec![Node::new("devices").with_attr("version", "2")]),
+                        Node::new("list").with_children(list),
+                    ])],
+            })
+            .await?;
+        let users = response
+            .get_child_by_tag("usync")
+            .and_then(|u| u.get_child_by_tag("list"))
+            .map(Node::get_children)
+            .unwrap_or_default();
+        let mut devices = Vec::new();
+        for user in users.iter().filter(|u| u.tag == "user") {
+            let Some(jid) = user.attrs.get("jid").and_then(|j| j.parse::<Jid>().ok()) else {
+                continue;
+            };
+            let list = user
+                .get_child_by_tag("devices")
+                .and_then(|d| d.get_child_by_tag("device-list"))
+                .map(Node::get_children)
+                .unwrap_or_default();
+            for device in list.iter().filter(|d| d.tag == "device") {
+                if let Some(id) = device.attrs.get("id").and_then(|id| id.parse().ok()) {
+                    devices.push(Jid::new_ad(jid.user.clone(), 0, id, jid.server.clone()));
+                }
+            }
+        }
+        Ok(devices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::connected_client;
+
+    #[tokio::test]
+    async fn user_devices_are_listed_per_user() {
+        let (client, server, _) = connected_client().await;
+
+        let users = vec![
+            "5511999:2@s.whatsapp.net".parse().unwrap(),
+            "5522888@s.whatsapp.net".parse().unwrap(),
+        ];
+        let query = {
+            let client = client.clone();
+            tokio::spawn(async move { client.get_user_devices(&users).await })
+        };
+        let iq = server.next_sent().await;
+        assert_eq!(iq.attrs["xmlns"], "usync");
+        let usync = iq.get_child_by_tag("usync").unwrap();
+        assert_eq!(usync.attrs["context"], "message");
+        let jids: Vec<_> = usync
+            .get_child_by_tag("list")
+            .unwrap()
+            .get_children()
+            .iter()
+            .map(|u| u.attrs["jid"].clone())
+            .collect();
+        assert_eq!(jids, ["5511999@s.whatsapp.net", "5522888@s.whatsapp.net"]);
+
+        let user = |jid: &str, ids: &[&str]| {
+            Node::new("user")
+                .with_attr("jid", jid)
+                .with_children(vec![Node::new("devices").with_children(vec![Node::new(
+                    "device-list",
+                )
+                .with_children(
+                    ids.iter()
+                        .map(|id| Node::new("device").with_attr("id", *id))
+                        .collect(),
+                )])])
+        };
+        server.push(
+            Node::new("iq")
+                .with_attr("type", "result")
+                .with_attr("id", iq.attrs["id"].clone())
+                .with_children(vec![Node::new("usync").with_children(vec![Node::new(
+                    "list",
+                )
+                .with_children(vec![
+                    user("5511999@s.whatsapp.net", &["0", "2"]),
+                    user("5522888@s.whatsapp.net", &["0"]),
+                ])])]),
+        );
+        let devices: Vec<String> = query
+            .await
+            .unwrap()
+            .unwrap()
+            .iter()
+            .map(Jid::to_string)
+            .collect();
+        assert_eq!(
+            devices,
+            [
+                "5511999@s.whatsapp.net",
+                "5511999:2@s.whatsapp.net",
+                "5522888@s.whatsapp.net"
+            ]
+        );
+    }
+}
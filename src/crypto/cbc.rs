@@ -0,0 +1,87 @@
+//! AES-256-CBC with PKCS#7 padding, used for Signal message bodies.
+
+use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
+use aes::{Aes256, Block};
+
+const BLOCK: usize = 16;
+
+/// Encrypt `plaintext`, padding it to a whole number of blocks.
+pub fn aes_cbc_encrypt(key: &[u8; 32], iv: &[u8; 16], plaintext: &[u8]) -> Vec<u8> {
+    let cipher = Aes256::new(key.into());
+    let pad = BLOCK - plaintext.len() % BLOCK;
+    let mut data = plaintext.to_vec();
+    data.resize(plaintext.len() + pad, pad as u8);
+    let mut previous = *iv;
+    for chunk in data.chunks_mut(BLOCK) {
+        for (b, p) in chunk.iter_mut().zip(previous) {
+            *b ^= p;
+        }
+        cipher.encrypt_block(Block::from_mut_slice(chunk));
+        previous.copy_from_slice(chunk);
+    }
+    data
+}
+
+/// Decrypt and unpad; `None` if the length or padding is invalid.
+pub fn aes_cbc_decrypt(key: &[u8; 32], iv: &[u8; 16], ciphertext: &[u8]) -> Option<Vec<u8>> {
+    if ciphertext.is_empty() || !ciphertext.len().is_multiple_of(BLOCK) {
+        return None;
+    }
+    let cipher = Aes256::new(key.into());
+    let mut data = ciphertext.to_vec();
+    let mut previous = *iv;
+    for chunk in data.chunks_mut(BLOCK) {
+        let mut next = [0u8; BLOCK];
+        next.copy_from_slice(chunk);
+        cipher.decrypt_block(Block::from_mut_slice(chunk));
+        for (b, p) in chunk.iter_mut().zip(previous) {
+            *b ^= p;
+        }
+        previous = next;
+    }
+    let pad = *data.last()? as usize;
+    if pad == 0 || pad > BLOCK || data[data.len() - pad..].iter().any(|&b| b as usize != pad) {
+        return None;
+    }
+    data.truncate(data.len() - pad);
+    Some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_nist_vector() {
+        // NIST SP 800-38A F.2.5 (CBC-AES256.Encrypt), first block; the second block of the
+        // output is the PKCS#7 padding.
+        let key: [u8; 32] =
+            hex::decode("603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4")
+                .unwrap()
+                .try_into()
+                .unwrap();
+        let iv: [u8; 16] = hex::decode("000102030405060708090a0b0c0d0e0f")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let plaintext = hex::decode("6bc1bee22e409f96e93d7e117393172a").unwrap();
+        let ciphertext = aes_cbc_encrypt(&key, &iv, &plaintext);
+        assert_eq!(
+            hex::encode(&ciphertext[..16]),
+            "f58c4c04d6e5f1ba779eabfb5f7bfbd6"
+        );
+        assert_eq!(ciphertext.len(), 32);
+        assert_eq!(aes_cbc_decrypt(&key, &iv, &ciphertext).unwrap(), plaintext);
+    }
+
+    #[test]
+    fn rejects_bad_padding_and_length() {
+        let (key, iv) = ([1u8; 32], [2u8; 16]);
+        let mut ciphertext = aes_cbc_encrypt(&key, &iv, b"hello");
+        assert_eq!(aes_cbc_decrypt(&key, &iv, &ciphertext).unwrap(), b"hello");
+        assert!(aes_cbc_decrypt(&key, &iv, &ciphertext[1..]).is_none());
+        assert!(aes_cbc_decrypt(&key, &iv, &[]).is_none());
+        *ciphertext.last_mut().unwrap() ^= 0xff;
+        assert!(aes_cbc_decrypt(&key, &iv, &ciphertext).is_none());
+    }
+}
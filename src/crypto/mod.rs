@@ -0,0 +1,90 @@
+//! Curve25519 keys as used by WhatsApp and Signal: one X25519 key pair both agrees on shared
+//! secrets and signs (XEdDSA), so identity keys are never Ed25519 keys.
+
+mod cbc;
+mod xeddsa;
+
+pub use cbc::{aes_cbc_decrypt, aes_cbc_encrypt};
+pub use xeddsa::verify;
+
+use rand::RngCore;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+/// Key type prefix of serialized Curve25519 public keys (Signal "DJB" keys).
+pub const DJB_KEY_TYPE: u8 = 0x05;
+
+/// Curve25519 key pair: clamped private scalar and Montgomery public key.
+#[derive(Clone)]
+pub struct KeyPair {
+    pub public: [u8; 32],
+    pub private: [u8; 32],
+}
+
+impl KeyPair {
+    /// Fresh random key pair.
+    pub fn generate() -> Self {
+        let mut private = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut private);
+        Self::from_private(private)
+    }
+
+    /// Key pair for an existing private key (clamped if it is not already).
+    pub fn from_private(mut private: [u8; 32]) -> Self {
+        private[0] &= 248;
+        private[31] &= 127;
+        private[31] |= 64;
+        let public = PublicKey::from(&StaticSecret::from(private)).to_bytes();
+        Self { public, private }
+    }
+
+    /// X25519 shared secret with a peer's public key.
+    pub fn agree(&self, public: &[u8; 32]) -> [u8; 32] {
+        StaticSecret::from(self.private)
+            .diffie_hellman(&PublicKey::from(*public))
+            .to_bytes()
+    }
+
+    /// XEdDSA signature of `message`, verifiable with [verify] and our public key.
+    pub fn sign(&self, message: &[u8]) -> [u8; 64] {
+        let mut random = [0u8; 64];
+        rand::thread_rng().fill_bytes(&mut random);
+        xeddsa::sign(&self.private, message, &random)
+    }
+}
+
+impl std::fmt::Debug for KeyPair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyPair")
+            .field("public", &hex::encode(self.public))
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_pairs_agree() {
+        let alice = KeyPair::generate();
+        let bob = KeyPair::generate();
+        assert_eq!(alice.agree(&bob.public), bob.agree(&alice.public));
+    }
+
+    #[test]
+    fn sign_verify_roundtrip() {
+        let pair = KeyPair::generate();
+        let signature = pair.sign(b"message");
+        assert!(verify(&pair.public, b"message", &signature));
+        assert!(!verify(&pair.public, b"massage", &signature));
+        assert!(!verify(&KeyPair::generate().public, b"message", &signature));
+    }
+
+    #[test]
+    fn from_private_clamps() {
+        let pair = KeyPair::from_private([0xff; 32]);
+        assert_eq!(pair.private[0], 0xf8);
+        assert_eq!(pair.private[31], 0x7f);
+        assert_eq!(KeyPair::from_private(pair.private).public, pair.public);
+    }
+}
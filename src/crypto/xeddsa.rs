@@ -0,0 +1,109 @@
+//! XEdDSA signatures with Curve25519 keys, in the libsignal encoding: an Ed25519 signature
+//! made with the Montgomery private scalar, with the sign bit of the Edwards public key stored
+//! in the top bit of the signature.
+
+use curve25519_dalek::edwards::EdwardsPoint;
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use curve25519_dalek::scalar::Scalar;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use sha2::{Digest, Sha512};
+
+/// Domain separation prefix for the signing nonce.
+const NONCE_PREFIX: [u8; 32] = {
+    let mut prefix = [0xff; 32];
+    prefix[0] = 0xfe;
+    prefix
+};
+
+/// Sign with a clamped Curve25519 private key and 64 bytes of randomness.
+pub(super) fn sign(private: &[u8; 32], message: &[u8], random: &[u8; 64]) -> [u8; 64] {
+    let a = Scalar::from_bytes_mod_order(*private);
+    let public = EdwardsPoint::mul_base(&a).compress().to_bytes();
+
+    let r = Scalar::from_bytes_mod_order_wide(
+        &Sha512::new()
+            .chain_update(NONCE_PREFIX)
+            .chain_update(private)
+            .chain_update(message)
+            .chain_update(random)
+            .finalize()
+            .into(),
+    );
+    let big_r = EdwardsPoint::mul_base(&r).compress().to_bytes();
+    let h = Scalar::from_bytes_mod_order_wide(
+        &Sha512::new()
+            .chain_update(big_r)
+            .chain_update(public)
+            .chain_update(message)
+            .finalize()
+            .into(),
+    );
+    let s = h * a + r;
+
+    let mut signature = [0u8; 64];
+    signature[..32].copy_from_slice(&big_r);
+    signature[32..].copy_from_slice(s.as_bytes());
+    signature[63] |= public[31] & 0x80;
+    signature
+}
+
+/// Verify an XEdDSA signature against a Curve25519 (Montgomery) public key.
+pub fn verify(public: &[u8; 32], message: &[u8], signature: &[u8; 64]) -> bool {
+    let mut montgomery = *public;
+    montgomery[31] &= 0x7f;
+    let Some(edwards) = MontgomeryPoint(montgomery).to_edwards(signature[63] >> 7) else {
+        return false;
+    };
+    let Ok(key) = VerifyingKey::from_bytes(&edwards.compress().to_bytes()) else {
+        return false;
+    };
+    let mut signature = *signature;
+    signature[63] &= 0x7f;
+    key.verify(message, &Signature::from_bytes(&signature))
+        .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(s: &str) -> Vec<u8> {
+        hex::decode(s).unwrap()
+    }
+
+    #[test]
+    fn verifies_libsignal_vector() {
+        // Curve25519Test.testSignature from libsignal-protocol-java: Alice's identity key
+        // signs her serialized (0x05-prefixed) ephemeral public key.
+        let private: [u8; 32] =
+            bytes("c097248412e58bf05df487968205132794178e367637f5818f81e0e6ce73e865")
+                .try_into()
+                .unwrap();
+        let public: [u8; 32] =
+            bytes("ab7e717d4a163b7d9a1d8071dfe9dcf8cdcd1cea3339b6356be84d887e322c64")
+                .try_into()
+                .unwrap();
+        let message = bytes("05edce9d9c415ca78cb7252e72c2c4a554d3eb29485a0e1d503118d1a82d99fb4a");
+        let signature: [u8; 64] = bytes(
+            "5de88ca9a89b4a115da79109c67c9c7464a3e4180274f1cb8c63c2984e286dfb\
+             ede82deb9dcd9fae0bfbb821569b3d9001bd8130cd11d486cef047bd60b86e88",
+        )
+        .try_into()
+        .unwrap();
+
+        assert_eq!(super::super::KeyPair::from_private(private).public, public);
+        assert!(verify(&public, &message, &signature));
+        let mut tampered = signature;
+        tampered[0] ^= 1;
+        assert!(!verify(&public, &message, &tampered));
+    }
+
+    #[test]
+    fn signing_is_deterministic_for_fixed_randomness() {
+        let pair = super::super::KeyPair::from_private([7; 32]);
+        let first = sign(&pair.private, b"message", &[1; 64]);
+        assert_eq!(first, sign(&pair.private, b"message", &[1; 64]));
+        assert_ne!(first, sign(&pair.private, b"message", &[2; 64]));
+        assert!(verify(&pair.public, b"message", &first));
+    }
+}
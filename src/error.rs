@@ -18,6 +18,9 @@ pub enum Error {
     #[error("send: {0}")]
     Send(#[from] SendError),
 
+    #[error("signal: {0}")]
+    Signal(#[from] SignalError),
+
     #[error("binary protocol: {0}")]
     Binary(String),
 
@@ -27,6 +30,9 @@ pub enum Error {
     #[error("not logged in")]
     NotLoggedIn,
 
+    #[error("no push name set")]
+    NoPushName,
+
     #[error("{0}")]
     Other(#[from] anyhow::Error),
 }
@@ -37,8 +43,8 @@ pub enum ConnectionError {
     #[error("websocket: {0}")]
     WebSocket(String),
 
-    #[error("handshake failed")]
-    HandshakeFailed,
+    #[error("handshake failed: {0}")]
+    HandshakeFailed(String),
 
     #[error("timeout")]
     Timeout,
@@ -48,31 +54,92 @@ pub enum ConnectionError {
 
     #[error("connect failure: {0}")]
     ConnectFailure(ConnectFailureReason),
+
+    #[error("already connected")]
+    AlreadyConnected,
+
+    #[error("no transport configured (enable the `full` feature or set a transport factory)")]
+    NoTransport,
+
+    #[error("invalid proxy: {0}")]
+    InvalidProxy(String),
 }
 
-/// Reason code for connection failures.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[repr(i32)]
+/// Reason code for connection failures (the `reason` attribute of a `<failure>` node).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ConnectFailureReason {
-    Generic = 400,
-    LoggedOut = 401,
-    TempBanned = 402,
-    MainDeviceGone = 403,
-    ClientOutdated = 405,
-    UnknownLogout = 406,
-    BadUserAgent = 409,
-    CATExpired = 413,
-    CATInvalid = 414,
-    NotFound = 415,
-    ClientUnknown = 418,
-    InternalServerError = 500,
-    Experimental = 501,
-    ServiceUnavailable = 503,
+    Generic,
+    LoggedOut,
+    TempBanned,
+    MainDeviceGone,
+    ClientOutdated,
+    UnknownLogout,
+    BadUserAgent,
+    CATExpired,
+    CATInvalid,
+    NotFound,
+    ClientUnknown,
+    InternalServerError,
+    Experimental,
+    ServiceUnavailable,
+    /// A code this library does not know about.
+    Unknown(i32),
 }
 
-impl std::fmt::Display for ConnectFailureReason {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let msg = match self {
+impl ConnectFailureReason {
+    /// Map a numeric failure code to a reason. Unrecognized codes become [`Self::Unknown`].
+    pub fn from_code(code: i32) -> Self {
+        match code {
+            400 => Self::Generic,
+            401 => Self::LoggedOut,
+            402 => Self::TempBanned,
+            403 => Self::MainDeviceGone,
+            405 => Self::ClientOutdated,
+            406 => Self::UnknownLogout,
+            409 => Self::BadUserAgent,
+            413 => Self::CATExpired,
+            414 => Self::CATInvalid,
+            415 => Self::NotFound,
+            418 => Self::ClientUnknown,
+            500 => Self::InternalServerError,
+            501 => Self::Experimental,
+            503 => Self::ServiceUnavailable,
+            other => Self::Unknown(other),
+        }
+    }
+
+    /// Numeric failure code as sent by the server.
+    pub fn code(&self) -> i32 {
+        match self {
+            Self::Generic => 400,
+            Self::LoggedOut => 401,
+            Self::TempBanned => 402,
+            Self::MainDeviceGone => 403,
+            Self::ClientOutdated => 405,
+            Self::UnknownLogout => 406,
+            Self::BadUserAgent => 409,
+            Self::CATExpired => 413,
+            Self::CATInvalid => 414,
+            Self::NotFound => 415,
+            Self::ClientUnknown => 418,
+            Self::InternalServerError => 500,
+            Self::Experimental => 501,
+            Self::ServiceUnavailable => 503,
+            Self::Unknown(code) => *code,
+        }
+    }
+
+    /// Whether this failure means the session is gone and the device must be paired again.
+    pub fn is_logged_out(&self) -> bool {
+        matches!(
+            self,
+            Self::LoggedOut | Self::MainDeviceGone | Self::UnknownLogout
+        )
+    }
+
+    /// Human-readable description of the failure.
+    pub fn message(&self) -> &'static str {
+        match self {
             Self::LoggedOut => "logged out from another device",
             Self::TempBanned => "account temporarily banned",
             Self::MainDeviceGone => "primary device was logged out",
@@ -81,9 +148,19 @@ impl std::fmt::Display for ConnectFailureReason {
             Self::BadUserAgent => "client user agent was rejected",
             Self::CATExpired => "messenger crypto auth token has expired",
             Self::CATInvalid => "messenger crypto auth token is invalid",
-            _ => "connection failure",
-        };
-        write!(f, "{} (code {})", msg, *self as i32)
+            Self::NotFound => "session not found",
+            Self::ClientUnknown => "client is unknown to the server",
+            Self::InternalServerError => "internal server error",
+            Self::Experimental => "experimental connect failure",
+            Self::ServiceUnavailable => "service unavailable",
+            Self::Generic | Self::Unknown(_) => "connection failure",
+        }
+    }
+}
+
+impl std::fmt::Display for ConnectFailureReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (code {})", self.message(), self.code())
     }
 }
 
@@ -102,6 +179,9 @@ pub enum PairingError {
     #[error("protocol: {0}")]
     Protocol(String),
 
+    #[error("invalid phone number: {0}")]
+    InvalidPhoneNumber(String),
+
     #[error("database: {0}")]
     Database(String),
 }
@@ -119,7 +199,7 @@ pub enum StoreError {
     IdentityNotFound,
 }
 
-/// Send message errors.
+/// Errors sending messages or requests.
 #[derive(Error, Debug)]
 pub enum SendError {
     #[error("message not found for retry")]
@@ -133,4 +213,74 @@ pub enum SendError {
 
     #[error("server error: {0}")]
     Server(String),
+
+    /// The server answered a request with `<iq type="error"><error code text/></iq>`.
+    #[error("iq error {code}: {text}")]
+    Iq { code: i32, text: String },
+}
+
+/// End-to-end encryption (Signal protocol) errors.
+#[derive(Error, Debug)]
+pub enum SignalError {
+    #[error("no session")]
+    NoSession,
+
+    #[error("invalid message: {0}")]
+    InvalidMessage(String),
+
+    #[error("invalid key: {0}")]
+    InvalidKey(String),
+
+    #[error("invalid MAC")]
+    InvalidMac,
+
+    #[error("invalid signature")]
+    InvalidSignature,
+
+    #[error("unsupported message version {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("duplicate message (counter {0})")]
+    DuplicateMessage(u32),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connect_failure_reason_code_roundtrip() {
+        for code in [
+            400, 401, 402, 403, 405, 406, 409, 413, 414, 415, 418, 500, 501, 503,
+        ] {
+            assert_eq!(ConnectFailureReason::from_code(code).code(), code);
+        }
+        assert_eq!(
+            ConnectFailureReason::from_code(499),
+            ConnectFailureReason::Unknown(499)
+        );
+        assert_eq!(ConnectFailureReason::Unknown(499).code(), 499);
+    }
+
+    #[test]
+    fn connect_failure_reason_logged_out() {
+        assert!(ConnectFailureReason::from_code(401).is_logged_out());
+        assert!(ConnectFailureReason::from_code(403).is_logged_out());
+        assert!(ConnectFailureReason::from_code(406).is_logged_out());
+        assert!(!ConnectFailureReason::from_code(503).is_logged_out());
+        assert!(!ConnectFailureReason::Unknown(401_000).is_logged_out());
+    }
+
+    #[test]
+    fn connect_failure_error_display() {
+        let err = Error::from(ConnectionError::ConnectFailure(
+            ConnectFailureReason::from_code(405),
+        ));
+        assert_eq!(
+            err.to_string(),
+            "connection: connect failure: client is out of date (code 405)"
+        );
+        let unknown = ConnectFailureReason::from_code(499);
+        assert_eq!(unknown.to_string(), "connection failure (code 499)");
+    }
 }
@@ -1,14 +1,28 @@
 //! Event types emitted by the client.
 
-use crate::types::Jid;
+mod stream;
+
+use crate::types::{
+    ChatPresence, ChatPresenceMedia, DeviceSentMeta, EditAttribute, Jid, ReceiptType,
+};
 use std::time::Duration;
 
+pub use crate::error::ConnectFailureReason;
+pub use stream::{EventStream, DEFAULT_EVENT_BUFFER};
+
 /// Events emitted by [Client](crate::Client) to registered handlers.
 #[derive(Clone, Debug)]
 pub enum Event {
-    /// QR codes for pairing. Show as QR one by one (first ~60s, others ~20s).
+    /// QR codes for pairing, emitted once when the server starts the pairing flow. Show them
+    /// as QR one by one (first 60s, others 20s); [Event::QrCode] does the rotation for you.
     Qr { codes: Vec<String> },
 
+    /// The QR code to display now and for how long, emitted for each code in turn.
+    QrCode { code: String, timeout: Duration },
+
+    /// Every QR code expired without being scanned; the client has disconnected.
+    QrTimeout,
+
     /// Pairing completed after scanning QR.
     PairSuccess {
         id: Jid,
@@ -26,12 +40,16 @@ pub enum Event {
         error: String,
     },
 
-    /// QR scanned but phone didn't have multidevice enabled.
+    /// QR scanned but phone didn't have multidevice enabled. The QR codes stay valid, so the
+    /// user can enable multidevice and scan again.
     QrScannedWithoutMultidevice,
 
     /// Client connected and authenticated.
     Connected,
 
+    /// The connection moved to a new [ConnectionState].
+    ConnectionStateChanged { state: ConnectionState },
+
     /// Keepalive pings timing out.
     KeepAliveTimeout {
         error_count: u32,
@@ -41,7 +59,9 @@ pub enum Event {
     /// Keepalive restored after timeouts.
     KeepAliveRestored,
 
-    /// Logged out from another device or connect failure.
+    /// The session was deleted: logged out from the phone (`on_connect` is true when the
+    /// server refused the login), or by [Client::logout](crate::Client::logout) /
+    /// [Client::disconnect](crate::Client::disconnect) with `reason` `None`.
     LoggedOut {
         on_connect: bool,
         reason: Option<ConnectFailureReason>,
@@ -56,99 +76,228 @@ pub enum Event {
         expire: Duration,
     },
 
-    /// Disconnected (transient).
+    /// Connection lost unexpectedly (not emitted for [Client::disconnect](crate::Client::disconnect)).
     Disconnected { reason: String },
 
     /// Incoming message (decrypted).
     Message(MessageEvent),
 
+    /// Incoming message that could not be decrypted. The client has asked the sender to
+    /// send it again, so it usually follows as an [Event::Message].
+    UndecryptableMessage(UndecryptableMessageEvent),
+
     /// Receipt (delivery/read).
     Receipt(ReceiptEvent),
 
+    /// Someone started or stopped typing or recording in a chat.
+    ChatPresence(ChatPresenceEvent),
+
+    /// A contact came online or went offline. Only sent for contacts we subscribed to with
+    /// [Client::subscribe_presence](crate::Client::subscribe_presence).
+    Presence(PresenceEvent),
+
     /// History sync notification.
     HistorySync { chunk_order: u32, progress: u32 },
 
     /// App state update.
     AppStateSync,
+
+    /// An [EventStream] consumer fell behind and `skipped` events were dropped for it.
+    /// Only yielded by streams; callback handlers never see it.
+    EventsLagged { skipped: u64 },
+}
+
+/// Lifecycle of the connection to the server; see
+/// [Client::connection_state](crate::Client::connection_state).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ConnectionState {
+    #[default]
+    Disconnected,
+    /// Opening the socket.
+    Connecting,
+    /// Running the Noise handshake.
+    Handshaking,
+    /// Handshake done, waiting for the server to accept the login (or start pairing).
+    Authenticating,
+    Connected,
 }
 
+/// A received message, decrypted and decoded.
 #[derive(Clone, Debug)]
 pub struct MessageEvent {
-    pub from: Jid,
-    pub to: Jid,
+    /// Chat the message is in: the group, or the other user of a direct chat (also for
+    /// messages we sent from another device).
+    pub chat: Jid,
+    /// Device that sent the message.
+    pub sender: Jid,
+    /// The sender's other identity, if the server included it: their phone number JID when
+    /// `sender` is a LID, or their LID when it is a phone number.
+    pub sender_alt: Option<Jid>,
     pub id: crate::types::MessageId,
     pub timestamp: std::time::SystemTime,
+    /// Display name the sender set for themselves (`notify`).
+    pub push_name: Option<String>,
     pub is_group: bool,
     pub is_from_me: bool,
-    /// Raw message payload (protobuf) - decode per message type.
+    /// `category` of the message, such as `peer` for messages between our own devices.
+    pub category: Option<String>,
+    pub edit: EditAttribute,
+    /// Set when another device of ours sent the message: `message` is then the one it sent,
+    /// unwrapped from the DeviceSentMessage.
+    pub device_sent: Option<DeviceSentMeta>,
+    pub message: Box<crate::proto::wa_e2e::Message>,
+    /// The decrypted and unpadded payload as received, for debugging.
     pub raw: Vec<u8>,
 }
 
+/// A received message that could not be decrypted; see [Event::UndecryptableMessage].
+#[derive(Clone, Debug)]
+pub struct UndecryptableMessageEvent {
+    pub chat: Jid,
+    pub sender: Jid,
+    pub sender_alt: Option<Jid>,
+    pub id: crate::types::MessageId,
+    pub timestamp: std::time::SystemTime,
+    pub push_name: Option<String>,
+    pub is_group: bool,
+    pub is_from_me: bool,
+    /// The server sent a placeholder (`<unavailable>`) instead of the content, e.g. for
+    /// view-once media on a companion device. No retry is requested for these.
+    pub is_unavailable: bool,
+    /// Why decryption failed (empty when `is_unavailable`).
+    pub error: String,
+}
+
+/// Receipt for messages we sent (or, from our other devices, for messages we received).
 #[derive(Clone, Debug)]
 pub struct ReceiptEvent {
+    /// Chat of the messages: the group, or the other user of a direct chat.
+    pub chat: Jid,
+    /// Where the receipt came from: the device, or the group for group receipts.
     pub from: Jid,
-    pub id: crate::types::MessageId,
+    /// Group member whose device sent a group receipt.
+    pub participant: Option<Jid>,
+    /// The messages it is for (receipts can cover several).
+    pub ids: Vec<crate::types::MessageId>,
     pub timestamp: std::time::SystemTime,
-    pub is_read: bool,
+    pub receipt_type: ReceiptType,
+    pub is_group: bool,
+    /// Sent by another device of ours, e.g. when we read messages on the phone.
     pub is_from_me: bool,
 }
 
-/// Connect failure reason.
-#[derive(Clone, Debug, Copy, PartialEq, Eq)]
-#[repr(i32)]
-pub enum ConnectFailureReason {
-    Generic = 400,
-    LoggedOut = 401,
-    TempBanned = 402,
-    MainDeviceGone = 403,
-    ClientOutdated = 405,
-    UnknownLogout = 406,
-    BadUserAgent = 409,
-    CATExpired = 413,
-    CATInvalid = 414,
-    NotFound = 415,
-    ClientUnknown = 418,
-    InternalServerError = 500,
-    Experimental = 501,
-    ServiceUnavailable = 503,
+/// Typing indicator from a chat; see [Event::ChatPresence].
+#[derive(Clone, Debug)]
+pub struct ChatPresenceEvent {
+    /// The group, or the other user of a direct chat.
+    pub chat: Jid,
+    /// Who is typing.
+    pub sender: Jid,
+    pub is_group: bool,
+    pub state: ChatPresence,
+    pub media: ChatPresenceMedia,
 }
 
-impl ConnectFailureReason {
-    pub fn from_code(code: i32) -> Option<Self> {
+/// Online status of a contact; see [Event::Presence].
+#[derive(Clone, Debug)]
+pub struct PresenceEvent {
+    pub from: Jid,
+    /// Offline; `false` means online.
+    pub unavailable: bool,
+    /// When they were last online, if they went offline and share it.
+    pub last_seen: Option<std::time::SystemTime>,
+}
+
+/// Temporary ban reason.
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Hash)]
+pub enum TempBanReason {
+    SentToTooManyPeople,
+    BlockedByUsers,
+    CreatedTooManyGroups,
+    SentTooManySameMessage,
+    BroadcastList,
+    /// A ban code this library does not know about.
+    Unknown(i32),
+}
+
+impl TempBanReason {
+    /// Map a numeric ban code to a reason. Unrecognized codes become [`Self::Unknown`].
+    pub fn from_code(code: i32) -> Self {
         match code {
-            400 => Some(Self::Generic),
-            401 => Some(Self::LoggedOut),
-            402 => Some(Self::TempBanned),
-            403 => Some(Self::MainDeviceGone),
-            405 => Some(Self::ClientOutdated),
-            406 => Some(Self::UnknownLogout),
-            409 => Some(Self::BadUserAgent),
-            413 => Some(Self::CATExpired),
-            414 => Some(Self::CATInvalid),
-            415 => Some(Self::NotFound),
-            418 => Some(Self::ClientUnknown),
-            500 => Some(Self::InternalServerError),
-            501 => Some(Self::Experimental),
-            503 => Some(Self::ServiceUnavailable),
-            _ => None,
+            101 => Self::SentToTooManyPeople,
+            102 => Self::BlockedByUsers,
+            103 => Self::CreatedTooManyGroups,
+            104 => Self::SentTooManySameMessage,
+            106 => Self::BroadcastList,
+            other => Self::Unknown(other),
+        }
+    }
+
+    /// Numeric ban code as sent by the server.
+    pub fn code(&self) -> i32 {
+        match self {
+            Self::SentToTooManyPeople => 101,
+            Self::BlockedByUsers => 102,
+            Self::CreatedTooManyGroups => 103,
+            Self::SentTooManySameMessage => 104,
+            Self::BroadcastList => 106,
+            Self::Unknown(code) => *code,
         }
     }
 
-    pub fn is_logged_out(&self) -> bool {
-        matches!(
-            self,
-            Self::LoggedOut | Self::MainDeviceGone | Self::UnknownLogout
-        )
+    /// Human-readable explanation of why the account was banned.
+    pub fn message(&self) -> &'static str {
+        match self {
+            Self::SentToTooManyPeople => {
+                "you sent too many messages to people who don't have you in their address books"
+            }
+            Self::BlockedByUsers => "too many people blocked you",
+            Self::CreatedTooManyGroups => {
+                "you created too many groups with people who don't have you in their address books"
+            }
+            Self::SentTooManySameMessage => "you sent the same message to too many people",
+            Self::BroadcastList => "you sent too many messages to a broadcast list",
+            Self::Unknown(_) => "you may have violated the terms of service (unknown error)",
+        }
     }
 }
 
-/// Temporary ban reason.
-#[derive(Clone, Debug, Copy, PartialEq, Eq)]
-#[repr(i32)]
-pub enum TempBanReason {
-    SentToTooManyPeople = 101,
-    BlockedByUsers = 102,
-    CreatedTooManyGroups = 103,
-    SentTooManySameMessage = 104,
-    BroadcastList = 106,
+impl std::fmt::Display for TempBanReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (code {})", self.message(), self.code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temp_ban_reason_code_roundtrip() {
+        for code in [101, 102, 103, 104, 106, 999] {
+            assert_eq!(TempBanReason::from_code(code).code(), code);
+        }
+        assert_eq!(TempBanReason::from_code(105), TempBanReason::Unknown(105));
+    }
+
+    #[test]
+    fn temp_ban_reason_display() {
+        let reason = TempBanReason::from_code(102);
+        assert_eq!(reason.to_string(), "too many people blocked you (code 102)");
+    }
+
+    #[test]
+    fn logged_out_event_uses_error_reason() {
+        let evt = Event::LoggedOut {
+            on_connect: true,
+            reason: Some(crate::error::ConnectFailureReason::from_code(401)),
+        };
+        match evt {
+            Event::LoggedOut {
+                reason: Some(reason),
+                ..
+            } => assert!(reason.is_logged_out()),
+            _ => panic!("expected LoggedOut"),
+        }
+    }
 }
@@ -0,0 +1,91 @@
+//! Async stream of client events, backed by a broadcast channel.
+
+use super::Event;
+use futures::stream::{BoxStream, Stream, StreamExt};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// Default number of events buffered per stream before a slow consumer starts lagging.
+pub const DEFAULT_EVENT_BUFFER: usize = 256;
+
+/// Stream of events returned by [Client::events](crate::Client::events).
+///
+/// Each stream has its own cursor into a bounded buffer shared by all streams. A consumer that
+/// falls more than the buffer size behind loses the oldest events; the stream then yields
+/// [Event::EventsLagged] with the number of skipped events and continues with the oldest
+/// event still buffered. The stream ends only once every clone of the client has been dropped and
+/// its background receive, keepalive and reconnect tasks have stopped; holding a clone or leaving
+/// a connection open keeps it pending.
+pub struct EventStream {
+    inner: BoxStream<'static, Event>,
+}
+
+impl EventStream {
+    pub(crate) fn new(rx: broadcast::Receiver<Event>) -> Self {
+        let inner = futures::stream::unfold(rx, |mut rx| async move {
+            match rx.recv().await {
+                Ok(evt) => Some((evt, rx)),
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!(skipped, "event stream lagged, dropped events");
+                    Some((Event::EventsLagged { skipped }, rx))
+                }
+                Err(RecvError::Closed) => None,
+            }
+        })
+        .boxed();
+        Self { inner }
+    }
+}
+
+impl Stream for EventStream {
+    type Item = Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+impl std::fmt::Debug for EventStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventStream").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn stream_yields_events_in_order() {
+        let (tx, rx) = broadcast::channel(8);
+        let mut stream = EventStream::new(rx);
+        tx.send(Event::Connected).unwrap();
+        tx.send(Event::StreamReplaced).unwrap();
+        drop(tx);
+        assert!(matches!(stream.next().await, Some(Event::Connected)));
+        assert!(matches!(stream.next().await, Some(Event::StreamReplaced)));
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn stream_reports_lag() {
+        let (tx, rx) = broadcast::channel(2);
+        let mut stream = EventStream::new(rx);
+        for i in 0..5 {
+            tx.send(Event::HistorySync {
+                chunk_order: i,
+                progress: 0,
+            })
+            .unwrap();
+        }
+        match stream.next().await {
+            Some(Event::EventsLagged { skipped }) => assert_eq!(skipped, 3),
+            other => panic!("expected EventsLagged, got {:?}", other),
+        }
+        match stream.next().await {
+            Some(Event::HistorySync { chunk_order, .. }) => assert_eq!(chunk_order, 3),
+            other => panic!("expected HistorySync, got {:?}", other),
+        }
+    }
+}
@@ -8,7 +8,7 @@
 //! - Receiving messages and events
 //! - QR code pairing (multidevice)
 //! - Group management and invite links
-//! - Typing indicators, delivery/read receipts
+//! - Typing indicators, online presence, delivery/read receipts
 //! - App state (contacts, pin/mute)
 //! - Retry receipts for decryption failures
 //!
@@ -30,21 +30,28 @@
 
 pub mod binary;
 pub mod client;
+pub mod crypto;
 pub mod error;
 pub mod events;
 pub mod pairing;
+pub mod proto;
+#[cfg(feature = "qr")]
+pub mod qr;
+pub mod signal;
 pub mod socket;
 pub mod store;
 pub mod transport;
 pub mod types;
 
-pub use client::{Client, CompletePairingParams, SendRequestExtra, SendResponse};
+pub use client::{
+    Client, CompletePairingParams, EventHandlerId, PairClientType, SendRequestExtra, SendResponse,
+};
 pub use error::{Error, Result};
-pub use events::Event;
+pub use events::{ConnectionState, Event};
 pub use pairing::{
-    generate_pairing_keys, sign_device_identity, verify_device_identity, verify_signed_identity,
-    PairingKeys, VerifiedIdentity,
+    generate_pairing_keys, sign_device_identity, verify_device_identity, PairingKeys,
+    VerifiedIdentity,
 };
 pub use store::{Device, DeviceStore, Store};
-pub use transport::Transport;
+pub use transport::{Transport, TransportFactory};
 pub use types::{Jid, MessageId};
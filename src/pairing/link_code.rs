@@ -0,0 +1,175 @@
+//! Pair-code (phone number) linking crypto: the 8-character code, the PBKDF2 key derived
+//! from it, and the wrapped keys exchanged with the primary device.
+
+use crate::error::{Error, PairingError};
+use crate::Result;
+use aes::cipher::{KeyIvInit, StreamCipher};
+use aes_gcm::aead::Aead;
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use hkdf::Hkdf;
+use rand::RngCore;
+use sha2::Sha256;
+
+type Aes256Ctr = ctr::Ctr128BE<aes::Aes256>;
+
+/// Characters used in pairing codes (base32 without 0, I, O and U).
+const LINK_CODE_ALPHABET: &[u8; 32] = b"123456789ABCDEFGHJKLMNPQRSTVWXYZ";
+
+/// PBKDF2-HMAC-SHA256 rounds for the key derived from the pairing code.
+const LINK_CODE_PBKDF2_ROUNDS: u32 = 2 << 16;
+
+/// Wrapped ephemeral key layout: salt (32) || iv (16) || AES-CTR(public key) (32).
+const WRAPPED_EPHEMERAL_LEN: usize = 32 + 16 + 32;
+
+/// Random 8-character pairing code to show to the user.
+pub fn generate_link_code() -> String {
+    let mut bytes = [0u8; 5];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    encode_link_code(bytes)
+}
+
+/// Encode 40 bits as 8 characters of [LINK_CODE_ALPHABET], most significant first.
+fn encode_link_code(bytes: [u8; 5]) -> String {
+    let value = bytes.iter().fold(0u64, |acc, b| acc << 8 | u64::from(*b));
+    (0..8)
+        .rev()
+        .map(|i| LINK_CODE_ALPHABET[(value >> (i * 5)) as usize & 31] as char)
+        .collect()
+}
+
+/// AES key derived from the pairing code and a salt.
+fn link_code_key(code: &str, salt: &[u8]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(code.as_bytes(), salt, LINK_CODE_PBKDF2_ROUNDS, &mut key);
+    key
+}
+
+/// Encrypt our ephemeral public key with the pairing code, for `companion_hello`.
+pub fn wrap_ephemeral_key(code: &str, ephemeral_public: &[u8; 32]) -> Vec<u8> {
+    let mut salt = [0u8; 32];
+    let mut iv = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut salt);
+    rand::thread_rng().fill_bytes(&mut iv);
+    let mut encrypted = *ephemeral_public;
+    Aes256Ctr::new(&link_code_key(code, &salt).into(), &iv.into()).apply_keystream(&mut encrypted);
+    [&salt[..], &iv[..], &encrypted[..]].concat()
+}
+
+/// Decrypt the primary device's ephemeral public key from `primary_hello`.
+pub fn unwrap_ephemeral_key(code: &str, wrapped: &[u8]) -> Result<[u8; 32]> {
+    if wrapped.len() != WRAPPED_EPHEMERAL_LEN {
+        return Err(Error::Pairing(PairingError::Protocol(format!(
+            "wrapped ephemeral key is {} bytes, expected {}",
+            wrapped.len(),
+            WRAPPED_EPHEMERAL_LEN
+        ))));
+    }
+    let (salt, rest) = wrapped.split_at(32);
+    let (iv, encrypted) = rest.split_at(16);
+    let mut public = [0u8; 32];
+    public.copy_from_slice(encrypted);
+    let iv: [u8; 16] = iv.try_into().expect("16-byte iv");
+    Aes256Ctr::new(&link_code_key(code, salt).into(), &iv.into()).apply_keystream(&mut public);
+    Ok(public)
+}
+
+/// Key bundle for `companion_finish`: salt (32) || nonce (12) || AES-GCM(identity ||
+/// primary identity || adv randomness), keyed from the ephemeral shared secret.
+pub fn wrap_key_bundle(
+    ephemeral_shared: &[u8; 32],
+    identity_public: &[u8; 32],
+    primary_identity_public: &[u8; 32],
+    adv_random: &[u8; 32],
+) -> Result<Vec<u8>> {
+    let mut salt = [0u8; 32];
+    let mut nonce = [0u8; 12];
+    rand::thread_rng().fill_bytes(&mut salt);
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(&salt), ephemeral_shared)
+        .expand(b"link_code_pairing_key_bundle_encryption_key", &mut key)
+        .expect("32 bytes is a valid HKDF length");
+    let plaintext = [
+        &identity_public[..],
+        &primary_identity_public[..],
+        &adv_random[..],
+    ]
+    .concat();
+    let encrypted = Aes256Gcm::new(&key.into())
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+        .map_err(|_| Error::Pairing(PairingError::Protocol("key bundle encryption".into())))?;
+    Ok([&salt[..], &nonce[..], &encrypted[..]].concat())
+}
+
+/// Adv secret for a pair-code link (replaces the random one advertised with QR codes).
+pub fn link_code_adv_secret(
+    ephemeral_shared: &[u8; 32],
+    identity_shared: &[u8; 32],
+    adv_random: &[u8; 32],
+) -> [u8; 32] {
+    let input = [&ephemeral_shared[..], &identity_shared[..], &adv_random[..]].concat();
+    let mut secret = [0u8; 32];
+    Hkdf::<Sha256>::new(None, &input)
+        .expand(b"adv_secret", &mut secret)
+        .expect("32 bytes is a valid HKDF length");
+    secret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn link_code_encoding() {
+        assert_eq!(encode_link_code([0; 5]), "11111111");
+        assert_eq!(encode_link_code([0xff; 5]), "ZZZZZZZZ");
+        assert_eq!(encode_link_code([0x01, 0x23, 0x45, 0x67, 0x89]), "15JMBSWA");
+        let code = generate_link_code();
+        assert_eq!(code.len(), 8);
+        assert!(code.bytes().all(|c| LINK_CODE_ALPHABET.contains(&c)));
+    }
+
+    #[test]
+    fn link_code_key_matches_pbkdf2_vector() {
+        // hashlib.pbkdf2_hmac("sha256", b"ABCDEFGH", bytes(range(32)), 131072)
+        assert_eq!(
+            hex::encode(link_code_key("ABCDEFGH", &(0..32).collect::<Vec<u8>>())),
+            "920ceedbc74a9319a309daf1493c79d001e31d9a6b3ed51cf7374849878d84e4"
+        );
+    }
+
+    #[test]
+    fn ephemeral_key_roundtrip() {
+        let public = [7u8; 32];
+        let wrapped = wrap_ephemeral_key("ABCDEFGH", &public);
+        assert_eq!(wrapped.len(), WRAPPED_EPHEMERAL_LEN);
+        assert_ne!(wrapped[48..], public);
+        assert_eq!(unwrap_ephemeral_key("ABCDEFGH", &wrapped).unwrap(), public);
+        assert!(unwrap_ephemeral_key("ABCDEFGH", &wrapped[1..]).is_err());
+    }
+
+    #[test]
+    fn key_bundle_decrypts_with_derived_key() {
+        let shared = [1u8; 32];
+        let bundle = wrap_key_bundle(&shared, &[2; 32], &[3; 32], &[4; 32]).unwrap();
+        let (salt, rest) = bundle.split_at(32);
+        let (nonce, encrypted) = rest.split_at(12);
+        let mut key = [0u8; 32];
+        Hkdf::<Sha256>::new(Some(salt), &shared)
+            .expand(b"link_code_pairing_key_bundle_encryption_key", &mut key)
+            .unwrap();
+        let plaintext = Aes256Gcm::new(&key.into())
+            .decrypt(Nonce::from_slice(nonce), encrypted)
+            .unwrap();
+        assert_eq!(plaintext, [[2u8; 32], [3; 32], [4; 32]].concat());
+    }
+
+    #[test]
+    fn adv_secret_matches_hkdf_vector() {
+        // HKDF-SHA256(ikm = [1]*32 + [2]*32 + [3]*32, salt = none, info = "adv_secret")
+        assert_eq!(
+            hex::encode(link_code_adv_secret(&[1; 32], &[2; 32], &[3; 32])),
+            "8a82f12de8f65571e0a25b79095ae8a5dc09a1bd72e0a886e25d5c9818087c4d"
+        );
+    }
+}
@@ -1,22 +1,44 @@
-//! Pairing crypto: device identity verification (HMAC), key generation (X25519, Ed25519),
-//! and signed device identity for persistence.
+//! Pairing crypto: key generation (Curve25519), ADV signed device identity verification and
+//! signing for pair-success, and pair-code (phone number) link keys.
 
+pub use crate::crypto::DJB_KEY_TYPE;
+use crate::crypto::{self, KeyPair};
 use crate::error::{Error, PairingError};
+use crate::proto::wa_adv::{
+    AdvDeviceIdentity, AdvEncryptionType, AdvSignedDeviceIdentity, AdvSignedDeviceIdentityHmac,
+};
+use crate::signal::{generate_registration_id, SignedPreKey};
 use crate::Result;
-use ed25519_dalek::{Signer, SigningKey, VerifyingKey};
 use hmac::{Hmac, Mac};
+use prost::Message;
 use rand::RngCore;
 use sha2::Sha256;
-use x25519_dalek::{PublicKey, StaticSecret};
 
-/// HMAC-SHA256 tag length in bytes.
-const HMAC_LEN: usize = 32;
+mod link_code;
 
-/// Verified device identity from the server payload (after HMAC check).
+pub use link_code::{
+    generate_link_code, link_code_adv_secret, unwrap_ephemeral_key, wrap_ephemeral_key,
+    wrap_key_bundle,
+};
+
+/// Message prefix of the primary device's account signature.
+pub const ADV_ACCOUNT_SIGNATURE_PREFIX: [u8; 2] = [6, 0];
+/// Message prefix of our device signature.
+pub const ADV_DEVICE_SIGNATURE_PREFIX: [u8; 2] = [6, 1];
+/// Account signature prefix (and HMAC prefix) for hosted accounts.
+pub const ADV_HOSTED_ACCOUNT_SIGNATURE_PREFIX: [u8; 2] = [6, 5];
+/// Device signature prefix for hosted accounts.
+pub const ADV_HOSTED_DEVICE_SIGNATURE_PREFIX: [u8; 2] = [6, 6];
+
+/// Device identity from a pair-success whose HMAC and account signature checked out.
 #[derive(Clone, Debug)]
 pub struct VerifiedIdentity {
-    /// Raw payload that was verified (excluding the HMAC tag).
-    pub payload: Vec<u8>,
+    /// Identity signed by the primary device (no device signature yet).
+    pub identity: AdvSignedDeviceIdentity,
+    /// Decoded `identity.details`.
+    pub details: AdvDeviceIdentity,
+    /// Whether the account is hosted (uses the hosted signature prefixes).
+    pub hosted: bool,
 }
 
 /// Keys generated for pairing: Noise key, identity key, and adv secret.
@@ -26,95 +48,185 @@ pub struct PairingKeys {
     pub noise_public: [u8; 32],
     /// X25519 Noise private key (32 bytes); store securely, not exposed in Device.
     pub noise_private: [u8; 32],
-    /// Ed25519 identity public key (32 bytes).
+    /// Curve25519 identity public key (32 bytes).
     pub identity_public: [u8; 32],
-    /// Ed25519 identity private key (32 bytes); store in Device.identity_key_priv.
+    /// Curve25519 identity private key (32 bytes); store in Device.identity_key_priv.
     pub identity_private: [u8; 32],
     /// Adv secret for pairing (32 bytes).
     pub adv_secret: [u8; 32],
+    /// Signal registration ID (14 bits, never 0).
+    pub registration_id: u32,
+    /// X25519 signed prekey pair advertised in the registration payload.
+    pub signed_prekey_public: [u8; 32],
+    pub signed_prekey_private: [u8; 32],
+    /// XEdDSA identity signature over `0x05 || signed_prekey_public`.
+    pub signed_prekey_signature: [u8; 64],
 }
 
-/// Verify device identity payload: last HMAC_LEN bytes are HMAC-SHA256 of the rest with the given key.
-/// Returns the payload without the tag, or error if verification fails.
+/// ID of the signed prekey generated with the pairing keys.
+pub const SIGNED_PREKEY_ID: u32 = 1;
+
+/// Check the `device-identity` of a pair-success: `container` is an encoded
+/// ADVSignedDeviceIdentityHMAC whose HMAC-SHA256 (keyed with our adv secret) must match, and
+/// whose inner identity must carry a valid account signature over our identity key.
 pub fn verify_device_identity(
-    payload_with_tag: &[u8],
-    hmac_key: &[u8],
+    container: &[u8],
+    adv_secret: &[u8; 32],
+    identity_public: &[u8; 32],
 ) -> Result<VerifiedIdentity> {
-    if payload_with_tag.len() < HMAC_LEN {
-        return Err(Error::Pairing(PairingError::InvalidDeviceIdentityHmac));
+    let container = AdvSignedDeviceIdentityHmac::decode(container)
+        .map_err(|e| protocol_error("device identity container", e))?;
+    let hosted = container.account_type == Some(AdvEncryptionType::Hosted as i32);
+    let details = container.details.unwrap_or_default();
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(adv_secret).expect("HMAC takes any key length");
+    if hosted {
+        mac.update(&ADV_HOSTED_ACCOUNT_SIGNATURE_PREFIX);
     }
-    let split = payload_with_tag.len() - HMAC_LEN;
-    let payload = &payload_with_tag[..split];
-    let tag = &payload_with_tag[split..];
-
-    let mut mac = Hmac::<Sha256>::new_from_slice(hmac_key)
-        .map_err(|_| Error::Pairing(PairingError::Protocol("invalid HMAC key length".into())))?;
-    mac.update(payload);
-    mac.verify_slice(tag)
+    mac.update(&details);
+    mac.verify_slice(&container.hmac.unwrap_or_default())
         .map_err(|_| Error::Pairing(PairingError::InvalidDeviceIdentityHmac))?;
 
+    let identity = AdvSignedDeviceIdentity::decode(details.as_slice())
+        .map_err(|e| protocol_error("signed device identity", e))?;
+    let details = AdvDeviceIdentity::decode(identity.details())
+        .map_err(|e| protocol_error("device identity details", e))?;
+
+    let prefix = if hosted {
+        ADV_HOSTED_ACCOUNT_SIGNATURE_PREFIX
+    } else {
+        ADV_ACCOUNT_SIGNATURE_PREFIX
+    };
+    let (Ok(account_key), Ok(signature)) = (
+        <[u8; 32]>::try_from(identity.account_signature_key()),
+        <[u8; 64]>::try_from(identity.account_signature()),
+    ) else {
+        return Err(Error::Pairing(PairingError::InvalidDeviceSignature));
+    };
+    let message = [&prefix[..], identity.details(), &identity_public[..]].concat();
+    if !crypto::verify(&account_key, &message, &signature) {
+        return Err(Error::Pairing(PairingError::InvalidDeviceSignature));
+    }
     Ok(VerifiedIdentity {
-        payload: payload.to_vec(),
+        identity,
+        details,
+        hosted,
     })
 }
 
-/// Generate fresh pairing keys: Noise (X25519), identity (Ed25519), and adv secret.
-pub fn generate_pairing_keys() -> PairingKeys {
-    let mut noise_private = [0u8; 32];
-    rand::thread_rng().fill_bytes(&mut noise_private);
-    let secret = StaticSecret::from(noise_private);
-    let noise_public = PublicKey::from(&secret).to_bytes();
+/// Add our device signature to a verified identity. The result is the account to store;
+/// it is also sent back (without the account signature key) in `pair-device-sign`.
+pub fn sign_device_identity(
+    verified: &VerifiedIdentity,
+    identity: &KeyPair,
+) -> AdvSignedDeviceIdentity {
+    let prefix = if verified.hosted {
+        ADV_HOSTED_DEVICE_SIGNATURE_PREFIX
+    } else {
+        ADV_DEVICE_SIGNATURE_PREFIX
+    };
+    let signed = &verified.identity;
+    let message = [
+        &prefix[..],
+        signed.details(),
+        &identity.public[..],
+        signed.account_signature_key(),
+    ]
+    .concat();
+    AdvSignedDeviceIdentity {
+        device_signature: Some(identity.sign(&message).to_vec()),
+        ..signed.clone()
+    }
+}
 
-    let identity_signing = SigningKey::generate(&mut rand::thread_rng());
-    let identity_public = identity_signing.verifying_key().to_bytes();
-    let identity_private = identity_signing.to_bytes();
+fn protocol_error(what: &str, e: prost::DecodeError) -> Error {
+    Error::Pairing(PairingError::Protocol(format!("invalid {}: {}", what, e)))
+}
+
+/// Generate fresh pairing keys: Noise and identity key pairs, signed prekey and adv secret.
+pub fn generate_pairing_keys() -> PairingKeys {
+    let noise = KeyPair::generate();
+    let identity = KeyPair::generate();
+    let signed_prekey = SignedPreKey::generate(SIGNED_PREKEY_ID, &identity);
 
     let mut adv_secret = [0u8; 32];
     rand::thread_rng().fill_bytes(&mut adv_secret);
 
     PairingKeys {
-        noise_public,
-        noise_private,
-        identity_public,
-        identity_private,
+        noise_public: noise.public,
+        noise_private: noise.private,
+        identity_public: identity.public,
+        identity_private: identity.private,
         adv_secret,
+        registration_id: generate_registration_id(),
+        signed_prekey_public: signed_prekey.key_pair.public,
+        signed_prekey_private: signed_prekey.key_pair.private,
+        signed_prekey_signature: signed_prekey.signature,
     }
 }
 
-/// Build a signed device identity blob for storage: verifying_key (32) || signature (64) || payload.
-/// The payload is signed with the identity private key so the server can verify later.
-pub fn sign_device_identity(payload: &[u8], identity_private: &[u8; 32]) -> Result<Vec<u8>> {
-    let signing_key = SigningKey::from_bytes(identity_private);
-    let verifying_key = signing_key.verifying_key();
-    let signature = signing_key.sign(payload);
-
-    let mut out = Vec::with_capacity(32 + 64 + payload.len());
-    out.extend_from_slice(verifying_key.as_bytes());
-    out.extend_from_slice(&signature.to_bytes());
-    out.extend_from_slice(payload);
-    Ok(out)
+/// QR code contents for one pairing ref: `ref,noise_pub,identity_pub,adv_secret`
+/// with the keys in standard base64.
+pub fn make_qr_code(
+    reference: &str,
+    noise_public: &[u8; 32],
+    identity_public: &[u8; 32],
+    adv_secret: &[u8; 32],
+) -> String {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    [
+        reference.to_string(),
+        STANDARD.encode(noise_public),
+        STANDARD.encode(identity_public),
+        STANDARD.encode(adv_secret),
+    ]
+    .join(",")
 }
 
-/// Verify a stored signed device identity blob (verifying_key || signature || payload).
-/// Returns the inner payload on success.
-pub fn verify_signed_identity(signed_blob: &[u8]) -> Result<Vec<u8>> {
-    if signed_blob.len() < 32 + 64 {
-        return Err(Error::Pairing(PairingError::InvalidDeviceSignature));
+/// Encoded pair-success `device-identity` as a primary device would send it: account
+/// signature by `primary` over our identity key, wrapped with the adv secret's HMAC.
+#[cfg(test)]
+pub(crate) fn primary_device_identity(
+    primary: &KeyPair,
+    identity_public: &[u8; 32],
+    adv_secret: &[u8; 32],
+    key_index: u32,
+    hosted: bool,
+) -> Vec<u8> {
+    let details = AdvDeviceIdentity {
+        raw_id: Some(12345),
+        timestamp: Some(1_700_000_000),
+        key_index: Some(key_index),
+        ..Default::default()
+    }
+    .encode_to_vec();
+    let (account_prefix, hmac_prefix): ([u8; 2], &[u8]) = if hosted {
+        (
+            ADV_HOSTED_ACCOUNT_SIGNATURE_PREFIX,
+            &ADV_HOSTED_ACCOUNT_SIGNATURE_PREFIX,
+        )
+    } else {
+        (ADV_ACCOUNT_SIGNATURE_PREFIX, &[])
+    };
+    let account_signature =
+        primary.sign(&[&account_prefix[..], &details, &identity_public[..]].concat());
+    let signed = AdvSignedDeviceIdentity {
+        details: Some(details),
+        account_signature_key: Some(primary.public.to_vec()),
+        account_signature: Some(account_signature.to_vec()),
+        device_signature: None,
     }
-    let key_bytes: [u8; 32] = signed_blob[..32]
-        .try_into()
-        .map_err(|_| Error::Pairing(PairingError::InvalidDeviceSignature))?;
-    let verifying_key = VerifyingKey::from_bytes(&key_bytes)
-        .map_err(|_| Error::Pairing(PairingError::InvalidDeviceSignature))?;
-    let sig_bytes: [u8; 64] = signed_blob[32..96]
-        .try_into()
-        .map_err(|_| Error::Pairing(PairingError::InvalidDeviceSignature))?;
-    let signature = ed25519_dalek::Signature::from_bytes(&sig_bytes);
-    let payload = &signed_blob[96..];
-    verifying_key
-        .verify_strict(payload, &signature)
-        .map_err(|_| Error::Pairing(PairingError::InvalidDeviceSignature))?;
-    Ok(payload.to_vec())
+    .encode_to_vec();
+    let mut mac = Hmac::<Sha256>::new_from_slice(adv_secret).unwrap();
+    mac.update(hmac_prefix);
+    mac.update(&signed);
+    AdvSignedDeviceIdentityHmac {
+        details: Some(signed),
+        hmac: Some(mac.finalize().into_bytes().to_vec()),
+        account_type: hosted.then_some(AdvEncryptionType::Hosted as i32),
+    }
+    .encode_to_vec()
 }
 
 #[cfg(test)]
@@ -122,44 +234,113 @@ mod tests {
     use super::*;
 
     #[test]
-    fn hmac_verify_roundtrip() {
-        let key = b"test-hmac-key-32-bytes-long!!!!!!";
-        let payload = b"device-identity-payload";
-        let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
-        mac.update(payload);
-        let tag = mac.finalize().into_bytes();
-        let mut with_tag = payload.to_vec();
-        with_tag.extend_from_slice(&tag);
-        let verified = verify_device_identity(&with_tag, key).unwrap();
-        assert_eq!(verified.payload, payload);
+    fn device_identity_verify_and_sign() {
+        let primary = KeyPair::generate();
+        let ours = KeyPair::generate();
+        let adv = [9u8; 32];
+        let container = primary_device_identity(&primary, &ours.public, &adv, 3, false);
+
+        let verified = verify_device_identity(&container, &adv, &ours.public).unwrap();
+        assert!(!verified.hosted);
+        assert_eq!(verified.details.key_index, Some(3));
+        assert_eq!(verified.identity.account_signature_key(), primary.public);
+
+        let account = sign_device_identity(&verified, &ours);
+        let signature: [u8; 64] = account.device_signature().try_into().unwrap();
+        let message = [
+            &ADV_DEVICE_SIGNATURE_PREFIX[..],
+            account.details(),
+            &ours.public[..],
+            &primary.public[..],
+        ]
+        .concat();
+        assert!(crypto::verify(&ours.public, &message, &signature));
+    }
+
+    #[test]
+    fn device_identity_hosted_prefixes() {
+        let primary = KeyPair::generate();
+        let ours = KeyPair::generate();
+        let container = primary_device_identity(&primary, &ours.public, &[1; 32], 1, true);
+        let verified = verify_device_identity(&container, &[1; 32], &ours.public).unwrap();
+        assert!(verified.hosted);
+
+        let account = sign_device_identity(&verified, &ours);
+        let signature: [u8; 64] = account.device_signature().try_into().unwrap();
+        let message = [
+            &ADV_HOSTED_DEVICE_SIGNATURE_PREFIX[..],
+            account.details(),
+            &ours.public[..],
+            &primary.public[..],
+        ]
+        .concat();
+        assert!(crypto::verify(&ours.public, &message, &signature));
+    }
+
+    #[test]
+    fn device_identity_rejects_wrong_hmac_key() {
+        let ours = KeyPair::generate();
+        let container =
+            primary_device_identity(&KeyPair::generate(), &ours.public, &[1; 32], 1, false);
+        assert!(matches!(
+            verify_device_identity(&container, &[2; 32], &ours.public),
+            Err(Error::Pairing(PairingError::InvalidDeviceIdentityHmac))
+        ));
+    }
+
+    #[test]
+    fn device_identity_rejects_signature_over_other_key() {
+        let other = KeyPair::generate();
+        let container =
+            primary_device_identity(&KeyPair::generate(), &other.public, &[1; 32], 1, false);
+        let ours = KeyPair::generate();
+        assert!(matches!(
+            verify_device_identity(&container, &[1; 32], &ours.public),
+            Err(Error::Pairing(PairingError::InvalidDeviceSignature))
+        ));
     }
 
     #[test]
-    fn hmac_reject_tampered() {
-        let key = b"test-hmac-key-32-bytes-long!!!!!!";
-        let payload = b"device-identity-payload";
-        let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
-        mac.update(payload);
-        let tag = mac.finalize().into_bytes();
-        let mut with_tag = payload.to_vec();
-        with_tag.extend_from_slice(&tag);
-        with_tag[0] ^= 1;
-        assert!(verify_device_identity(&with_tag, key).is_err());
+    fn device_identity_rejects_garbage() {
+        assert!(matches!(
+            verify_device_identity(&[0xff, 0xff], &[1; 32], &[0; 32]),
+            Err(Error::Pairing(PairingError::Protocol(_)))
+        ));
     }
 
     #[test]
     fn pairing_keys_generated() {
         let keys = generate_pairing_keys();
-        assert_eq!(keys.noise_public.len(), 32);
-        assert_eq!(keys.identity_public.len(), 32);
+        assert_eq!(
+            KeyPair::from_private(keys.identity_private).public,
+            keys.identity_public
+        );
+        let mut signed = vec![DJB_KEY_TYPE];
+        signed.extend_from_slice(&keys.signed_prekey_public);
+        assert!(crypto::verify(
+            &keys.identity_public,
+            &signed,
+            &keys.signed_prekey_signature
+        ));
     }
 
     #[test]
-    fn sign_verify_identity_roundtrip() {
+    fn qr_code_layout() {
         let keys = generate_pairing_keys();
-        let payload = b"account-payload-to-store";
-        let signed = sign_device_identity(payload, &keys.identity_private).unwrap();
-        let verified = verify_signed_identity(&signed).unwrap();
-        assert_eq!(verified, payload);
+        let code = make_qr_code(
+            "2@abc==",
+            &keys.noise_public,
+            &keys.identity_public,
+            &keys.adv_secret,
+        );
+        let parts: Vec<&str> = code.split(',').collect();
+        assert_eq!(parts.len(), 4);
+        assert_eq!(parts[0], "2@abc==");
+        use base64::Engine;
+        let decode = |s| base64::engine::general_purpose::STANDARD.decode(s).unwrap();
+        assert_eq!(decode(parts[1]), keys.noise_public);
+        assert_eq!(decode(parts[2]), keys.identity_public);
+        assert_eq!(decode(parts[3]), keys.adv_secret);
+        assert!((1..=0x3fff).contains(&keys.registration_id));
     }
 }
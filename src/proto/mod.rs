@@ -0,0 +1,15 @@
+//! WhatsApp protobuf definitions.
+//!
+//! Each submodule corresponds to one vendored `.proto` file in this directory. The Rust types
+//! are generated by `prost-build` and checked in, so building the crate does not need `protoc`;
+//! regenerate them whenever a `.proto` file changes.
+
+#![allow(clippy::all)]
+
+pub mod signal_storage;
+pub mod signal_wire;
+pub mod wa_adv;
+pub mod wa_common;
+pub mod wa_companion_reg;
+pub mod wa_e2e;
+pub mod wa_wa6;
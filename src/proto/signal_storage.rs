@@ -0,0 +1,117 @@
+// This file is @generated by prost-build.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SessionStructure {
+    #[prost(uint32, optional, tag = "1")]
+    pub session_version: ::core::option::Option<u32>,
+    #[prost(bytes = "vec", optional, tag = "2")]
+    pub local_identity_public: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "3")]
+    pub remote_identity_public: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "4")]
+    pub root_key: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(uint32, optional, tag = "5")]
+    pub previous_counter: ::core::option::Option<u32>,
+    #[prost(message, optional, tag = "6")]
+    pub sender_chain: ::core::option::Option<session_structure::Chain>,
+    #[prost(message, repeated, tag = "7")]
+    pub receiver_chains: ::prost::alloc::vec::Vec<session_structure::Chain>,
+    #[prost(message, optional, tag = "9")]
+    pub pending_pre_key: ::core::option::Option<session_structure::PendingPreKey>,
+    #[prost(uint32, optional, tag = "10")]
+    pub remote_registration_id: ::core::option::Option<u32>,
+    #[prost(uint32, optional, tag = "11")]
+    pub local_registration_id: ::core::option::Option<u32>,
+    #[prost(bytes = "vec", optional, tag = "13")]
+    pub alice_base_key: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+}
+/// Nested message and enum types in `SessionStructure`.
+pub mod session_structure {
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Chain {
+        #[prost(bytes = "vec", optional, tag = "1")]
+        pub sender_ratchet_key: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+        #[prost(bytes = "vec", optional, tag = "2")]
+        pub sender_ratchet_key_private: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+        #[prost(message, optional, tag = "3")]
+        pub chain_key: ::core::option::Option<chain::ChainKey>,
+        #[prost(message, repeated, tag = "4")]
+        pub message_keys: ::prost::alloc::vec::Vec<chain::MessageKey>,
+    }
+    /// Nested message and enum types in `Chain`.
+    pub mod chain {
+        #[derive(Clone, PartialEq, ::prost::Message)]
+        pub struct ChainKey {
+            #[prost(uint32, optional, tag = "1")]
+            pub index: ::core::option::Option<u32>,
+            #[prost(bytes = "vec", optional, tag = "2")]
+            pub key: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+        }
+        #[derive(Clone, PartialEq, ::prost::Message)]
+        pub struct MessageKey {
+            #[prost(uint32, optional, tag = "1")]
+            pub index: ::core::option::Option<u32>,
+            #[prost(bytes = "vec", optional, tag = "2")]
+            pub cipher_key: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+            #[prost(bytes = "vec", optional, tag = "3")]
+            pub mac_key: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+            #[prost(bytes = "vec", optional, tag = "4")]
+            pub iv: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+        }
+    }
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct PendingPreKey {
+        #[prost(uint32, optional, tag = "1")]
+        pub pre_key_id: ::core::option::Option<u32>,
+        #[prost(int32, optional, tag = "3")]
+        pub signed_pre_key_id: ::core::option::Option<i32>,
+        #[prost(bytes = "vec", optional, tag = "2")]
+        pub base_key: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    }
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecordStructure {
+    #[prost(message, optional, tag = "1")]
+    pub current_session: ::core::option::Option<SessionStructure>,
+    #[prost(message, repeated, tag = "2")]
+    pub previous_sessions: ::prost::alloc::vec::Vec<SessionStructure>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SenderKeyStateStructure {
+    #[prost(uint32, optional, tag = "1")]
+    pub sender_key_id: ::core::option::Option<u32>,
+    #[prost(message, optional, tag = "2")]
+    pub sender_chain_key: ::core::option::Option<sender_key_state_structure::SenderChainKey>,
+    #[prost(message, optional, tag = "3")]
+    pub sender_signing_key: ::core::option::Option<sender_key_state_structure::SenderSigningKey>,
+    #[prost(message, repeated, tag = "4")]
+    pub sender_message_keys: ::prost::alloc::vec::Vec<sender_key_state_structure::SenderMessageKey>,
+}
+/// Nested message and enum types in `SenderKeyStateStructure`.
+pub mod sender_key_state_structure {
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct SenderChainKey {
+        #[prost(uint32, optional, tag = "1")]
+        pub iteration: ::core::option::Option<u32>,
+        #[prost(bytes = "vec", optional, tag = "2")]
+        pub seed: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    }
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct SenderMessageKey {
+        #[prost(uint32, optional, tag = "1")]
+        pub iteration: ::core::option::Option<u32>,
+        #[prost(bytes = "vec", optional, tag = "2")]
+        pub seed: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    }
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct SenderSigningKey {
+        #[prost(bytes = "vec", optional, tag = "1")]
+        pub public: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+        #[prost(bytes = "vec", optional, tag = "2")]
+        pub private: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    }
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SenderKeyRecordStructure {
+    #[prost(message, repeated, tag = "1")]
+    pub sender_key_states: ::prost::alloc::vec::Vec<SenderKeyStateStructure>,
+}
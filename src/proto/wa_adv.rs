@@ -0,0 +1,60 @@
+// This file is @generated by prost-build.
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct AdvDeviceIdentity {
+    #[prost(uint32, optional, tag = "1")]
+    pub raw_id: ::core::option::Option<u32>,
+    #[prost(uint64, optional, tag = "2")]
+    pub timestamp: ::core::option::Option<u64>,
+    #[prost(uint32, optional, tag = "3")]
+    pub key_index: ::core::option::Option<u32>,
+    #[prost(enumeration = "AdvEncryptionType", optional, tag = "4")]
+    pub account_type: ::core::option::Option<i32>,
+    #[prost(enumeration = "AdvEncryptionType", optional, tag = "5")]
+    pub device_type: ::core::option::Option<i32>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AdvSignedDeviceIdentity {
+    #[prost(bytes = "vec", optional, tag = "1")]
+    pub details: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "2")]
+    pub account_signature_key: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "3")]
+    pub account_signature: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "4")]
+    pub device_signature: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AdvSignedDeviceIdentityHmac {
+    #[prost(bytes = "vec", optional, tag = "1")]
+    pub details: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "2")]
+    pub hmac: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(enumeration = "AdvEncryptionType", optional, tag = "3")]
+    pub account_type: ::core::option::Option<i32>,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum AdvEncryptionType {
+    E2Ee = 0,
+    Hosted = 1,
+}
+impl AdvEncryptionType {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::E2Ee => "E2EE",
+            Self::Hosted => "HOSTED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "E2EE" => Some(Self::E2Ee),
+            "HOSTED" => Some(Self::Hosted),
+            _ => None,
+        }
+    }
+}
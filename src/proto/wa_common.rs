@@ -0,0 +1,12 @@
+// This file is @generated by prost-build.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MessageKey {
+    #[prost(string, optional, tag = "1")]
+    pub remote_jid: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(bool, optional, tag = "2")]
+    pub from_me: ::core::option::Option<bool>,
+    #[prost(string, optional, tag = "3")]
+    pub id: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "4")]
+    pub participant: ::core::option::Option<::prost::alloc::string::String>,
+}
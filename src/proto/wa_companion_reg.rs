@@ -0,0 +1,142 @@
+// This file is @generated by prost-build.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DeviceProps {
+    #[prost(string, optional, tag = "1")]
+    pub os: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(message, optional, tag = "2")]
+    pub version: ::core::option::Option<device_props::AppVersion>,
+    #[prost(enumeration = "device_props::PlatformType", optional, tag = "3")]
+    pub platform_type: ::core::option::Option<i32>,
+    #[prost(bool, optional, tag = "4")]
+    pub require_full_sync: ::core::option::Option<bool>,
+    #[prost(message, optional, tag = "5")]
+    pub history_sync_config: ::core::option::Option<device_props::HistorySyncConfig>,
+}
+/// Nested message and enum types in `DeviceProps`.
+pub mod device_props {
+    #[derive(Clone, Copy, PartialEq, ::prost::Message)]
+    pub struct HistorySyncConfig {
+        #[prost(uint32, optional, tag = "1")]
+        pub full_sync_days_limit: ::core::option::Option<u32>,
+        #[prost(uint32, optional, tag = "2")]
+        pub full_sync_size_mb_limit: ::core::option::Option<u32>,
+        #[prost(uint32, optional, tag = "3")]
+        pub storage_quota_mb: ::core::option::Option<u32>,
+        #[prost(bool, optional, tag = "4")]
+        pub inline_initial_payload_in_e2_ee_msg: ::core::option::Option<bool>,
+        #[prost(uint32, optional, tag = "5")]
+        pub recent_sync_days_limit: ::core::option::Option<u32>,
+        #[prost(bool, optional, tag = "6")]
+        pub support_call_log_history: ::core::option::Option<bool>,
+        #[prost(bool, optional, tag = "7")]
+        pub support_bot_user_agent_chat_history: ::core::option::Option<bool>,
+        #[prost(bool, optional, tag = "8")]
+        pub support_cag_reactions_and_polls: ::core::option::Option<bool>,
+        #[prost(bool, optional, tag = "9")]
+        pub support_biz_hosted_msg: ::core::option::Option<bool>,
+        #[prost(bool, optional, tag = "10")]
+        pub support_recent_sync_chunk_message_count_tuning: ::core::option::Option<bool>,
+    }
+    #[derive(Clone, Copy, PartialEq, ::prost::Message)]
+    pub struct AppVersion {
+        #[prost(uint32, optional, tag = "1")]
+        pub primary: ::core::option::Option<u32>,
+        #[prost(uint32, optional, tag = "2")]
+        pub secondary: ::core::option::Option<u32>,
+        #[prost(uint32, optional, tag = "3")]
+        pub tertiary: ::core::option::Option<u32>,
+        #[prost(uint32, optional, tag = "4")]
+        pub quaternary: ::core::option::Option<u32>,
+        #[prost(uint32, optional, tag = "5")]
+        pub quinary: ::core::option::Option<u32>,
+    }
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum PlatformType {
+        Unknown = 0,
+        Chrome = 1,
+        Firefox = 2,
+        Ie = 3,
+        Opera = 4,
+        Safari = 5,
+        Edge = 6,
+        Desktop = 7,
+        Ipad = 8,
+        AndroidTablet = 9,
+        Ohana = 10,
+        Aloha = 11,
+        Catalina = 12,
+        TclTv = 13,
+        IosPhone = 14,
+        IosCatalyst = 15,
+        AndroidPhone = 16,
+        AndroidAmbiguous = 17,
+        WearOs = 18,
+        ArWrist = 19,
+        ArDevice = 20,
+        Uwp = 21,
+        Vr = 22,
+    }
+    impl PlatformType {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::Unknown => "UNKNOWN",
+                Self::Chrome => "CHROME",
+                Self::Firefox => "FIREFOX",
+                Self::Ie => "IE",
+                Self::Opera => "OPERA",
+                Self::Safari => "SAFARI",
+                Self::Edge => "EDGE",
+                Self::Desktop => "DESKTOP",
+                Self::Ipad => "IPAD",
+                Self::AndroidTablet => "ANDROID_TABLET",
+                Self::Ohana => "OHANA",
+                Self::Aloha => "ALOHA",
+                Self::Catalina => "CATALINA",
+                Self::TclTv => "TCL_TV",
+                Self::IosPhone => "IOS_PHONE",
+                Self::IosCatalyst => "IOS_CATALYST",
+                Self::AndroidPhone => "ANDROID_PHONE",
+                Self::AndroidAmbiguous => "ANDROID_AMBIGUOUS",
+                Self::WearOs => "WEAR_OS",
+                Self::ArWrist => "AR_WRIST",
+                Self::ArDevice => "AR_DEVICE",
+                Self::Uwp => "UWP",
+                Self::Vr => "VR",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "UNKNOWN" => Some(Self::Unknown),
+                "CHROME" => Some(Self::Chrome),
+                "FIREFOX" => Some(Self::Firefox),
+                "IE" => Some(Self::Ie),
+                "OPERA" => Some(Self::Opera),
+                "SAFARI" => Some(Self::Safari),
+                "EDGE" => Some(Self::Edge),
+                "DESKTOP" => Some(Self::Desktop),
+                "IPAD" => Some(Self::Ipad),
+                "ANDROID_TABLET" => Some(Self::AndroidTablet),
+                "OHANA" => Some(Self::Ohana),
+                "ALOHA" => Some(Self::Aloha),
+                "CATALINA" => Some(Self::Catalina),
+                "TCL_TV" => Some(Self::TclTv),
+                "IOS_PHONE" => Some(Self::IosPhone),
+                "IOS_CATALYST" => Some(Self::IosCatalyst),
+                "ANDROID_PHONE" => Some(Self::AndroidPhone),
+                "ANDROID_AMBIGUOUS" => Some(Self::AndroidAmbiguous),
+                "WEAR_OS" => Some(Self::WearOs),
+                "AR_WRIST" => Some(Self::ArWrist),
+                "AR_DEVICE" => Some(Self::ArDevice),
+                "UWP" => Some(Self::Uwp),
+                "VR" => Some(Self::Vr),
+                _ => None,
+            }
+        }
+    }
+}
@@ -0,0 +1,992 @@
+// This file is @generated by prost-build.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Message {
+    #[prost(string, optional, tag = "1")]
+    pub conversation: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(message, optional, tag = "2")]
+    pub sender_key_distribution_message: ::core::option::Option<SenderKeyDistributionMessage>,
+    #[prost(message, optional, boxed, tag = "3")]
+    pub image_message: ::core::option::Option<::prost::alloc::boxed::Box<ImageMessage>>,
+    #[prost(message, optional, boxed, tag = "4")]
+    pub contact_message: ::core::option::Option<::prost::alloc::boxed::Box<ContactMessage>>,
+    #[prost(message, optional, boxed, tag = "5")]
+    pub location_message: ::core::option::Option<::prost::alloc::boxed::Box<LocationMessage>>,
+    #[prost(message, optional, boxed, tag = "6")]
+    pub extended_text_message:
+        ::core::option::Option<::prost::alloc::boxed::Box<ExtendedTextMessage>>,
+    #[prost(message, optional, boxed, tag = "7")]
+    pub document_message: ::core::option::Option<::prost::alloc::boxed::Box<DocumentMessage>>,
+    #[prost(message, optional, boxed, tag = "8")]
+    pub audio_message: ::core::option::Option<::prost::alloc::boxed::Box<AudioMessage>>,
+    #[prost(message, optional, boxed, tag = "9")]
+    pub video_message: ::core::option::Option<::prost::alloc::boxed::Box<VideoMessage>>,
+    #[prost(message, optional, boxed, tag = "12")]
+    pub protocol_message: ::core::option::Option<::prost::alloc::boxed::Box<ProtocolMessage>>,
+    #[prost(message, optional, boxed, tag = "13")]
+    pub contacts_array_message:
+        ::core::option::Option<::prost::alloc::boxed::Box<ContactsArrayMessage>>,
+    #[prost(message, optional, tag = "15")]
+    pub fast_ratchet_key_sender_key_distribution_message:
+        ::core::option::Option<SenderKeyDistributionMessage>,
+    #[prost(message, optional, boxed, tag = "18")]
+    pub live_location_message:
+        ::core::option::Option<::prost::alloc::boxed::Box<LiveLocationMessage>>,
+    #[prost(message, optional, boxed, tag = "26")]
+    pub sticker_message: ::core::option::Option<::prost::alloc::boxed::Box<StickerMessage>>,
+    #[prost(message, optional, boxed, tag = "28")]
+    pub group_invite_message:
+        ::core::option::Option<::prost::alloc::boxed::Box<GroupInviteMessage>>,
+    #[prost(message, optional, boxed, tag = "31")]
+    pub device_sent_message: ::core::option::Option<::prost::alloc::boxed::Box<DeviceSentMessage>>,
+    #[prost(message, optional, tag = "35")]
+    pub message_context_info: ::core::option::Option<MessageContextInfo>,
+    #[prost(message, optional, boxed, tag = "37")]
+    pub view_once_message: ::core::option::Option<::prost::alloc::boxed::Box<FutureProofMessage>>,
+    #[prost(message, optional, boxed, tag = "40")]
+    pub ephemeral_message: ::core::option::Option<::prost::alloc::boxed::Box<FutureProofMessage>>,
+    #[prost(message, optional, tag = "46")]
+    pub reaction_message: ::core::option::Option<ReactionMessage>,
+    #[prost(message, optional, boxed, tag = "49")]
+    pub poll_creation_message:
+        ::core::option::Option<::prost::alloc::boxed::Box<PollCreationMessage>>,
+    #[prost(message, optional, tag = "50")]
+    pub poll_update_message: ::core::option::Option<PollUpdateMessage>,
+    #[prost(message, optional, boxed, tag = "53")]
+    pub document_with_caption_message:
+        ::core::option::Option<::prost::alloc::boxed::Box<FutureProofMessage>>,
+    #[prost(message, optional, boxed, tag = "55")]
+    pub view_once_message_v2:
+        ::core::option::Option<::prost::alloc::boxed::Box<FutureProofMessage>>,
+    #[prost(message, optional, tag = "56")]
+    pub enc_reaction_message: ::core::option::Option<EncReactionMessage>,
+    #[prost(message, optional, boxed, tag = "58")]
+    pub edited_message: ::core::option::Option<::prost::alloc::boxed::Box<FutureProofMessage>>,
+    #[prost(message, optional, boxed, tag = "59")]
+    pub view_once_message_v2_extension:
+        ::core::option::Option<::prost::alloc::boxed::Box<FutureProofMessage>>,
+    #[prost(message, optional, boxed, tag = "60")]
+    pub poll_creation_message_v2:
+        ::core::option::Option<::prost::alloc::boxed::Box<PollCreationMessage>>,
+    #[prost(message, optional, tag = "63")]
+    pub pin_in_chat_message: ::core::option::Option<PinInChatMessage>,
+    #[prost(message, optional, boxed, tag = "64")]
+    pub poll_creation_message_v3:
+        ::core::option::Option<::prost::alloc::boxed::Box<PollCreationMessage>>,
+    #[prost(message, optional, boxed, tag = "66")]
+    pub ptv_message: ::core::option::Option<::prost::alloc::boxed::Box<VideoMessage>>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FutureProofMessage {
+    #[prost(message, optional, boxed, tag = "1")]
+    pub message: ::core::option::Option<::prost::alloc::boxed::Box<Message>>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DeviceSentMessage {
+    #[prost(string, optional, tag = "1")]
+    pub destination_jid: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(message, optional, boxed, tag = "2")]
+    pub message: ::core::option::Option<::prost::alloc::boxed::Box<Message>>,
+    #[prost(string, optional, tag = "3")]
+    pub phash: ::core::option::Option<::prost::alloc::string::String>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SenderKeyDistributionMessage {
+    #[prost(string, optional, tag = "1")]
+    pub group_id: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(bytes = "vec", optional, tag = "2")]
+    pub axolotl_sender_key_distribution_message:
+        ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MessageContextInfo {
+    #[prost(message, optional, tag = "1")]
+    pub device_list_metadata: ::core::option::Option<DeviceListMetadata>,
+    #[prost(int32, optional, tag = "2")]
+    pub device_list_metadata_version: ::core::option::Option<i32>,
+    #[prost(bytes = "vec", optional, tag = "3")]
+    pub message_secret: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "4")]
+    pub padding_bytes: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(uint32, optional, tag = "5")]
+    pub message_add_on_duration_in_secs: ::core::option::Option<u32>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DeviceListMetadata {
+    #[prost(bytes = "vec", optional, tag = "1")]
+    pub sender_key_hash: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(uint64, optional, tag = "2")]
+    pub sender_timestamp: ::core::option::Option<u64>,
+    #[prost(uint32, repeated, tag = "3")]
+    pub sender_key_indexes: ::prost::alloc::vec::Vec<u32>,
+    #[prost(enumeration = "super::wa_adv::AdvEncryptionType", optional, tag = "4")]
+    pub sender_account_type: ::core::option::Option<i32>,
+    #[prost(enumeration = "super::wa_adv::AdvEncryptionType", optional, tag = "5")]
+    pub receiver_account_type: ::core::option::Option<i32>,
+    #[prost(bytes = "vec", optional, tag = "8")]
+    pub recipient_key_hash: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(uint64, optional, tag = "9")]
+    pub recipient_timestamp: ::core::option::Option<u64>,
+    #[prost(uint32, repeated, tag = "10")]
+    pub recipient_key_indexes: ::prost::alloc::vec::Vec<u32>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ContextInfo {
+    #[prost(string, optional, tag = "1")]
+    pub stanza_id: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "2")]
+    pub participant: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(message, optional, boxed, tag = "3")]
+    pub quoted_message: ::core::option::Option<::prost::alloc::boxed::Box<Message>>,
+    #[prost(string, optional, tag = "4")]
+    pub remote_jid: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, repeated, tag = "15")]
+    pub mentioned_jid: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(uint32, optional, tag = "21")]
+    pub forwarding_score: ::core::option::Option<u32>,
+    #[prost(bool, optional, tag = "22")]
+    pub is_forwarded: ::core::option::Option<bool>,
+    #[prost(uint32, optional, tag = "25")]
+    pub expiration: ::core::option::Option<u32>,
+    #[prost(int64, optional, tag = "26")]
+    pub ephemeral_setting_timestamp: ::core::option::Option<i64>,
+    #[prost(bytes = "vec", optional, tag = "27")]
+    pub ephemeral_shared_secret: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(message, optional, tag = "40")]
+    pub disappearing_mode: ::core::option::Option<DisappearingMode>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DisappearingMode {
+    #[prost(enumeration = "disappearing_mode::Initiator", optional, tag = "1")]
+    pub initiator: ::core::option::Option<i32>,
+    #[prost(enumeration = "disappearing_mode::Trigger", optional, tag = "2")]
+    pub trigger: ::core::option::Option<i32>,
+    #[prost(string, optional, tag = "3")]
+    pub initiator_device_jid: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(bool, optional, tag = "4")]
+    pub initiated_by_me: ::core::option::Option<bool>,
+}
+/// Nested message and enum types in `DisappearingMode`.
+pub mod disappearing_mode {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Initiator {
+        ChangedInChat = 0,
+        InitiatedByMe = 1,
+        InitiatedByOther = 2,
+    }
+    impl Initiator {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::ChangedInChat => "CHANGED_IN_CHAT",
+                Self::InitiatedByMe => "INITIATED_BY_ME",
+                Self::InitiatedByOther => "INITIATED_BY_OTHER",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "CHANGED_IN_CHAT" => Some(Self::ChangedInChat),
+                "INITIATED_BY_ME" => Some(Self::InitiatedByMe),
+                "INITIATED_BY_OTHER" => Some(Self::InitiatedByOther),
+                _ => None,
+            }
+        }
+    }
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Trigger {
+        Unknown = 0,
+        ChatSetting = 1,
+        AccountSetting = 2,
+        BulkChange = 3,
+    }
+    impl Trigger {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::Unknown => "UNKNOWN",
+                Self::ChatSetting => "CHAT_SETTING",
+                Self::AccountSetting => "ACCOUNT_SETTING",
+                Self::BulkChange => "BULK_CHANGE",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "UNKNOWN" => Some(Self::Unknown),
+                "CHAT_SETTING" => Some(Self::ChatSetting),
+                "ACCOUNT_SETTING" => Some(Self::AccountSetting),
+                "BULK_CHANGE" => Some(Self::BulkChange),
+                _ => None,
+            }
+        }
+    }
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExtendedTextMessage {
+    #[prost(string, optional, tag = "1")]
+    pub text: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "2")]
+    pub matched_text: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "4")]
+    pub canonical_url: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "5")]
+    pub description: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "6")]
+    pub title: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(fixed32, optional, tag = "7")]
+    pub text_argb: ::core::option::Option<u32>,
+    #[prost(fixed32, optional, tag = "8")]
+    pub background_argb: ::core::option::Option<u32>,
+    #[prost(enumeration = "extended_text_message::FontType", optional, tag = "9")]
+    pub font: ::core::option::Option<i32>,
+    #[prost(
+        enumeration = "extended_text_message::PreviewType",
+        optional,
+        tag = "10"
+    )]
+    pub preview_type: ::core::option::Option<i32>,
+    #[prost(bytes = "vec", optional, tag = "16")]
+    pub jpeg_thumbnail: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(message, optional, boxed, tag = "17")]
+    pub context_info: ::core::option::Option<::prost::alloc::boxed::Box<ContextInfo>>,
+    #[prost(bool, optional, tag = "18")]
+    pub do_not_play_inline: ::core::option::Option<bool>,
+}
+/// Nested message and enum types in `ExtendedTextMessage`.
+pub mod extended_text_message {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum PreviewType {
+        None = 0,
+        Video = 1,
+    }
+    impl PreviewType {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::None => "NONE",
+                Self::Video => "VIDEO",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "NONE" => Some(Self::None),
+                "VIDEO" => Some(Self::Video),
+                _ => None,
+            }
+        }
+    }
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum FontType {
+        System = 0,
+        SystemText = 1,
+        FbScript = 2,
+        SystemBold = 6,
+    }
+    impl FontType {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::System => "SYSTEM",
+                Self::SystemText => "SYSTEM_TEXT",
+                Self::FbScript => "FB_SCRIPT",
+                Self::SystemBold => "SYSTEM_BOLD",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "SYSTEM" => Some(Self::System),
+                "SYSTEM_TEXT" => Some(Self::SystemText),
+                "FB_SCRIPT" => Some(Self::FbScript),
+                "SYSTEM_BOLD" => Some(Self::SystemBold),
+                _ => None,
+            }
+        }
+    }
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ImageMessage {
+    #[prost(string, optional, tag = "1")]
+    pub url: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "2")]
+    pub mimetype: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "3")]
+    pub caption: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(bytes = "vec", optional, tag = "4")]
+    pub file_sha256: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(uint64, optional, tag = "5")]
+    pub file_length: ::core::option::Option<u64>,
+    #[prost(uint32, optional, tag = "6")]
+    pub height: ::core::option::Option<u32>,
+    #[prost(uint32, optional, tag = "7")]
+    pub width: ::core::option::Option<u32>,
+    #[prost(bytes = "vec", optional, tag = "8")]
+    pub media_key: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "9")]
+    pub file_enc_sha256: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(string, optional, tag = "11")]
+    pub direct_path: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(int64, optional, tag = "12")]
+    pub media_key_timestamp: ::core::option::Option<i64>,
+    #[prost(bytes = "vec", optional, tag = "16")]
+    pub jpeg_thumbnail: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(message, optional, boxed, tag = "17")]
+    pub context_info: ::core::option::Option<::prost::alloc::boxed::Box<ContextInfo>>,
+    #[prost(bytes = "vec", optional, tag = "18")]
+    pub first_scan_sidecar: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(uint32, optional, tag = "19")]
+    pub first_scan_length: ::core::option::Option<u32>,
+    #[prost(bytes = "vec", optional, tag = "21")]
+    pub scans_sidecar: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(uint32, repeated, packed = "false", tag = "22")]
+    pub scan_lengths: ::prost::alloc::vec::Vec<u32>,
+    #[prost(bytes = "vec", optional, tag = "23")]
+    pub mid_quality_file_sha256: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "24")]
+    pub mid_quality_file_enc_sha256: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(bool, optional, tag = "25")]
+    pub view_once: ::core::option::Option<bool>,
+    #[prost(string, optional, tag = "26")]
+    pub thumbnail_direct_path: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(bytes = "vec", optional, tag = "27")]
+    pub thumbnail_sha256: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "28")]
+    pub thumbnail_enc_sha256: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(string, optional, tag = "29")]
+    pub static_url: ::core::option::Option<::prost::alloc::string::String>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VideoMessage {
+    #[prost(string, optional, tag = "1")]
+    pub url: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "2")]
+    pub mimetype: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(bytes = "vec", optional, tag = "3")]
+    pub file_sha256: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(uint64, optional, tag = "4")]
+    pub file_length: ::core::option::Option<u64>,
+    #[prost(uint32, optional, tag = "5")]
+    pub seconds: ::core::option::Option<u32>,
+    #[prost(bytes = "vec", optional, tag = "6")]
+    pub media_key: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(string, optional, tag = "7")]
+    pub caption: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(bool, optional, tag = "8")]
+    pub gif_playback: ::core::option::Option<bool>,
+    #[prost(uint32, optional, tag = "9")]
+    pub height: ::core::option::Option<u32>,
+    #[prost(uint32, optional, tag = "10")]
+    pub width: ::core::option::Option<u32>,
+    #[prost(bytes = "vec", optional, tag = "11")]
+    pub file_enc_sha256: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(string, optional, tag = "13")]
+    pub direct_path: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(int64, optional, tag = "14")]
+    pub media_key_timestamp: ::core::option::Option<i64>,
+    #[prost(bytes = "vec", optional, tag = "16")]
+    pub jpeg_thumbnail: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(message, optional, boxed, tag = "17")]
+    pub context_info: ::core::option::Option<::prost::alloc::boxed::Box<ContextInfo>>,
+    #[prost(bytes = "vec", optional, tag = "18")]
+    pub streaming_sidecar: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(enumeration = "video_message::Attribution", optional, tag = "19")]
+    pub gif_attribution: ::core::option::Option<i32>,
+    #[prost(bool, optional, tag = "20")]
+    pub view_once: ::core::option::Option<bool>,
+    #[prost(string, optional, tag = "21")]
+    pub thumbnail_direct_path: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(bytes = "vec", optional, tag = "22")]
+    pub thumbnail_sha256: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "23")]
+    pub thumbnail_enc_sha256: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(string, optional, tag = "24")]
+    pub static_url: ::core::option::Option<::prost::alloc::string::String>,
+}
+/// Nested message and enum types in `VideoMessage`.
+pub mod video_message {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Attribution {
+        None = 0,
+        Giphy = 1,
+        Tenor = 2,
+    }
+    impl Attribution {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::None => "NONE",
+                Self::Giphy => "GIPHY",
+                Self::Tenor => "TENOR",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "NONE" => Some(Self::None),
+                "GIPHY" => Some(Self::Giphy),
+                "TENOR" => Some(Self::Tenor),
+                _ => None,
+            }
+        }
+    }
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AudioMessage {
+    #[prost(string, optional, tag = "1")]
+    pub url: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "2")]
+    pub mimetype: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(bytes = "vec", optional, tag = "3")]
+    pub file_sha256: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(uint64, optional, tag = "4")]
+    pub file_length: ::core::option::Option<u64>,
+    #[prost(uint32, optional, tag = "5")]
+    pub seconds: ::core::option::Option<u32>,
+    #[prost(bool, optional, tag = "6")]
+    pub ptt: ::core::option::Option<bool>,
+    #[prost(bytes = "vec", optional, tag = "7")]
+    pub media_key: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "8")]
+    pub file_enc_sha256: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(string, optional, tag = "9")]
+    pub direct_path: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(int64, optional, tag = "10")]
+    pub media_key_timestamp: ::core::option::Option<i64>,
+    #[prost(message, optional, boxed, tag = "17")]
+    pub context_info: ::core::option::Option<::prost::alloc::boxed::Box<ContextInfo>>,
+    #[prost(bytes = "vec", optional, tag = "18")]
+    pub streaming_sidecar: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "19")]
+    pub waveform: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(fixed32, optional, tag = "20")]
+    pub background_argb: ::core::option::Option<u32>,
+    #[prost(bool, optional, tag = "21")]
+    pub view_once: ::core::option::Option<bool>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DocumentMessage {
+    #[prost(string, optional, tag = "1")]
+    pub url: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "2")]
+    pub mimetype: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "3")]
+    pub title: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(bytes = "vec", optional, tag = "4")]
+    pub file_sha256: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(uint64, optional, tag = "5")]
+    pub file_length: ::core::option::Option<u64>,
+    #[prost(uint32, optional, tag = "6")]
+    pub page_count: ::core::option::Option<u32>,
+    #[prost(bytes = "vec", optional, tag = "7")]
+    pub media_key: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(string, optional, tag = "8")]
+    pub file_name: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(bytes = "vec", optional, tag = "9")]
+    pub file_enc_sha256: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(string, optional, tag = "10")]
+    pub direct_path: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(int64, optional, tag = "11")]
+    pub media_key_timestamp: ::core::option::Option<i64>,
+    #[prost(bool, optional, tag = "12")]
+    pub contact_vcard: ::core::option::Option<bool>,
+    #[prost(string, optional, tag = "13")]
+    pub thumbnail_direct_path: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(bytes = "vec", optional, tag = "14")]
+    pub thumbnail_sha256: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "15")]
+    pub thumbnail_enc_sha256: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "16")]
+    pub jpeg_thumbnail: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(message, optional, boxed, tag = "17")]
+    pub context_info: ::core::option::Option<::prost::alloc::boxed::Box<ContextInfo>>,
+    #[prost(uint32, optional, tag = "18")]
+    pub thumbnail_height: ::core::option::Option<u32>,
+    #[prost(uint32, optional, tag = "19")]
+    pub thumbnail_width: ::core::option::Option<u32>,
+    #[prost(string, optional, tag = "20")]
+    pub caption: ::core::option::Option<::prost::alloc::string::String>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StickerMessage {
+    #[prost(string, optional, tag = "1")]
+    pub url: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(bytes = "vec", optional, tag = "2")]
+    pub file_sha256: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "3")]
+    pub file_enc_sha256: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "4")]
+    pub media_key: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(string, optional, tag = "5")]
+    pub mimetype: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(uint32, optional, tag = "6")]
+    pub height: ::core::option::Option<u32>,
+    #[prost(uint32, optional, tag = "7")]
+    pub width: ::core::option::Option<u32>,
+    #[prost(string, optional, tag = "8")]
+    pub direct_path: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(uint64, optional, tag = "9")]
+    pub file_length: ::core::option::Option<u64>,
+    #[prost(int64, optional, tag = "10")]
+    pub media_key_timestamp: ::core::option::Option<i64>,
+    #[prost(uint32, optional, tag = "11")]
+    pub first_frame_length: ::core::option::Option<u32>,
+    #[prost(bytes = "vec", optional, tag = "12")]
+    pub first_frame_sidecar: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(bool, optional, tag = "13")]
+    pub is_animated: ::core::option::Option<bool>,
+    #[prost(bytes = "vec", optional, tag = "16")]
+    pub png_thumbnail: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(message, optional, boxed, tag = "17")]
+    pub context_info: ::core::option::Option<::prost::alloc::boxed::Box<ContextInfo>>,
+    #[prost(int64, optional, tag = "18")]
+    pub sticker_sent_ts: ::core::option::Option<i64>,
+    #[prost(bool, optional, tag = "19")]
+    pub is_avatar: ::core::option::Option<bool>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ContactMessage {
+    #[prost(string, optional, tag = "1")]
+    pub display_name: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "16")]
+    pub vcard: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(message, optional, boxed, tag = "17")]
+    pub context_info: ::core::option::Option<::prost::alloc::boxed::Box<ContextInfo>>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ContactsArrayMessage {
+    #[prost(string, optional, tag = "1")]
+    pub display_name: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(message, repeated, tag = "2")]
+    pub contacts: ::prost::alloc::vec::Vec<ContactMessage>,
+    #[prost(message, optional, boxed, tag = "17")]
+    pub context_info: ::core::option::Option<::prost::alloc::boxed::Box<ContextInfo>>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LocationMessage {
+    #[prost(double, optional, tag = "1")]
+    pub degrees_latitude: ::core::option::Option<f64>,
+    #[prost(double, optional, tag = "2")]
+    pub degrees_longitude: ::core::option::Option<f64>,
+    #[prost(string, optional, tag = "3")]
+    pub name: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "4")]
+    pub address: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "5")]
+    pub url: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(bool, optional, tag = "6")]
+    pub is_live: ::core::option::Option<bool>,
+    #[prost(uint32, optional, tag = "7")]
+    pub accuracy_in_meters: ::core::option::Option<u32>,
+    #[prost(float, optional, tag = "8")]
+    pub speed_in_mps: ::core::option::Option<f32>,
+    #[prost(uint32, optional, tag = "9")]
+    pub degrees_clockwise_from_magnetic_north: ::core::option::Option<u32>,
+    #[prost(string, optional, tag = "11")]
+    pub comment: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(bytes = "vec", optional, tag = "16")]
+    pub jpeg_thumbnail: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(message, optional, boxed, tag = "17")]
+    pub context_info: ::core::option::Option<::prost::alloc::boxed::Box<ContextInfo>>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LiveLocationMessage {
+    #[prost(double, optional, tag = "1")]
+    pub degrees_latitude: ::core::option::Option<f64>,
+    #[prost(double, optional, tag = "2")]
+    pub degrees_longitude: ::core::option::Option<f64>,
+    #[prost(uint32, optional, tag = "3")]
+    pub accuracy_in_meters: ::core::option::Option<u32>,
+    #[prost(float, optional, tag = "4")]
+    pub speed_in_mps: ::core::option::Option<f32>,
+    #[prost(uint32, optional, tag = "5")]
+    pub degrees_clockwise_from_magnetic_north: ::core::option::Option<u32>,
+    #[prost(string, optional, tag = "6")]
+    pub caption: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(int64, optional, tag = "7")]
+    pub sequence_number: ::core::option::Option<i64>,
+    #[prost(uint32, optional, tag = "8")]
+    pub time_offset: ::core::option::Option<u32>,
+    #[prost(bytes = "vec", optional, tag = "16")]
+    pub jpeg_thumbnail: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(message, optional, boxed, tag = "17")]
+    pub context_info: ::core::option::Option<::prost::alloc::boxed::Box<ContextInfo>>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GroupInviteMessage {
+    #[prost(string, optional, tag = "1")]
+    pub group_jid: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "2")]
+    pub invite_code: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(int64, optional, tag = "3")]
+    pub invite_expiration: ::core::option::Option<i64>,
+    #[prost(string, optional, tag = "4")]
+    pub group_name: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(bytes = "vec", optional, tag = "5")]
+    pub jpeg_thumbnail: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(string, optional, tag = "6")]
+    pub caption: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(message, optional, boxed, tag = "7")]
+    pub context_info: ::core::option::Option<::prost::alloc::boxed::Box<ContextInfo>>,
+    #[prost(enumeration = "group_invite_message::GroupType", optional, tag = "8")]
+    pub group_type: ::core::option::Option<i32>,
+}
+/// Nested message and enum types in `GroupInviteMessage`.
+pub mod group_invite_message {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum GroupType {
+        Default = 0,
+        Parent = 1,
+    }
+    impl GroupType {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::Default => "DEFAULT",
+                Self::Parent => "PARENT",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "DEFAULT" => Some(Self::Default),
+                "PARENT" => Some(Self::Parent),
+                _ => None,
+            }
+        }
+    }
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ReactionMessage {
+    #[prost(message, optional, tag = "1")]
+    pub key: ::core::option::Option<super::wa_common::MessageKey>,
+    #[prost(string, optional, tag = "2")]
+    pub text: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "3")]
+    pub grouping_key: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(int64, optional, tag = "4")]
+    pub sender_timestamp_ms: ::core::option::Option<i64>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EncReactionMessage {
+    #[prost(message, optional, tag = "1")]
+    pub target_message_key: ::core::option::Option<super::wa_common::MessageKey>,
+    #[prost(bytes = "vec", optional, tag = "2")]
+    pub enc_payload: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "3")]
+    pub enc_iv: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PollCreationMessage {
+    #[prost(bytes = "vec", optional, tag = "1")]
+    pub enc_key: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(string, optional, tag = "2")]
+    pub name: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(message, repeated, tag = "3")]
+    pub options: ::prost::alloc::vec::Vec<poll_creation_message::Option>,
+    #[prost(uint32, optional, tag = "4")]
+    pub selectable_options_count: ::core::option::Option<u32>,
+    #[prost(message, optional, boxed, tag = "5")]
+    pub context_info: ::core::option::Option<::prost::alloc::boxed::Box<ContextInfo>>,
+}
+/// Nested message and enum types in `PollCreationMessage`.
+pub mod poll_creation_message {
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Option {
+        #[prost(string, optional, tag = "1")]
+        pub option_name: ::core::option::Option<::prost::alloc::string::String>,
+    }
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PollUpdateMessage {
+    #[prost(message, optional, tag = "1")]
+    pub poll_creation_message_key: ::core::option::Option<super::wa_common::MessageKey>,
+    #[prost(message, optional, tag = "2")]
+    pub vote: ::core::option::Option<PollEncValue>,
+    #[prost(message, optional, tag = "3")]
+    pub metadata: ::core::option::Option<PollUpdateMessageMetadata>,
+    #[prost(int64, optional, tag = "4")]
+    pub sender_timestamp_ms: ::core::option::Option<i64>,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct PollUpdateMessageMetadata {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PollEncValue {
+    #[prost(bytes = "vec", optional, tag = "1")]
+    pub enc_payload: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "2")]
+    pub enc_iv: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PollVoteMessage {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pub selected_options: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PinInChatMessage {
+    #[prost(message, optional, tag = "1")]
+    pub key: ::core::option::Option<super::wa_common::MessageKey>,
+    #[prost(enumeration = "pin_in_chat_message::Type", optional, tag = "2")]
+    pub r#type: ::core::option::Option<i32>,
+    #[prost(int64, optional, tag = "3")]
+    pub sender_timestamp_ms: ::core::option::Option<i64>,
+}
+/// Nested message and enum types in `PinInChatMessage`.
+pub mod pin_in_chat_message {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Type {
+        UnknownType = 0,
+        PinForAll = 1,
+        UnpinForAll = 2,
+    }
+    impl Type {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::UnknownType => "UNKNOWN_TYPE",
+                Self::PinForAll => "PIN_FOR_ALL",
+                Self::UnpinForAll => "UNPIN_FOR_ALL",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "UNKNOWN_TYPE" => Some(Self::UnknownType),
+                "PIN_FOR_ALL" => Some(Self::PinForAll),
+                "UNPIN_FOR_ALL" => Some(Self::UnpinForAll),
+                _ => None,
+            }
+        }
+    }
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ProtocolMessage {
+    #[prost(message, optional, tag = "1")]
+    pub key: ::core::option::Option<super::wa_common::MessageKey>,
+    #[prost(enumeration = "protocol_message::Type", optional, tag = "2")]
+    pub r#type: ::core::option::Option<i32>,
+    #[prost(uint32, optional, tag = "4")]
+    pub ephemeral_expiration: ::core::option::Option<u32>,
+    #[prost(int64, optional, tag = "5")]
+    pub ephemeral_setting_timestamp: ::core::option::Option<i64>,
+    #[prost(message, optional, tag = "6")]
+    pub history_sync_notification: ::core::option::Option<HistorySyncNotification>,
+    #[prost(message, optional, tag = "7")]
+    pub app_state_sync_key_share: ::core::option::Option<AppStateSyncKeyShare>,
+    #[prost(message, optional, tag = "11")]
+    pub disappearing_mode: ::core::option::Option<DisappearingMode>,
+    #[prost(message, optional, boxed, tag = "14")]
+    pub edited_message: ::core::option::Option<::prost::alloc::boxed::Box<Message>>,
+    #[prost(int64, optional, tag = "15")]
+    pub timestamp_ms: ::core::option::Option<i64>,
+}
+/// Nested message and enum types in `ProtocolMessage`.
+pub mod protocol_message {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Type {
+        Revoke = 0,
+        EphemeralSetting = 3,
+        EphemeralSyncResponse = 4,
+        HistorySyncNotification = 5,
+        AppStateSyncKeyShare = 6,
+        AppStateSyncKeyRequest = 7,
+        MsgFanoutBackfillRequest = 8,
+        InitialSecurityNotificationSettingSync = 9,
+        AppStateFatalExceptionNotification = 10,
+        SharePhoneNumber = 11,
+        MessageEdit = 14,
+        PeerDataOperationRequestMessage = 16,
+        PeerDataOperationRequestResponseMessage = 17,
+    }
+    impl Type {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::Revoke => "REVOKE",
+                Self::EphemeralSetting => "EPHEMERAL_SETTING",
+                Self::EphemeralSyncResponse => "EPHEMERAL_SYNC_RESPONSE",
+                Self::HistorySyncNotification => "HISTORY_SYNC_NOTIFICATION",
+                Self::AppStateSyncKeyShare => "APP_STATE_SYNC_KEY_SHARE",
+                Self::AppStateSyncKeyRequest => "APP_STATE_SYNC_KEY_REQUEST",
+                Self::MsgFanoutBackfillRequest => "MSG_FANOUT_BACKFILL_REQUEST",
+                Self::InitialSecurityNotificationSettingSync => {
+                    "INITIAL_SECURITY_NOTIFICATION_SETTING_SYNC"
+                }
+                Self::AppStateFatalExceptionNotification => {
+                    "APP_STATE_FATAL_EXCEPTION_NOTIFICATION"
+                }
+                Self::SharePhoneNumber => "SHARE_PHONE_NUMBER",
+                Self::MessageEdit => "MESSAGE_EDIT",
+                Self::PeerDataOperationRequestMessage => "PEER_DATA_OPERATION_REQUEST_MESSAGE",
+                Self::PeerDataOperationRequestResponseMessage => {
+                    "PEER_DATA_OPERATION_REQUEST_RESPONSE_MESSAGE"
+                }
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "REVOKE" => Some(Self::Revoke),
+                "EPHEMERAL_SETTING" => Some(Self::EphemeralSetting),
+                "EPHEMERAL_SYNC_RESPONSE" => Some(Self::EphemeralSyncResponse),
+                "HISTORY_SYNC_NOTIFICATION" => Some(Self::HistorySyncNotification),
+                "APP_STATE_SYNC_KEY_SHARE" => Some(Self::AppStateSyncKeyShare),
+                "APP_STATE_SYNC_KEY_REQUEST" => Some(Self::AppStateSyncKeyRequest),
+                "MSG_FANOUT_BACKFILL_REQUEST" => Some(Self::MsgFanoutBackfillRequest),
+                "INITIAL_SECURITY_NOTIFICATION_SETTING_SYNC" => {
+                    Some(Self::InitialSecurityNotificationSettingSync)
+                }
+                "APP_STATE_FATAL_EXCEPTION_NOTIFICATION" => {
+                    Some(Self::AppStateFatalExceptionNotification)
+                }
+                "SHARE_PHONE_NUMBER" => Some(Self::SharePhoneNumber),
+                "MESSAGE_EDIT" => Some(Self::MessageEdit),
+                "PEER_DATA_OPERATION_REQUEST_MESSAGE" => {
+                    Some(Self::PeerDataOperationRequestMessage)
+                }
+                "PEER_DATA_OPERATION_REQUEST_RESPONSE_MESSAGE" => {
+                    Some(Self::PeerDataOperationRequestResponseMessage)
+                }
+                _ => None,
+            }
+        }
+    }
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HistorySyncNotification {
+    #[prost(bytes = "vec", optional, tag = "1")]
+    pub file_sha256: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(uint64, optional, tag = "2")]
+    pub file_length: ::core::option::Option<u64>,
+    #[prost(bytes = "vec", optional, tag = "3")]
+    pub media_key: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "4")]
+    pub file_enc_sha256: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(string, optional, tag = "5")]
+    pub direct_path: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(
+        enumeration = "history_sync_notification::HistorySyncType",
+        optional,
+        tag = "6"
+    )]
+    pub sync_type: ::core::option::Option<i32>,
+    #[prost(uint32, optional, tag = "7")]
+    pub chunk_order: ::core::option::Option<u32>,
+    #[prost(string, optional, tag = "8")]
+    pub original_message_id: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(uint32, optional, tag = "9")]
+    pub progress: ::core::option::Option<u32>,
+    #[prost(int64, optional, tag = "10")]
+    pub oldest_msg_in_chunk_timestamp_sec: ::core::option::Option<i64>,
+}
+/// Nested message and enum types in `HistorySyncNotification`.
+pub mod history_sync_notification {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum HistorySyncType {
+        InitialBootstrap = 0,
+        InitialStatusV3 = 1,
+        Full = 2,
+        Recent = 3,
+        PushName = 4,
+        NonBlockingData = 5,
+        OnDemand = 6,
+    }
+    impl HistorySyncType {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::InitialBootstrap => "INITIAL_BOOTSTRAP",
+                Self::InitialStatusV3 => "INITIAL_STATUS_V3",
+                Self::Full => "FULL",
+                Self::Recent => "RECENT",
+                Self::PushName => "PUSH_NAME",
+                Self::NonBlockingData => "NON_BLOCKING_DATA",
+                Self::OnDemand => "ON_DEMAND",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "INITIAL_BOOTSTRAP" => Some(Self::InitialBootstrap),
+                "INITIAL_STATUS_V3" => Some(Self::InitialStatusV3),
+                "FULL" => Some(Self::Full),
+                "RECENT" => Some(Self::Recent),
+                "PUSH_NAME" => Some(Self::PushName),
+                "NON_BLOCKING_DATA" => Some(Self::NonBlockingData),
+                "ON_DEMAND" => Some(Self::OnDemand),
+                _ => None,
+            }
+        }
+    }
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AppStateSyncKeyShare {
+    #[prost(message, repeated, tag = "1")]
+    pub keys: ::prost::alloc::vec::Vec<AppStateSyncKey>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AppStateSyncKey {
+    #[prost(message, optional, tag = "1")]
+    pub key_id: ::core::option::Option<AppStateSyncKeyId>,
+    #[prost(message, optional, tag = "2")]
+    pub key_data: ::core::option::Option<AppStateSyncKeyData>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AppStateSyncKeyId {
+    #[prost(bytes = "vec", optional, tag = "1")]
+    pub key_id: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AppStateSyncKeyData {
+    #[prost(bytes = "vec", optional, tag = "1")]
+    pub key_data: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(message, optional, tag = "2")]
+    pub fingerprint: ::core::option::Option<AppStateSyncKeyFingerprint>,
+    #[prost(int64, optional, tag = "3")]
+    pub timestamp: ::core::option::Option<i64>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AppStateSyncKeyFingerprint {
+    #[prost(uint32, optional, tag = "1")]
+    pub raw_id: ::core::option::Option<u32>,
+    #[prost(uint32, optional, tag = "2")]
+    pub current_index: ::core::option::Option<u32>,
+    #[prost(uint32, repeated, tag = "3")]
+    pub device_indexes: ::prost::alloc::vec::Vec<u32>,
+}
@@ -0,0 +1,540 @@
+// This file is @generated by prost-build.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ClientPayload {
+    #[prost(uint64, optional, tag = "1")]
+    pub username: ::core::option::Option<u64>,
+    #[prost(bool, optional, tag = "3")]
+    pub passive: ::core::option::Option<bool>,
+    #[prost(message, optional, tag = "5")]
+    pub user_agent: ::core::option::Option<client_payload::UserAgent>,
+    #[prost(message, optional, tag = "6")]
+    pub web_info: ::core::option::Option<client_payload::WebInfo>,
+    #[prost(string, optional, tag = "7")]
+    pub push_name: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(sfixed32, optional, tag = "9")]
+    pub session_id: ::core::option::Option<i32>,
+    #[prost(bool, optional, tag = "10")]
+    pub short_connect: ::core::option::Option<bool>,
+    #[prost(enumeration = "client_payload::ConnectType", optional, tag = "12")]
+    pub connect_type: ::core::option::Option<i32>,
+    #[prost(enumeration = "client_payload::ConnectReason", optional, tag = "13")]
+    pub connect_reason: ::core::option::Option<i32>,
+    #[prost(int32, repeated, packed = "false", tag = "14")]
+    pub shards: ::prost::alloc::vec::Vec<i32>,
+    #[prost(uint32, optional, tag = "16")]
+    pub connect_attempt_count: ::core::option::Option<u32>,
+    #[prost(uint32, optional, tag = "18")]
+    pub device: ::core::option::Option<u32>,
+    #[prost(message, optional, tag = "19")]
+    pub device_pairing_data: ::core::option::Option<client_payload::DevicePairingRegistrationData>,
+    #[prost(enumeration = "client_payload::Product", optional, tag = "20")]
+    pub product: ::core::option::Option<i32>,
+    #[prost(bytes = "vec", optional, tag = "21")]
+    pub fb_cat: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "22")]
+    pub fb_user_agent: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(bool, optional, tag = "23")]
+    pub oc: ::core::option::Option<bool>,
+    #[prost(int32, optional, tag = "24")]
+    pub lc: ::core::option::Option<i32>,
+    #[prost(uint64, optional, tag = "31")]
+    pub fb_app_id: ::core::option::Option<u64>,
+    #[prost(bytes = "vec", optional, tag = "32")]
+    pub fb_device_id: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    #[prost(bool, optional, tag = "33")]
+    pub pull: ::core::option::Option<bool>,
+    #[prost(bytes = "vec", optional, tag = "34")]
+    pub padding_bytes: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+}
+/// Nested message and enum types in `ClientPayload`.
+pub mod client_payload {
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct DevicePairingRegistrationData {
+        #[prost(bytes = "vec", optional, tag = "1")]
+        pub e_regid: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+        #[prost(bytes = "vec", optional, tag = "2")]
+        pub e_keytype: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+        #[prost(bytes = "vec", optional, tag = "3")]
+        pub e_ident: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+        #[prost(bytes = "vec", optional, tag = "4")]
+        pub e_skey_id: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+        #[prost(bytes = "vec", optional, tag = "5")]
+        pub e_skey_val: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+        #[prost(bytes = "vec", optional, tag = "6")]
+        pub e_skey_sig: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+        #[prost(bytes = "vec", optional, tag = "7")]
+        pub build_hash: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+        #[prost(bytes = "vec", optional, tag = "8")]
+        pub device_props: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    }
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct WebInfo {
+        #[prost(string, optional, tag = "1")]
+        pub ref_token: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(string, optional, tag = "2")]
+        pub version: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(enumeration = "web_info::WebSubPlatform", optional, tag = "4")]
+        pub web_sub_platform: ::core::option::Option<i32>,
+    }
+    /// Nested message and enum types in `WebInfo`.
+    pub mod web_info {
+        #[derive(
+            Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration,
+        )]
+        #[repr(i32)]
+        pub enum WebSubPlatform {
+            WebBrowser = 0,
+            AppStore = 1,
+            WinStore = 2,
+            Darwin = 3,
+            Win32 = 4,
+        }
+        impl WebSubPlatform {
+            /// String value of the enum field names used in the ProtoBuf definition.
+            ///
+            /// The values are not transformed in any way and thus are considered stable
+            /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+            pub fn as_str_name(&self) -> &'static str {
+                match self {
+                    Self::WebBrowser => "WEB_BROWSER",
+                    Self::AppStore => "APP_STORE",
+                    Self::WinStore => "WIN_STORE",
+                    Self::Darwin => "DARWIN",
+                    Self::Win32 => "WIN32",
+                }
+            }
+            /// Creates an enum from field names used in the ProtoBuf definition.
+            pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+                match value {
+                    "WEB_BROWSER" => Some(Self::WebBrowser),
+                    "APP_STORE" => Some(Self::AppStore),
+                    "WIN_STORE" => Some(Self::WinStore),
+                    "DARWIN" => Some(Self::Darwin),
+                    "WIN32" => Some(Self::Win32),
+                    _ => None,
+                }
+            }
+        }
+    }
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct UserAgent {
+        #[prost(enumeration = "user_agent::Platform", optional, tag = "1")]
+        pub platform: ::core::option::Option<i32>,
+        #[prost(message, optional, tag = "2")]
+        pub app_version: ::core::option::Option<user_agent::AppVersion>,
+        #[prost(string, optional, tag = "3")]
+        pub mcc: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(string, optional, tag = "4")]
+        pub mnc: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(string, optional, tag = "5")]
+        pub os_version: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(string, optional, tag = "6")]
+        pub manufacturer: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(string, optional, tag = "7")]
+        pub device: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(string, optional, tag = "8")]
+        pub os_build_number: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(string, optional, tag = "9")]
+        pub phone_id: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(enumeration = "user_agent::ReleaseChannel", optional, tag = "10")]
+        pub release_channel: ::core::option::Option<i32>,
+        #[prost(string, optional, tag = "11")]
+        pub locale_language_iso6391: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(string, optional, tag = "12")]
+        pub locale_country_iso31661_alpha2: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(string, optional, tag = "13")]
+        pub device_board: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(string, optional, tag = "14")]
+        pub device_exp_id: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(enumeration = "user_agent::DeviceType", optional, tag = "15")]
+        pub device_type: ::core::option::Option<i32>,
+        #[prost(string, optional, tag = "16")]
+        pub device_model_type: ::core::option::Option<::prost::alloc::string::String>,
+    }
+    /// Nested message and enum types in `UserAgent`.
+    pub mod user_agent {
+        #[derive(Clone, Copy, PartialEq, ::prost::Message)]
+        pub struct AppVersion {
+            #[prost(uint32, optional, tag = "1")]
+            pub primary: ::core::option::Option<u32>,
+            #[prost(uint32, optional, tag = "2")]
+            pub secondary: ::core::option::Option<u32>,
+            #[prost(uint32, optional, tag = "3")]
+            pub tertiary: ::core::option::Option<u32>,
+            #[prost(uint32, optional, tag = "4")]
+            pub quaternary: ::core::option::Option<u32>,
+            #[prost(uint32, optional, tag = "5")]
+            pub quinary: ::core::option::Option<u32>,
+        }
+        #[derive(
+            Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration,
+        )]
+        #[repr(i32)]
+        pub enum DeviceType {
+            Phone = 0,
+            Tablet = 1,
+            Desktop = 2,
+            Wearable = 3,
+            Vr = 4,
+        }
+        impl DeviceType {
+            /// String value of the enum field names used in the ProtoBuf definition.
+            ///
+            /// The values are not transformed in any way and thus are considered stable
+            /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+            pub fn as_str_name(&self) -> &'static str {
+                match self {
+                    Self::Phone => "PHONE",
+                    Self::Tablet => "TABLET",
+                    Self::Desktop => "DESKTOP",
+                    Self::Wearable => "WEARABLE",
+                    Self::Vr => "VR",
+                }
+            }
+            /// Creates an enum from field names used in the ProtoBuf definition.
+            pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+                match value {
+                    "PHONE" => Some(Self::Phone),
+                    "TABLET" => Some(Self::Tablet),
+                    "DESKTOP" => Some(Self::Desktop),
+                    "WEARABLE" => Some(Self::Wearable),
+                    "VR" => Some(Self::Vr),
+                    _ => None,
+                }
+            }
+        }
+        #[derive(
+            Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration,
+        )]
+        #[repr(i32)]
+        pub enum ReleaseChannel {
+            Release = 0,
+            Beta = 1,
+            Alpha = 2,
+            Debug = 3,
+        }
+        impl ReleaseChannel {
+            /// String value of the enum field names used in the ProtoBuf definition.
+            ///
+            /// The values are not transformed in any way and thus are considered stable
+            /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+            pub fn as_str_name(&self) -> &'static str {
+                match self {
+                    Self::Release => "RELEASE",
+                    Self::Beta => "BETA",
+                    Self::Alpha => "ALPHA",
+                    Self::Debug => "DEBUG",
+                }
+            }
+            /// Creates an enum from field names used in the ProtoBuf definition.
+            pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+                match value {
+                    "RELEASE" => Some(Self::Release),
+                    "BETA" => Some(Self::Beta),
+                    "ALPHA" => Some(Self::Alpha),
+                    "DEBUG" => Some(Self::Debug),
+                    _ => None,
+                }
+            }
+        }
+        #[derive(
+            Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration,
+        )]
+        #[repr(i32)]
+        pub enum Platform {
+            Android = 0,
+            Ios = 1,
+            WindowsPhone = 2,
+            Blackberry = 3,
+            Blackberryx = 4,
+            S40 = 5,
+            S60 = 6,
+            PythonClient = 7,
+            Tizen = 8,
+            Enterprise = 9,
+            SmbAndroid = 10,
+            Kaios = 11,
+            SmbIos = 12,
+            Windows = 13,
+            Web = 14,
+            Portal = 15,
+            GreenAndroid = 16,
+            GreenIphone = 17,
+            BlueAndroid = 18,
+            BlueIphone = 19,
+            FbliteAndroid = 20,
+            MliteAndroid = 21,
+            IgliteAndroid = 22,
+            Page = 23,
+            Macos = 24,
+            OculusMsg = 25,
+            OculusCall = 26,
+            Milan = 27,
+            Capi = 28,
+            Wearos = 29,
+            Ardevice = 30,
+            Vrdevice = 31,
+            BlueWeb = 32,
+            Ipad = 33,
+        }
+        impl Platform {
+            /// String value of the enum field names used in the ProtoBuf definition.
+            ///
+            /// The values are not transformed in any way and thus are considered stable
+            /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+            pub fn as_str_name(&self) -> &'static str {
+                match self {
+                    Self::Android => "ANDROID",
+                    Self::Ios => "IOS",
+                    Self::WindowsPhone => "WINDOWS_PHONE",
+                    Self::Blackberry => "BLACKBERRY",
+                    Self::Blackberryx => "BLACKBERRYX",
+                    Self::S40 => "S40",
+                    Self::S60 => "S60",
+                    Self::PythonClient => "PYTHON_CLIENT",
+                    Self::Tizen => "TIZEN",
+                    Self::Enterprise => "ENTERPRISE",
+                    Self::SmbAndroid => "SMB_ANDROID",
+                    Self::Kaios => "KAIOS",
+                    Self::SmbIos => "SMB_IOS",
+                    Self::Windows => "WINDOWS",
+                    Self::Web => "WEB",
+                    Self::Portal => "PORTAL",
+                    Self::GreenAndroid => "GREEN_ANDROID",
+                    Self::GreenIphone => "GREEN_IPHONE",
+                    Self::BlueAndroid => "BLUE_ANDROID",
+                    Self::BlueIphone => "BLUE_IPHONE",
+                    Self::FbliteAndroid => "FBLITE_ANDROID",
+                    Self::MliteAndroid => "MLITE_ANDROID",
+                    Self::IgliteAndroid => "IGLITE_ANDROID",
+                    Self::Page => "PAGE",
+                    Self::Macos => "MACOS",
+                    Self::OculusMsg => "OCULUS_MSG",
+                    Self::OculusCall => "OCULUS_CALL",
+                    Self::Milan => "MILAN",
+                    Self::Capi => "CAPI",
+                    Self::Wearos => "WEAROS",
+                    Self::Ardevice => "ARDEVICE",
+                    Self::Vrdevice => "VRDEVICE",
+                    Self::BlueWeb => "BLUE_WEB",
+                    Self::Ipad => "IPAD",
+                }
+            }
+            /// Creates an enum from field names used in the ProtoBuf definition.
+            pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+                match value {
+                    "ANDROID" => Some(Self::Android),
+                    "IOS" => Some(Self::Ios),
+                    "WINDOWS_PHONE" => Some(Self::WindowsPhone),
+                    "BLACKBERRY" => Some(Self::Blackberry),
+                    "BLACKBERRYX" => Some(Self::Blackberryx),
+                    "S40" => Some(Self::S40),
+                    "S60" => Some(Self::S60),
+                    "PYTHON_CLIENT" => Some(Self::PythonClient),
+                    "TIZEN" => Some(Self::Tizen),
+                    "ENTERPRISE" => Some(Self::Enterprise),
+                    "SMB_ANDROID" => Some(Self::SmbAndroid),
+                    "KAIOS" => Some(Self::Kaios),
+                    "SMB_IOS" => Some(Self::SmbIos),
+                    "WINDOWS" => Some(Self::Windows),
+                    "WEB" => Some(Self::Web),
+                    "PORTAL" => Some(Self::Portal),
+                    "GREEN_ANDROID" => Some(Self::GreenAndroid),
+                    "GREEN_IPHONE" => Some(Self::GreenIphone),
+                    "BLUE_ANDROID" => Some(Self::BlueAndroid),
+                    "BLUE_IPHONE" => Some(Self::BlueIphone),
+                    "FBLITE_ANDROID" => Some(Self::FbliteAndroid),
+                    "MLITE_ANDROID" => Some(Self::MliteAndroid),
+                    "IGLITE_ANDROID" => Some(Self::IgliteAndroid),
+                    "PAGE" => Some(Self::Page),
+                    "MACOS" => Some(Self::Macos),
+                    "OCULUS_MSG" => Some(Self::OculusMsg),
+                    "OCULUS_CALL" => Some(Self::OculusCall),
+                    "MILAN" => Some(Self::Milan),
+                    "CAPI" => Some(Self::Capi),
+                    "WEAROS" => Some(Self::Wearos),
+                    "ARDEVICE" => Some(Self::Ardevice),
+                    "VRDEVICE" => Some(Self::Vrdevice),
+                    "BLUE_WEB" => Some(Self::BlueWeb),
+                    "IPAD" => Some(Self::Ipad),
+                    _ => None,
+                }
+            }
+        }
+    }
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Product {
+        Whatsapp = 0,
+        Messenger = 1,
+        Interop = 2,
+        InteropMsgr = 3,
+    }
+    impl Product {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::Whatsapp => "WHATSAPP",
+                Self::Messenger => "MESSENGER",
+                Self::Interop => "INTEROP",
+                Self::InteropMsgr => "INTEROP_MSGR",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "WHATSAPP" => Some(Self::Whatsapp),
+                "MESSENGER" => Some(Self::Messenger),
+                "INTEROP" => Some(Self::Interop),
+                "INTEROP_MSGR" => Some(Self::InteropMsgr),
+                _ => None,
+            }
+        }
+    }
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum ConnectType {
+        CellularUnknown = 0,
+        WifiUnknown = 1,
+        CellularEdge = 100,
+        CellularIden = 101,
+        CellularUmts = 102,
+        CellularEvdo = 103,
+        CellularGprs = 104,
+        CellularHsdpa = 105,
+        CellularHsupa = 106,
+        CellularHspa = 107,
+        CellularCdma = 108,
+        Cellular1Xrtt = 109,
+        CellularEhrpd = 110,
+        CellularLte = 111,
+        CellularHspap = 112,
+    }
+    impl ConnectType {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::CellularUnknown => "CELLULAR_UNKNOWN",
+                Self::WifiUnknown => "WIFI_UNKNOWN",
+                Self::CellularEdge => "CELLULAR_EDGE",
+                Self::CellularIden => "CELLULAR_IDEN",
+                Self::CellularUmts => "CELLULAR_UMTS",
+                Self::CellularEvdo => "CELLULAR_EVDO",
+                Self::CellularGprs => "CELLULAR_GPRS",
+                Self::CellularHsdpa => "CELLULAR_HSDPA",
+                Self::CellularHsupa => "CELLULAR_HSUPA",
+                Self::CellularHspa => "CELLULAR_HSPA",
+                Self::CellularCdma => "CELLULAR_CDMA",
+                Self::Cellular1Xrtt => "CELLULAR_1XRTT",
+                Self::CellularEhrpd => "CELLULAR_EHRPD",
+                Self::CellularLte => "CELLULAR_LTE",
+                Self::CellularHspap => "CELLULAR_HSPAP",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "CELLULAR_UNKNOWN" => Some(Self::CellularUnknown),
+                "WIFI_UNKNOWN" => Some(Self::WifiUnknown),
+                "CELLULAR_EDGE" => Some(Self::CellularEdge),
+                "CELLULAR_IDEN" => Some(Self::CellularIden),
+                "CELLULAR_UMTS" => Some(Self::CellularUmts),
+                "CELLULAR_EVDO" => Some(Self::CellularEvdo),
+                "CELLULAR_GPRS" => Some(Self::CellularGprs),
+                "CELLULAR_HSDPA" => Some(Self::CellularHsdpa),
+                "CELLULAR_HSUPA" => Some(Self::CellularHsupa),
+                "CELLULAR_HSPA" => Some(Self::CellularHspa),
+                "CELLULAR_CDMA" => Some(Self::CellularCdma),
+                "CELLULAR_1XRTT" => Some(Self::Cellular1Xrtt),
+                "CELLULAR_EHRPD" => Some(Self::CellularEhrpd),
+                "CELLULAR_LTE" => Some(Self::CellularLte),
+                "CELLULAR_HSPAP" => Some(Self::CellularHspap),
+                _ => None,
+            }
+        }
+    }
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum ConnectReason {
+        Push = 0,
+        UserActivated = 1,
+        Scheduled = 2,
+        ErrorReconnect = 3,
+        NetworkSwitch = 4,
+        PingReconnect = 5,
+        Unknown = 6,
+    }
+    impl ConnectReason {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::Push => "PUSH",
+                Self::UserActivated => "USER_ACTIVATED",
+                Self::Scheduled => "SCHEDULED",
+                Self::ErrorReconnect => "ERROR_RECONNECT",
+                Self::NetworkSwitch => "NETWORK_SWITCH",
+                Self::PingReconnect => "PING_RECONNECT",
+                Self::Unknown => "UNKNOWN",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "PUSH" => Some(Self::Push),
+                "USER_ACTIVATED" => Some(Self::UserActivated),
+                "SCHEDULED" => Some(Self::Scheduled),
+                "ERROR_RECONNECT" => Some(Self::ErrorReconnect),
+                "NETWORK_SWITCH" => Some(Self::NetworkSwitch),
+                "PING_RECONNECT" => Some(Self::PingReconnect),
+                "UNKNOWN" => Some(Self::Unknown),
+                _ => None,
+            }
+        }
+    }
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HandshakeMessage {
+    #[prost(message, optional, tag = "2")]
+    pub client_hello: ::core::option::Option<handshake_message::ClientHello>,
+    #[prost(message, optional, tag = "3")]
+    pub server_hello: ::core::option::Option<handshake_message::ServerHello>,
+    #[prost(message, optional, tag = "4")]
+    pub client_finish: ::core::option::Option<handshake_message::ClientFinish>,
+}
+/// Nested message and enum types in `HandshakeMessage`.
+pub mod handshake_message {
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ClientFinish {
+        #[prost(bytes = "vec", optional, tag = "1")]
+        pub r#static: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+        #[prost(bytes = "vec", optional, tag = "2")]
+        pub payload: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    }
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ServerHello {
+        #[prost(bytes = "vec", optional, tag = "1")]
+        pub ephemeral: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+        #[prost(bytes = "vec", optional, tag = "2")]
+        pub r#static: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+        #[prost(bytes = "vec", optional, tag = "3")]
+        pub payload: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    }
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ClientHello {
+        #[prost(bytes = "vec", optional, tag = "1")]
+        pub ephemeral: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+        #[prost(bytes = "vec", optional, tag = "2")]
+        pub r#static: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+        #[prost(bytes = "vec", optional, tag = "3")]
+        pub payload: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+    }
+}
@@ -0,0 +1,104 @@
+//! QR code rendering for pairing codes from [Event::Qr](crate::Event::Qr) /
+//! [Event::QrCode](crate::Event::QrCode). Requires the `qr` feature.
+//!
+//! ```ignore
+//! if let Event::QrCode { code, .. } = evt {
+//!     println!("{}", whatsapp_pkg::qr::render_terminal(&code)?);
+//! }
+//! ```
+
+use crate::{Error, Result};
+use qrcode::render::{svg, unicode::Dense1x2};
+use qrcode::{Color, EcLevel, QrCode};
+
+/// Modules of blank border around the code, as required by the QR spec.
+const QUIET_ZONE: usize = 4;
+
+fn encode(code: &str) -> Result<QrCode> {
+    QrCode::with_error_correction_level(code, EcLevel::L)
+        .map_err(|e| Error::Other(anyhow::anyhow!("qr encode: {}", e)))
+}
+
+/// Render as Unicode half-block characters (two modules per character row), for printing
+/// in a terminal. Colors are inverted for the usual light-on-dark terminal: light modules are
+/// drawn as blocks and dark modules are left blank.
+pub fn render_terminal(code: &str) -> Result<String> {
+    Ok(encode(code)?
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .quiet_zone(true)
+        .build())
+}
+
+/// Render as an SVG document; `module_size` is the side of one module in pixels.
+pub fn render_svg(code: &str, module_size: u32) -> Result<String> {
+    Ok(encode(code)?
+        .render::<svg::Color>()
+        .module_dimensions(module_size, module_size)
+        .quiet_zone(true)
+        .build())
+}
+
+/// Render as a grayscale PNG image; `module_size` is the side of one module in pixels.
+pub fn render_png(code: &str, module_size: u32) -> Result<Vec<u8>> {
+    let qr = encode(code)?;
+    let scale = module_size.max(1) as usize;
+    let modules = qr.width() + 2 * QUIET_ZONE;
+    let side = modules * scale;
+    let colors = qr.to_colors();
+
+    let mut pixels = vec![0xffu8; side * side];
+    for (i, color) in colors.iter().enumerate() {
+        if *color != Color::Dark {
+            continue;
+        }
+        let (x, y) = (i % qr.width() + QUIET_ZONE, i / qr.width() + QUIET_ZONE);
+        for row in y * scale..(y + 1) * scale {
+            pixels[row * side + x * scale..row * side + (x + 1) * scale].fill(0);
+        }
+    }
+
+    let mut out = Vec::new();
+    let png_error = |e: png::EncodingError| Error::Other(anyhow::anyhow!("qr png: {}", e));
+    let mut encoder = png::Encoder::new(&mut out, side as u32, side as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(png_error)?;
+    writer.write_image_data(&pixels).map_err(png_error)?;
+    writer.finish().map_err(png_error)?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: &str = "2@abc,bm9pc2U=,aWRlbnRpdHk=,YWR2";
+
+    #[test]
+    fn terminal_uses_half_blocks() {
+        let art = render_terminal(CODE).unwrap();
+        let width = encode(CODE).unwrap().width() + 2 * QUIET_ZONE;
+        let lines: Vec<&str> = art.lines().collect();
+        assert_eq!(lines.len(), width.div_ceil(2));
+        assert!(lines.iter().all(|l| l.chars().count() == width));
+        assert!(art.chars().all(|c| " ▀▄█\n".contains(c)));
+    }
+
+    #[test]
+    fn svg_is_a_document() {
+        let svg = render_svg(CODE, 4).unwrap();
+        assert!(svg.contains("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+    }
+
+    #[test]
+    fn png_has_expected_size() {
+        let bytes = render_png(CODE, 3).unwrap();
+        let decoder = png::Decoder::new(bytes.as_slice());
+        let reader = decoder.read_info().unwrap();
+        let side = (encode(CODE).unwrap().width() + 2 * QUIET_ZONE) as u32 * 3;
+        assert_eq!((reader.info().width, reader.info().height), (side, side));
+    }
+}
@@ -0,0 +1,276 @@
+//! Signal ciphertext messages as carried in `<enc>` nodes: a version byte, the protobuf and,
+//! for [SignalMessage], a truncated HMAC over both parties' identity keys.
+
+use super::{parse_public_key, serialize_public_key};
+use crate::error::SignalError;
+use crate::proto::signal_wire;
+use crate::Result;
+use hmac::{Hmac, Mac};
+use prost::Message;
+use sha2::Sha256;
+
+/// Signal protocol version of the messages we produce and accept.
+pub const CIPHERTEXT_VERSION: u8 = 3;
+
+/// Length of the truncated HMAC-SHA256 that ends a [SignalMessage].
+const MAC_LEN: usize = 8;
+
+/// Version byte: current version in the high nibble, oldest compatible version in the low one.
+pub(super) const VERSION_BYTE: u8 = CIPHERTEXT_VERSION << 4 | CIPHERTEXT_VERSION;
+
+/// A message of an established session (`<enc type="msg">`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignalMessage {
+    pub ratchet_key: [u8; 32],
+    pub counter: u32,
+    pub previous_counter: u32,
+    pub ciphertext: Vec<u8>,
+    serialized: Vec<u8>,
+}
+
+impl SignalMessage {
+    pub(super) fn new(
+        mac_key: &[u8; 32],
+        ratchet_key: [u8; 32],
+        counter: u32,
+        previous_counter: u32,
+        ciphertext: Vec<u8>,
+        sender_identity: &[u8; 32],
+        receiver_identity: &[u8; 32],
+    ) -> Self {
+        let mut serialized = vec![VERSION_BYTE];
+        signal_wire::SignalMessage {
+            ratchet_key: Some(serialize_public_key(&ratchet_key)),
+            counter: Some(counter),
+            previous_counter: Some(previous_counter),
+            ciphertext: Some(ciphertext.clone()),
+        }
+        .encode(&mut serialized)
+        .expect("Vec has unlimited capacity");
+        let mac = compute_mac(mac_key, sender_identity, receiver_identity, &serialized);
+        serialized.extend_from_slice(&mac);
+        Self {
+            ratchet_key,
+            counter,
+            previous_counter,
+            ciphertext,
+            serialized,
+        }
+    }
+
+    pub fn deserialize(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() <= 1 + MAC_LEN {
+            return Err(invalid("SignalMessage too short"));
+        }
+        check_version(bytes[0])?;
+        let proto = signal_wire::SignalMessage::decode(&bytes[1..bytes.len() - MAC_LEN])
+            .map_err(|e| invalid(&format!("SignalMessage: {}", e)))?;
+        let (Some(ratchet_key), Some(counter), Some(ciphertext)) =
+            (proto.ratchet_key, proto.counter, proto.ciphertext)
+        else {
+            return Err(invalid("incomplete SignalMessage"));
+        };
+        Ok(Self {
+            ratchet_key: parse_public_key(&ratchet_key)?,
+            counter,
+            previous_counter: proto.previous_counter.unwrap_or(0),
+            ciphertext,
+            serialized: bytes.to_vec(),
+        })
+    }
+
+    pub fn serialize(&self) -> &[u8] {
+        &self.serialized
+    }
+
+    pub(super) fn verify_mac(
+        &self,
+        mac_key: &[u8; 32],
+        sender_identity: &[u8; 32],
+        receiver_identity: &[u8; 32],
+    ) -> Result<()> {
+        let (body, mac) = self.serialized.split_at(self.serialized.len() - MAC_LEN);
+        let expected = compute_mac(mac_key, sender_identity, receiver_identity, body);
+        // Constant-time comparison of the truncated tag.
+        if expected
+            .iter()
+            .zip(mac)
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            != 0
+        {
+            return Err(SignalError::InvalidMac.into());
+        }
+        Ok(())
+    }
+}
+
+/// First message(s) of a session started from a prekey bundle (`<enc type="pkmsg">`): the
+/// X3DH parameters the recipient needs to build the session, plus a [SignalMessage].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PreKeySignalMessage {
+    pub registration_id: u32,
+    pub prekey_id: Option<u32>,
+    pub signed_prekey_id: u32,
+    pub base_key: [u8; 32],
+    pub identity_key: [u8; 32],
+    pub message: SignalMessage,
+    serialized: Vec<u8>,
+}
+
+impl PreKeySignalMessage {
+    pub(super) fn new(
+        registration_id: u32,
+        prekey_id: Option<u32>,
+        signed_prekey_id: u32,
+        base_key: [u8; 32],
+        identity_key: [u8; 32],
+        message: SignalMessage,
+    ) -> Self {
+        let mut serialized = vec![VERSION_BYTE];
+        signal_wire::PreKeySignalMessage {
+            registration_id: Some(registration_id),
+            pre_key_id: prekey_id,
+            signed_pre_key_id: Some(signed_prekey_id),
+            base_key: Some(serialize_public_key(&base_key)),
+            identity_key: Some(serialize_public_key(&identity_key)),
+            message: Some(message.serialize().to_vec()),
+        }
+        .encode(&mut serialized)
+        .expect("Vec has unlimited capacity");
+        Self {
+            registration_id,
+            prekey_id,
+            signed_prekey_id,
+            base_key,
+            identity_key,
+            message,
+            serialized,
+        }
+    }
+
+    pub fn deserialize(bytes: &[u8]) -> Result<Self> {
+        let Some((&version, body)) = bytes.split_first() else {
+            return Err(invalid("empty PreKeySignalMessage"));
+        };
+        check_version(version)?;
+        let proto = signal_wire::PreKeySignalMessage::decode(body)
+            .map_err(|e| invalid(&format!("PreKeySignalMessage: {}", e)))?;
+        let (Some(signed_prekey_id), Some(base_key), Some(identity_key), Some(message)) = (
+            proto.signed_pre_key_id,
+            proto.base_key,
+            proto.identity_key,
+            proto.message,
+        ) else {
+            return Err(invalid("incomplete PreKeySignalMessage"));
+        };
+        Ok(Self {
+            registration_id: proto.registration_id.unwrap_or(0),
+            prekey_id: proto.pre_key_id,
+            signed_prekey_id,
+            base_key: parse_public_key(&base_key)?,
+            identity_key: parse_public_key(&identity_key)?,
+            message: SignalMessage::deserialize(&message)?,
+            serialized: bytes.to_vec(),
+        })
+    }
+
+    pub fn serialize(&self) -> &[u8] {
+        &self.serialized
+    }
+}
+
+/// Output of encrypting for a session.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CiphertextMessage {
+    /// Sent until the recipient has answered, so it can still set up the session.
+    PreKey(PreKeySignalMessage),
+    Whisper(SignalMessage),
+}
+
+impl CiphertextMessage {
+    /// `type` attribute of the `<enc>` node carrying this message.
+    pub fn enc_type(&self) -> &'static str {
+        match self {
+            Self::PreKey(_) => "pkmsg",
+            Self::Whisper(_) => "msg",
+        }
+    }
+
+    pub fn serialize(&self) -> &[u8] {
+        match self {
+            Self::PreKey(m) => m.serialize(),
+            Self::Whisper(m) => m.serialize(),
+        }
+    }
+}
+
+fn compute_mac(
+    mac_key: &[u8; 32],
+    sender_identity: &[u8; 32],
+    receiver_identity: &[u8; 32],
+    serialized: &[u8],
+) -> [u8; MAC_LEN] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(mac_key).expect("HMAC takes any key length");
+    mac.update(&serialize_public_key(sender_identity));
+    mac.update(&serialize_public_key(receiver_identity));
+    mac.update(serialized);
+    mac.finalize().into_bytes()[..MAC_LEN]
+        .try_into()
+        .expect("MAC_LEN bytes")
+}
+
+pub(super) fn check_version(byte: u8) -> Result<()> {
+    match byte >> 4 {
+        CIPHERTEXT_VERSION => Ok(()),
+        version => Err(SignalError::UnsupportedVersion(version).into()),
+    }
+}
+
+pub(super) fn invalid(reason: &str) -> crate::Error {
+    SignalError::InvalidMessage(reason.to_string()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    fn message() -> SignalMessage {
+        SignalMessage::new(&[1; 32], [2; 32], 5, 3, vec![4; 16], &[6; 32], &[7; 32])
+    }
+
+    #[test]
+    fn signal_message_roundtrip_and_mac() {
+        let sent = message();
+        assert_eq!(sent.serialize()[0], 0x33);
+        let received = SignalMessage::deserialize(sent.serialize()).unwrap();
+        assert_eq!(received, sent);
+        received.verify_mac(&[1; 32], &[6; 32], &[7; 32]).unwrap();
+        assert!(matches!(
+            received.verify_mac(&[1; 32], &[7; 32], &[6; 32]),
+            Err(Error::Signal(SignalError::InvalidMac))
+        ));
+    }
+
+    #[test]
+    fn prekey_message_roundtrip() {
+        let sent = PreKeySignalMessage::new(42, Some(9), 1, [8; 32], [6; 32], message());
+        let received = PreKeySignalMessage::deserialize(sent.serialize()).unwrap();
+        assert_eq!(received, sent);
+        assert_eq!(CiphertextMessage::PreKey(received).enc_type(), "pkmsg");
+        assert_eq!(CiphertextMessage::Whisper(message()).enc_type(), "msg");
+    }
+
+    #[test]
+    fn rejects_other_versions_and_garbage() {
+        let mut bytes = message().serialize().to_vec();
+        bytes[0] = 0x22;
+        assert!(matches!(
+            SignalMessage::deserialize(&bytes),
+            Err(Error::Signal(SignalError::UnsupportedVersion(2)))
+        ));
+        assert!(SignalMessage::deserialize(&[0x33; 4]).is_err());
+        assert!(PreKeySignalMessage::deserialize(&[]).is_err());
+        assert!(PreKeySignalMessage::deserialize(&[0x33, 0xff]).is_err());
+    }
+}
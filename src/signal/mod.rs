@@ -0,0 +1,128 @@
+//! Signal protocol end-to-end encryption: X3DH session setup from prekey bundles and the Double
+//! Ratchet, producing and consuming the `pkmsg` / `msg` ciphertexts of `<enc>` nodes.
+//!
+//! Group messages (`skmsg`) use sender keys instead, see [SenderKeyRecord].
+//!
+//! Everything here works on in-memory records; persisting them is up to the caller.
+
+mod group;
+mod message;
+mod prekey;
+mod ratchet;
+mod session;
+
+pub use group::{SenderKeyDistributionMessage, SenderKeyMessage, SenderKeyRecord};
+pub use message::{CiphertextMessage, PreKeySignalMessage, SignalMessage, CIPHERTEXT_VERSION};
+pub use prekey::{generate_registration_id, PreKey, SignedPreKey, MAX_PREKEY_ID};
+pub use session::{PreKeyBundle, SessionRecord};
+
+use crate::crypto::DJB_KEY_TYPE;
+use crate::error::SignalError;
+use crate::types::{Jid, DEFAULT_USER_SERVER, HIDDEN_USER_SERVER};
+use crate::Result;
+use std::fmt;
+
+/// A device as Signal addresses it: the user (suffixed with the agent for non-phone-number
+/// accounts) and the device ID.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ProtocolAddress {
+    pub name: String,
+    pub device_id: u32,
+}
+
+impl ProtocolAddress {
+    pub fn new(name: impl Into<String>, device_id: u32) -> Self {
+        Self {
+            name: name.into(),
+            device_id,
+        }
+    }
+}
+
+impl From<&Jid> for ProtocolAddress {
+    fn from(jid: &Jid) -> Self {
+        let agent = match jid.server.as_str() {
+            DEFAULT_USER_SERVER => 0,
+            HIDDEN_USER_SERVER => 1,
+            _ => jid.raw_agent,
+        };
+        let name = match agent {
+            0 => jid.user.clone(),
+            agent => format!("{}_{}", jid.user, agent),
+        };
+        Self::new(name, jid.device.into())
+    }
+}
+
+impl fmt::Display for ProtocolAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.name, self.device_id)
+    }
+}
+
+/// Key of a [SenderKeyRecord]: the group and the member sending with it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SenderKeyName {
+    pub group_id: String,
+    pub sender: ProtocolAddress,
+}
+
+impl SenderKeyName {
+    pub fn new(group: &Jid, sender: &Jid) -> Self {
+        Self {
+            group_id: group.to_string(),
+            sender: sender.into(),
+        }
+    }
+}
+
+/// Public key as carried in Signal messages: the key type byte and the 32-byte key.
+pub fn serialize_public_key(key: &[u8; 32]) -> Vec<u8> {
+    let mut serialized = Vec::with_capacity(33);
+    serialized.push(DJB_KEY_TYPE);
+    serialized.extend_from_slice(key);
+    serialized
+}
+
+/// Inverse of [serialize_public_key].
+pub fn parse_public_key(serialized: &[u8]) -> Result<[u8; 32]> {
+    match serialized.split_first() {
+        Some((&DJB_KEY_TYPE, key)) => key
+            .try_into()
+            .map_err(|_| SignalError::InvalidKey(format!("{}-byte public key", key.len())).into()),
+        _ => Err(SignalError::InvalidKey("missing key type 0x05".into()).into()),
+    }
+}
+
+/// Fixed-size field of a stored record.
+fn stored_bytes<const N: usize>(field: Option<&[u8]>) -> Result<[u8; N]> {
+    field.and_then(|b| b.try_into().ok()).ok_or_else(|| {
+        SignalError::InvalidMessage(format!("stored record: bad {}-byte field", N)).into()
+    })
+}
+
+fn corrupt_record(record: &str, what: &str) -> crate::Error {
+    SignalError::InvalidMessage(format!("{} record: bad {}", record, what)).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protocol_address_from_jid() {
+        let address = |jid: &str| ProtocolAddress::from(&jid.parse::<Jid>().unwrap()).to_string();
+        assert_eq!(address("5511999990000:3@s.whatsapp.net"), "5511999990000:3");
+        assert_eq!(address("5511999990000@s.whatsapp.net"), "5511999990000:0");
+        assert_eq!(address("98765:2@lid"), "98765_1:2");
+    }
+
+    #[test]
+    fn public_keys_carry_type_byte() {
+        let serialized = serialize_public_key(&[7; 32]);
+        assert_eq!(serialized[0], DJB_KEY_TYPE);
+        assert_eq!(parse_public_key(&serialized).unwrap(), [7; 32]);
+        assert!(parse_public_key(&serialized[1..]).is_err());
+        assert!(parse_public_key(&serialized[..32]).is_err());
+    }
+}
@@ -0,0 +1,81 @@
+//! Our prekeys: the one-time and signed prekeys remote devices use to start sessions with us.
+
+use super::serialize_public_key;
+use crate::crypto::KeyPair;
+use rand::Rng;
+
+/// Prekey IDs go on the wire as 3 bytes.
+pub const MAX_PREKEY_ID: u32 = 0xff_ffff;
+
+/// Random Signal registration ID (14 bits, never 0).
+pub fn generate_registration_id() -> u32 {
+    rand::thread_rng().gen_range(1..=0x3fff)
+}
+
+/// One-time prekey: consumed by the first remote device that starts a session with it.
+#[derive(Clone, Debug)]
+pub struct PreKey {
+    pub id: u32,
+    pub key_pair: KeyPair,
+}
+
+impl PreKey {
+    pub fn generate(id: u32) -> Self {
+        Self {
+            id,
+            key_pair: KeyPair::generate(),
+        }
+    }
+}
+
+/// Prekey signed with our identity key, published alongside the one-time prekeys and used by
+/// every session started until it is rotated.
+#[derive(Clone, Debug)]
+pub struct SignedPreKey {
+    pub id: u32,
+    pub key_pair: KeyPair,
+    pub signature: [u8; 64],
+}
+
+impl SignedPreKey {
+    /// New signed prekey: XEdDSA signature of the serialized public key by `identity`.
+    pub fn generate(id: u32, identity: &KeyPair) -> Self {
+        let key_pair = KeyPair::generate();
+        let signature = identity.sign(&serialize_public_key(&key_pair.public));
+        Self {
+            id,
+            key_pair,
+            signature,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto;
+
+    #[test]
+    fn signed_prekey_is_signed_by_identity() {
+        let identity = KeyPair::generate();
+        let prekey = SignedPreKey::generate(1, &identity);
+        let serialized = serialize_public_key(&prekey.key_pair.public);
+        assert!(crypto::verify(
+            &identity.public,
+            &serialized,
+            &prekey.signature
+        ));
+        assert!(!crypto::verify(
+            &KeyPair::generate().public,
+            &serialized,
+            &prekey.signature
+        ));
+    }
+
+    #[test]
+    fn registration_ids_fit_in_14_bits() {
+        for _ in 0..100 {
+            assert!((1..=0x3fff).contains(&generate_registration_id()));
+        }
+    }
+}
@@ -0,0 +1,796 @@
+//! Sessions: X3DH setup from a prekey bundle (initiator) or a prekey message (responder),
+//! then Double Ratchet encryption and decryption.
+
+use super::message::{CiphertextMessage, PreKeySignalMessage, SignalMessage, CIPHERTEXT_VERSION};
+use super::ratchet::{derive_secrets, ChainKey, MessageKeys, RootKey};
+use super::{corrupt_record, parse_public_key, serialize_public_key, stored_bytes};
+use crate::crypto::{self, KeyPair};
+use crate::error::SignalError;
+use crate::proto::signal_storage::session_structure::{
+    chain, Chain, PendingPreKey as PendingProto,
+};
+use crate::proto::signal_storage::{RecordStructure, SessionStructure};
+use crate::Result;
+use prost::Message;
+
+/// Receiver chains kept per session (older ones are dropped).
+const MAX_RECEIVER_CHAINS: usize = 5;
+/// Skipped message keys kept per receiver chain, for out-of-order messages.
+const MAX_MESSAGE_KEYS: usize = 2000;
+/// How far ahead of the chain a message counter may be.
+const MAX_FUTURE_MESSAGES: u32 = 2000;
+/// Previous sessions kept per record, for messages still in flight after a new session.
+const MAX_ARCHIVED_STATES: usize = 40;
+
+/// Published keys of a remote device, fetched from the server to start a session.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PreKeyBundle {
+    pub registration_id: u32,
+    pub device_id: u32,
+    /// One-time prekey (id and public key), if the server had one left.
+    pub prekey: Option<(u32, [u8; 32])>,
+    pub signed_prekey_id: u32,
+    pub signed_prekey: [u8; 32],
+    /// XEdDSA signature of the serialized signed prekey by `identity_key`.
+    pub signed_prekey_signature: [u8; 64],
+    pub identity_key: [u8; 32],
+}
+
+/// All sessions with one remote device: the current one and a few archived ones.
+#[derive(Clone, Debug, Default)]
+pub struct SessionRecord {
+    current: Option<SessionState>,
+    /// Newest first.
+    previous: Vec<SessionState>,
+}
+
+impl SessionRecord {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn deserialize(bytes: &[u8]) -> Result<Self> {
+        let record = RecordStructure::decode(bytes)
+            .map_err(|e| SignalError::InvalidMessage(format!("session record: {}", e)))?;
+        Ok(Self {
+            current: record
+                .current_session
+                .map(SessionState::from_proto)
+                .transpose()?,
+            previous: record
+                .previous_sessions
+                .into_iter()
+                .map(SessionState::from_proto)
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        RecordStructure {
+            current_session: self.current.as_ref().map(SessionState::to_proto),
+            previous_sessions: self.previous.iter().map(SessionState::to_proto).collect(),
+        }
+        .encode_to_vec()
+    }
+
+    /// Whether there is a session to encrypt with.
+    pub fn has_session(&self) -> bool {
+        self.current.is_some()
+    }
+
+    pub fn remote_registration_id(&self) -> Option<u32> {
+        self.current.as_ref().map(|s| s.remote_registration_id)
+    }
+
+    pub fn remote_identity(&self) -> Option<[u8; 32]> {
+        self.current.as_ref().map(|s| s.remote_identity)
+    }
+
+    /// Whether the session started by a prekey message with this base key already exists.
+    pub fn has_session_with_base_key(&self, base_key: &[u8; 32]) -> bool {
+        self.current
+            .iter()
+            .chain(&self.previous)
+            .any(|s| s.alice_base_key == *base_key)
+    }
+
+    /// Start a new session from a remote device's prekey bundle (X3DH as initiator). Until
+    /// the remote answers, [SessionRecord::encrypt] produces [CiphertextMessage::PreKey].
+    pub fn process_bundle(
+        &mut self,
+        identity: &KeyPair,
+        registration_id: u32,
+        bundle: &PreKeyBundle,
+    ) -> Result<()> {
+        if !crypto::verify(
+            &bundle.identity_key,
+            &serialize_public_key(&bundle.signed_prekey),
+            &bundle.signed_prekey_signature,
+        ) {
+            return Err(SignalError::InvalidSignature.into());
+        }
+        let state = SessionState::initialize_alice(
+            identity,
+            registration_id,
+            bundle,
+            &KeyPair::generate(),
+            KeyPair::generate(),
+        );
+        self.promote(state);
+        Ok(())
+    }
+
+    /// Decrypt a prekey message, first setting up the session it starts (X3DH as responder)
+    /// unless that was already done for an earlier message. `one_time_prekey` is the key pair
+    /// for `message.prekey_id`. Nothing changes if decryption fails.
+    pub fn decrypt_prekey_message(
+        &mut self,
+        identity: &KeyPair,
+        registration_id: u32,
+        message: &PreKeySignalMessage,
+        signed_prekey: &KeyPair,
+        one_time_prekey: Option<&KeyPair>,
+    ) -> Result<Vec<u8>> {
+        let mut record = self.clone();
+        if !record.has_session_with_base_key(&message.base_key) {
+            record.promote(SessionState::initialize_bob(
+                identity,
+                registration_id,
+                message,
+                signed_prekey,
+                one_time_prekey,
+            ));
+        }
+        let plaintext = record.decrypt(&message.message)?;
+        *self = record;
+        Ok(plaintext)
+    }
+
+    pub fn encrypt(&mut self, plaintext: &[u8]) -> Result<CiphertextMessage> {
+        let state = self.current.as_mut().ok_or(SignalError::NoSession)?;
+        Ok(state.encrypt(plaintext))
+    }
+
+    /// Decrypt with the current session or, failing that, an archived one (which then
+    /// becomes current). Nothing changes if decryption fails.
+    pub fn decrypt(&mut self, message: &SignalMessage) -> Result<Vec<u8>> {
+        let mut state = self.current.clone().ok_or(SignalError::NoSession)?;
+        let error = match state.decrypt(message) {
+            Ok(plaintext) => {
+                self.current = Some(state);
+                return Ok(plaintext);
+            }
+            Err(e) => e,
+        };
+        for i in 0..self.previous.len() {
+            let mut state = self.previous[i].clone();
+            if let Ok(plaintext) = state.decrypt(message) {
+                self.previous.remove(i);
+                self.promote(state);
+                return Ok(plaintext);
+            }
+        }
+        Err(error)
+    }
+
+    fn promote(&mut self, state: SessionState) {
+        if let Some(old) = self.current.replace(state) {
+            self.previous.insert(0, old);
+            self.previous.truncate(MAX_ARCHIVED_STATES);
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct ReceiverChain {
+    ratchet_key: [u8; 32],
+    chain_key: ChainKey,
+    /// Keys of skipped messages that may still arrive.
+    message_keys: Vec<MessageKeys>,
+}
+
+/// X3DH parameters repeated in every message until the remote answers.
+#[derive(Clone, Debug)]
+struct PendingPreKey {
+    prekey_id: Option<u32>,
+    signed_prekey_id: u32,
+    base_key: [u8; 32],
+}
+
+#[derive(Clone, Debug)]
+struct SessionState {
+    local_identity: [u8; 32],
+    remote_identity: [u8; 32],
+    root_key: RootKey,
+    previous_counter: u32,
+    sender_ratchet: KeyPair,
+    sender_chain: ChainKey,
+    /// Oldest first.
+    receiver_chains: Vec<ReceiverChain>,
+    pending_prekey: Option<PendingPreKey>,
+    local_registration_id: u32,
+    remote_registration_id: u32,
+    /// Initiator's base key, identifying the X3DH run that created the session.
+    alice_base_key: [u8; 32],
+}
+
+/// Root key and first chain key from the X3DH agreements.
+fn x3dh(agreements: &[[u8; 32]]) -> (RootKey, ChainKey) {
+    let mut secrets = vec![0xff; 32];
+    for agreement in agreements {
+        secrets.extend_from_slice(agreement);
+    }
+    let mut derived = [0u8; 64];
+    derive_secrets(None, &secrets, b"WhisperText", &mut derived);
+    let (root, chain) = derived.split_at(32);
+    (
+        RootKey(root.try_into().expect("32 bytes")),
+        ChainKey {
+            key: chain.try_into().expect("32 bytes"),
+            index: 0,
+        },
+    )
+}
+
+impl SessionState {
+    fn initialize_alice(
+        identity: &KeyPair,
+        registration_id: u32,
+        bundle: &PreKeyBundle,
+        base_key: &KeyPair,
+        sending_ratchet: KeyPair,
+    ) -> Self {
+        let mut agreements = vec![
+            identity.agree(&bundle.signed_prekey),
+            base_key.agree(&bundle.identity_key),
+            base_key.agree(&bundle.signed_prekey),
+        ];
+        if let Some((_, prekey)) = &bundle.prekey {
+            agreements.push(base_key.agree(prekey));
+        }
+        let (root_key, chain_key) = x3dh(&agreements);
+        let (root_key, sender_chain) =
+            root_key.create_chain(&bundle.signed_prekey, &sending_ratchet);
+        Self {
+            local_identity: identity.public,
+            remote_identity: bundle.identity_key,
+            root_key,
+            previous_counter: 0,
+            sender_ratchet: sending_ratchet,
+            sender_chain,
+            receiver_chains: vec![ReceiverChain {
+                ratchet_key: bundle.signed_prekey,
+                chain_key,
+                message_keys: Vec::new(),
+            }],
+            pending_prekey: Some(PendingPreKey {
+                prekey_id: bundle.prekey.map(|(id, _)| id),
+                signed_prekey_id: bundle.signed_prekey_id,
+                base_key: base_key.public,
+            }),
+            local_registration_id: registration_id,
+            remote_registration_id: bundle.registration_id,
+            alice_base_key: base_key.public,
+        }
+    }
+
+    fn initialize_bob(
+        identity: &KeyPair,
+        registration_id: u32,
+        message: &PreKeySignalMessage,
+        signed_prekey: &KeyPair,
+        one_time_prekey: Option<&KeyPair>,
+    ) -> Self {
+        let mut agreements = vec![
+            signed_prekey.agree(&message.identity_key),
+            identity.agree(&message.base_key),
+            signed_prekey.agree(&message.base_key),
+        ];
+        if let Some(prekey) = one_time_prekey {
+            agreements.push(prekey.agree(&message.base_key));
+        }
+        let (root_key, sender_chain) = x3dh(&agreements);
+        Self {
+            local_identity: identity.public,
+            remote_identity: message.identity_key,
+            root_key,
+            previous_counter: 0,
+            sender_ratchet: signed_prekey.clone(),
+            sender_chain,
+            receiver_chains: Vec::new(),
+            pending_prekey: None,
+            local_registration_id: registration_id,
+            remote_registration_id: message.registration_id,
+            alice_base_key: message.base_key,
+        }
+    }
+
+    fn encrypt(&mut self, plaintext: &[u8]) -> CiphertextMessage {
+        let keys = self.sender_chain.message_keys();
+        let ciphertext = crypto::aes_cbc_encrypt(&keys.cipher_key, &keys.iv, plaintext);
+        let message = SignalMessage::new(
+            &keys.mac_key,
+            self.sender_ratchet.public,
+            keys.counter,
+            self.previous_counter,
+            ciphertext,
+            &self.local_identity,
+            &self.remote_identity,
+        );
+        self.sender_chain = self.sender_chain.next();
+        match &self.pending_prekey {
+            Some(pending) => CiphertextMessage::PreKey(PreKeySignalMessage::new(
+                self.local_registration_id,
+                pending.prekey_id,
+                pending.signed_prekey_id,
+                pending.base_key,
+                self.local_identity,
+                message,
+            )),
+            None => CiphertextMessage::Whisper(message),
+        }
+    }
+
+    fn decrypt(&mut self, message: &SignalMessage) -> Result<Vec<u8>> {
+        let chain = self.receiver_chain(&message.ratchet_key);
+        let keys = Self::message_keys(&mut self.receiver_chains[chain], message.counter)?;
+        message.verify_mac(&keys.mac_key, &self.remote_identity, &self.local_identity)?;
+        let plaintext = crypto::aes_cbc_decrypt(&keys.cipher_key, &keys.iv, &message.ciphertext)
+            .ok_or_else(|| SignalError::InvalidMessage("bad ciphertext padding".into()))?;
+        // The remote has the session now; stop sending prekey messages.
+        self.pending_prekey = None;
+        Ok(plaintext)
+    }
+
+    /// Index of the receiver chain for `their_ratchet`, performing a DH ratchet step (and
+    /// starting a new sending chain) when the remote has a new ratchet key.
+    fn receiver_chain(&mut self, their_ratchet: &[u8; 32]) -> usize {
+        if let Some(i) = self
+            .receiver_chains
+            .iter()
+            .position(|c| c.ratchet_key == *their_ratchet)
+        {
+            return i;
+        }
+        let (root_key, chain_key) = self
+            .root_key
+            .create_chain(their_ratchet, &self.sender_ratchet);
+        let sender_ratchet = KeyPair::generate();
+        let (root_key, sender_chain) = root_key.create_chain(their_ratchet, &sender_ratchet);
+        self.root_key = root_key;
+        self.receiver_chains.push(ReceiverChain {
+            ratchet_key: *their_ratchet,
+            chain_key,
+            message_keys: Vec::new(),
+        });
+        if self.receiver_chains.len() > MAX_RECEIVER_CHAINS {
+            self.receiver_chains.remove(0);
+        }
+        self.previous_counter = self.sender_chain.index.saturating_sub(1);
+        self.sender_ratchet = sender_ratchet;
+        self.sender_chain = sender_chain;
+        self.receiver_chains.len() - 1
+    }
+
+    /// Keys for message `counter` of `chain`, keeping the keys of any skipped messages.
+    fn message_keys(chain: &mut ReceiverChain, counter: u32) -> Result<MessageKeys> {
+        if counter < chain.chain_key.index {
+            let skipped = chain.message_keys.iter().position(|k| k.counter == counter);
+            return match skipped {
+                Some(i) => Ok(chain.message_keys.remove(i)),
+                None => Err(SignalError::DuplicateMessage(counter).into()),
+            };
+        }
+        if counter - chain.chain_key.index > MAX_FUTURE_MESSAGES {
+            return Err(SignalError::InvalidMessage(format!(
+                "message counter {} too far ahead of {}",
+                counter, chain.chain_key.index
+            ))
+            .into());
+        }
+        let mut chain_key = chain.chain_key.clone();
+        while chain_key.index < counter {
+            chain.message_keys.push(chain_key.message_keys());
+            if chain.message_keys.len() > MAX_MESSAGE_KEYS {
+                chain.message_keys.remove(0);
+            }
+            chain_key = chain_key.next();
+        }
+        chain.chain_key = chain_key.next();
+        Ok(chain_key.message_keys())
+    }
+
+    fn to_proto(&self) -> SessionStructure {
+        let chain_proto = |ratchet_key: &[u8; 32], chain_key: &ChainKey| Chain {
+            sender_ratchet_key: Some(serialize_public_key(ratchet_key)),
+            sender_ratchet_key_private: None,
+            chain_key: Some(chain::ChainKey {
+                index: Some(chain_key.index),
+                key: Some(chain_key.key.to_vec()),
+            }),
+            message_keys: Vec::new(),
+        };
+        SessionStructure {
+            session_version: Some(CIPHERTEXT_VERSION.into()),
+            local_identity_public: Some(serialize_public_key(&self.local_identity)),
+            remote_identity_public: Some(serialize_public_key(&self.remote_identity)),
+            root_key: Some(self.root_key.0.to_vec()),
+            previous_counter: Some(self.previous_counter),
+            sender_chain: Some(Chain {
+                sender_ratchet_key_private: Some(self.sender_ratchet.private.to_vec()),
+                ..chain_proto(&self.sender_ratchet.public, &self.sender_chain)
+            }),
+            receiver_chains: self
+                .receiver_chains
+                .iter()
+                .map(|c| Chain {
+                    message_keys: c
+                        .message_keys
+                        .iter()
+                        .map(|k| chain::MessageKey {
+                            index: Some(k.counter),
+                            cipher_key: Some(k.cipher_key.to_vec()),
+                            mac_key: Some(k.mac_key.to_vec()),
+                            iv: Some(k.iv.to_vec()),
+                        })
+                        .collect(),
+                    ..chain_proto(&c.ratchet_key, &c.chain_key)
+                })
+                .collect(),
+            pending_pre_key: self.pending_prekey.as_ref().map(|p| PendingProto {
+                pre_key_id: p.prekey_id,
+                signed_pre_key_id: Some(p.signed_prekey_id as i32),
+                base_key: Some(serialize_public_key(&p.base_key)),
+            }),
+            remote_registration_id: Some(self.remote_registration_id),
+            local_registration_id: Some(self.local_registration_id),
+            alice_base_key: Some(serialize_public_key(&self.alice_base_key)),
+        }
+    }
+
+    fn from_proto(session: SessionStructure) -> Result<Self> {
+        if session.session_version != Some(CIPHERTEXT_VERSION.into()) {
+            return Err(
+                SignalError::UnsupportedVersion(session.session_version.unwrap_or(0) as u8).into(),
+            );
+        }
+        let sender = session
+            .sender_chain
+            .ok_or_else(|| corrupt_record("session", "sender chain"))?;
+        let sender_private: [u8; 32] = stored_bytes(sender.sender_ratchet_key_private.as_deref())?;
+        Ok(Self {
+            local_identity: parse_public_key(
+                session.local_identity_public.as_deref().unwrap_or_default(),
+            )?,
+            remote_identity: parse_public_key(
+                session
+                    .remote_identity_public
+                    .as_deref()
+                    .unwrap_or_default(),
+            )?,
+            root_key: RootKey(stored_bytes(session.root_key.as_deref())?),
+            previous_counter: session.previous_counter.unwrap_or(0),
+            sender_ratchet: KeyPair::from_private(sender_private),
+            sender_chain: chain_key_from_proto(sender.chain_key)?,
+            receiver_chains: session
+                .receiver_chains
+                .into_iter()
+                .map(|c| {
+                    Ok(ReceiverChain {
+                        ratchet_key: parse_public_key(
+                            c.sender_ratchet_key.as_deref().unwrap_or_default(),
+                        )?,
+                        message_keys: c
+                            .message_keys
+                            .into_iter()
+                            .map(|k| {
+                                Ok(MessageKeys {
+                                    cipher_key: stored_bytes(k.cipher_key.as_deref())?,
+                                    mac_key: stored_bytes(k.mac_key.as_deref())?,
+                                    iv: stored_bytes(k.iv.as_deref())?,
+                                    counter: k.index.unwrap_or(0),
+                                })
+                            })
+                            .collect::<Result<_>>()?,
+                        chain_key: chain_key_from_proto(c.chain_key)?,
+                    })
+                })
+                .collect::<Result<_>>()?,
+            pending_prekey: session
+                .pending_pre_key
+                .map(|p| {
+                    Ok::<_, crate::Error>(PendingPreKey {
+                        prekey_id: p.pre_key_id,
+                        signed_prekey_id: p.signed_pre_key_id.unwrap_or(0) as u32,
+                        base_key: parse_public_key(p.base_key.as_deref().unwrap_or_default())?,
+                    })
+                })
+                .transpose()?,
+            local_registration_id: session.local_registration_id.unwrap_or(0),
+            remote_registration_id: session.remote_registration_id.unwrap_or(0),
+            alice_base_key: parse_public_key(
+                session.alice_base_key.as_deref().unwrap_or_default(),
+            )?,
+        })
+    }
+}
+
+fn chain_key_from_proto(chain_key: Option<chain::ChainKey>) -> Result<ChainKey> {
+    let chain_key = chain_key.ok_or_else(|| corrupt_record("session", "chain key"))?;
+    Ok(ChainKey {
+        key: stored_bytes(chain_key.key.as_deref())?,
+        index: chain_key.index.unwrap_or(0),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    struct Party {
+        identity: KeyPair,
+        registration_id: u32,
+        signed_prekey: KeyPair,
+        prekey: KeyPair,
+    }
+
+    impl Party {
+        fn new(registration_id: u32) -> Self {
+            Self {
+                identity: KeyPair::generate(),
+                registration_id,
+                signed_prekey: KeyPair::generate(),
+                prekey: KeyPair::generate(),
+            }
+        }
+
+        fn bundle(&self) -> PreKeyBundle {
+            PreKeyBundle {
+                registration_id: self.registration_id,
+                device_id: 0,
+                prekey: Some((31, self.prekey.public)),
+                signed_prekey_id: 1,
+                signed_prekey: self.signed_prekey.public,
+                signed_prekey_signature: self
+                    .identity
+                    .sign(&serialize_public_key(&self.signed_prekey.public)),
+                identity_key: self.identity.public,
+            }
+        }
+
+        fn receive(
+            &self,
+            record: &mut SessionRecord,
+            message: &CiphertextMessage,
+        ) -> Result<Vec<u8>> {
+            match message {
+                CiphertextMessage::PreKey(m) => {
+                    let m = PreKeySignalMessage::deserialize(m.serialize())?;
+                    assert_eq!(m.prekey_id, Some(31));
+                    record.decrypt_prekey_message(
+                        &self.identity,
+                        self.registration_id,
+                        &m,
+                        &self.signed_prekey,
+                        Some(&self.prekey),
+                    )
+                }
+                CiphertextMessage::Whisper(m) => {
+                    record.decrypt(&SignalMessage::deserialize(m.serialize())?)
+                }
+            }
+        }
+    }
+
+    /// Alice starts a session with Bob's bundle; returns both records.
+    fn start() -> (Party, SessionRecord, Party, SessionRecord) {
+        let alice = Party::new(1111);
+        let bob = Party::new(2222);
+        let mut alice_record = SessionRecord::new();
+        alice_record
+            .process_bundle(&alice.identity, alice.registration_id, &bob.bundle())
+            .unwrap();
+        (alice, alice_record, bob, SessionRecord::new())
+    }
+
+    #[test]
+    fn two_parties_converse() {
+        let (alice, mut alice_record, bob, mut bob_record) = start();
+
+        let first = alice_record.encrypt(b"hello bob").unwrap();
+        assert_eq!(first.enc_type(), "pkmsg");
+        assert_eq!(bob.receive(&mut bob_record, &first).unwrap(), b"hello bob");
+        assert_eq!(bob_record.remote_registration_id(), Some(1111));
+        assert_eq!(bob_record.remote_identity(), Some(alice.identity.public));
+
+        // Until Bob answers, Alice keeps sending prekey messages.
+        let second = alice_record.encrypt(b"still there?").unwrap();
+        assert_eq!(second.enc_type(), "pkmsg");
+        assert_eq!(
+            bob.receive(&mut bob_record, &second).unwrap(),
+            b"still there?"
+        );
+
+        let reply = bob_record.encrypt(b"hi alice").unwrap();
+        assert_eq!(reply.enc_type(), "msg");
+        assert_eq!(
+            alice.receive(&mut alice_record, &reply).unwrap(),
+            b"hi alice"
+        );
+
+        // Several DH ratchet steps in both directions.
+        for round in 0..5 {
+            let text = format!("round {}", round);
+            let m = alice_record.encrypt(text.as_bytes()).unwrap();
+            assert_eq!(m.enc_type(), "msg");
+            assert_eq!(bob.receive(&mut bob_record, &m).unwrap(), text.as_bytes());
+            let m = bob_record.encrypt(text.as_bytes()).unwrap();
+            assert_eq!(
+                alice.receive(&mut alice_record, &m).unwrap(),
+                text.as_bytes()
+            );
+        }
+    }
+
+    #[test]
+    fn out_of_order_and_duplicate_messages() {
+        let (alice, mut alice_record, bob, mut bob_record) = start();
+        let first = alice_record.encrypt(b"0").unwrap();
+        bob.receive(&mut bob_record, &first).unwrap();
+        let reply = bob_record.encrypt(b"ack").unwrap();
+        alice.receive(&mut alice_record, &reply).unwrap();
+
+        let messages: Vec<_> = (1..=4)
+            .map(|i| alice_record.encrypt(i.to_string().as_bytes()).unwrap())
+            .collect();
+        for i in [3, 0, 2, 1] {
+            assert_eq!(
+                bob.receive(&mut bob_record, &messages[i]).unwrap(),
+                (i + 1).to_string().as_bytes()
+            );
+        }
+        assert!(matches!(
+            bob.receive(&mut bob_record, &messages[2]),
+            Err(Error::Signal(SignalError::DuplicateMessage(_)))
+        ));
+    }
+
+    #[test]
+    fn tampered_message_leaves_session_untouched() {
+        let (_alice, mut alice_record, bob, mut bob_record) = start();
+        let CiphertextMessage::PreKey(first) = alice_record.encrypt(b"hello").unwrap() else {
+            panic!("expected a prekey message");
+        };
+        let mut inner = first.message.serialize().to_vec();
+        let last = inner.len() - 1;
+        inner[last] ^= 1;
+        let tampered = PreKeySignalMessage::new(
+            first.registration_id,
+            first.prekey_id,
+            first.signed_prekey_id,
+            first.base_key,
+            first.identity_key,
+            SignalMessage::deserialize(&inner).unwrap(),
+        );
+        assert!(matches!(
+            bob_record.decrypt_prekey_message(
+                &bob.identity,
+                bob.registration_id,
+                &tampered,
+                &bob.signed_prekey,
+                Some(&bob.prekey),
+            ),
+            Err(Error::Signal(SignalError::InvalidMac))
+        ));
+        assert!(!bob_record.has_session());
+        assert_eq!(
+            bob.receive(&mut bob_record, &CiphertextMessage::PreKey(first))
+                .unwrap(),
+            b"hello"
+        );
+    }
+
+    #[test]
+    fn record_survives_serialization() {
+        let (alice, mut alice_record, bob, mut bob_record) = start();
+        let first = alice_record.encrypt(b"one").unwrap();
+        bob.receive(&mut bob_record, &first).unwrap();
+        let skipped = bob_record.encrypt(b"skipped").unwrap();
+        let delivered = bob_record.encrypt(b"two").unwrap();
+
+        let mut alice_record = SessionRecord::deserialize(&alice_record.serialize()).unwrap();
+        let mut bob_record = SessionRecord::deserialize(&bob_record.serialize()).unwrap();
+        assert_eq!(
+            alice.receive(&mut alice_record, &delivered).unwrap(),
+            b"two"
+        );
+        let mut alice_record = SessionRecord::deserialize(&alice_record.serialize()).unwrap();
+        assert_eq!(
+            alice.receive(&mut alice_record, &skipped).unwrap(),
+            b"skipped"
+        );
+        let three = alice_record.encrypt(b"three").unwrap();
+        assert_eq!(bob.receive(&mut bob_record, &three).unwrap(), b"three");
+        assert!(SessionRecord::deserialize(&[0xff]).is_err());
+    }
+
+    #[test]
+    fn bundle_signature_is_checked() {
+        let alice = Party::new(1);
+        let mut bundle = Party::new(2).bundle();
+        bundle.signed_prekey_signature[0] ^= 1;
+        assert!(matches!(
+            SessionRecord::new().process_bundle(&alice.identity, 1, &bundle),
+            Err(Error::Signal(SignalError::InvalidSignature))
+        ));
+    }
+
+    #[test]
+    fn late_message_for_archived_session_is_decrypted() {
+        let (alice, mut alice_record, bob, mut bob_record) = start();
+        let first = alice_record.encrypt(b"first").unwrap();
+        bob.receive(&mut bob_record, &first).unwrap();
+        let late = alice_record.encrypt(b"late").unwrap();
+
+        // Alice starts over (e.g. after reinstalling) before the late message arrives.
+        let mut restarted = SessionRecord::new();
+        restarted
+            .process_bundle(&alice.identity, alice.registration_id, &bob.bundle())
+            .unwrap();
+        let new = restarted.encrypt(b"new session").unwrap();
+        assert_eq!(bob.receive(&mut bob_record, &new).unwrap(), b"new session");
+        assert_eq!(bob.receive(&mut bob_record, &late).unwrap(), b"late");
+        // The archived session became current again.
+        let reply = bob_record.encrypt(b"reply").unwrap();
+        assert_eq!(alice.receive(&mut alice_record, &reply).unwrap(), b"reply");
+        assert!(matches!(
+            SessionRecord::new().encrypt(b"nobody"),
+            Err(Error::Signal(SignalError::NoSession))
+        ));
+    }
+
+    #[test]
+    fn first_message_matches_reference_vector() {
+        // Fixed keys (private key bytes 0x11.. for Alice, 0x21.. for Bob); the expected bytes
+        // come from an independent Python implementation of X3DH and the Double Ratchet.
+        let key = |b: u8| KeyPair::from_private([b; 32]);
+        let bob_identity = key(0x21);
+        let bob_signed_prekey = key(0x22);
+        let bob_prekey = key(0x23);
+        let bundle = PreKeyBundle {
+            registration_id: 2222,
+            device_id: 0,
+            prekey: Some((31, bob_prekey.public)),
+            signed_prekey_id: 1,
+            signed_prekey: bob_signed_prekey.public,
+            signed_prekey_signature: [0; 64],
+            identity_key: bob_identity.public,
+        };
+        let mut state =
+            SessionState::initialize_alice(&key(0x11), 1111, &bundle, &key(0x12), key(0x13));
+        let message = state.encrypt(b"hello bob");
+        assert_eq!(hex::encode(message.serialize()), FIRST_MESSAGE);
+
+        let CiphertextMessage::PreKey(message) = message else {
+            panic!("expected a prekey message");
+        };
+        let mut record = SessionRecord::new();
+        let plaintext = record
+            .decrypt_prekey_message(
+                &bob_identity,
+                2222,
+                &message,
+                &bob_signed_prekey,
+                Some(&bob_prekey),
+            )
+            .unwrap();
+        assert_eq!(plaintext, b"hello bob");
+    }
+
+    const FIRST_MESSAGE: &str = "33081f122105052a50773ac8d91773f2dc9662e12f0defe915e415b8a1c8e20a\
+     5a3d6ab2b8431a21057b4e909bbe7ffe44c465a220037d608ee35897d31ef972\
+     f07f74892cb0f73f132242330a2105197fc2c567dc03ee2aadf0ed86681dac24\
+     daa76e83ca555875dd3be7376e53061000180022105291cfc35f22dcb617e9ed\
+     57b8e5e978a275f4d37d8eac1128d7083001";
+}
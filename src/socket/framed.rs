@@ -28,12 +28,20 @@ impl FramedSend {
 
     /// Write one frame as a single WebSocket binary message: 3-byte length + body.
     pub async fn send_frame(&self, data: &[u8]) -> Result<()> {
+        self.send_frame_with_header(&[], data).await
+    }
+
+    /// Like [send_frame](Self::send_frame) but with `header` before the length prefix
+    /// (the connection header that precedes the first frame).
+    pub async fn send_frame_with_header(&self, header: &[u8], data: &[u8]) -> Result<()> {
         if data.len() > MAX_FRAME_SIZE {
             return Err(Error::Binary("frame too large".into()));
         }
-        let mut msg = Vec::with_capacity(3 + data.len());
-        msg.resize(3, 0);
-        write_frame_len(&mut msg, data.len());
+        let mut msg = Vec::with_capacity(header.len() + 3 + data.len());
+        msg.extend_from_slice(header);
+        let len_at = msg.len();
+        msg.resize(len_at + 3, 0);
+        write_frame_len(&mut msg[len_at..], data.len());
         msg.extend_from_slice(data);
         let mut w = self.writer.lock().await;
         w.send(Message::Binary(msg))
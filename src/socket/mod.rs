@@ -15,9 +15,10 @@ pub use framed::{FramedRecv, FramedSend};
 #[cfg(feature = "full")]
 pub use noise::{
     connect_noise, connect_noise_default, run_client_handshake, NoiseRecv, NoiseTransport,
+    NoiseTransportFactory,
 };
 #[cfg(feature = "full")]
-pub use ws::{connect, connect_default};
+pub use ws::{connect, connect_default, connect_with_proxy};
 
 /// Maximum frame body size (3-byte length = 2^24 - 1).
 pub const MAX_FRAME_SIZE: usize = (1 << 24) - 1;
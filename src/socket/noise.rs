@@ -1,79 +1,127 @@
 //! Noise protocol handshake and transport (WhatsApp: XX_25519_AESGCM_SHA256).
 //! Requires `full` feature.
 
-use crate::binary::{NOISE_START_PATTERN, WA_CONN_HEADER};
+use crate::binary::WA_CONN_HEADER;
 use crate::error::{ConnectionError, Error};
+use crate::proto::wa_wa6::{handshake_message, HandshakeMessage};
+use crate::transport::{
+    DialOptions, FrameReceiver, HandshakeParams, PendingConnection, Transport, TransportFactory,
+};
 use crate::Result;
 use async_trait::async_trait;
+use prost::Message;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
 use super::framed::{FramedRecv, FramedSend};
 
-/// Noise pattern used by WhatsApp Web.
+/// Noise pattern used by WhatsApp Web. snow pads the name to 32 bytes with zeros, which
+/// yields the same initial hash as [NOISE_START_PATTERN](crate::binary::NOISE_START_PATTERN).
 const NOISE_PATTERN: &str = "Noise_XX_25519_AESGCM_SHA256";
 
-/// Prologue hashed into the handshake: WA header + pattern string (must match server).
-fn prologue() -> Vec<u8> {
-    let mut p = Vec::with_capacity(WA_CONN_HEADER.len() + NOISE_START_PATTERN.len());
-    p.extend_from_slice(&WA_CONN_HEADER);
-    p.extend_from_slice(NOISE_START_PATTERN);
-    p
+/// Length of an encrypted static key in the handshake (32-byte key + 16-byte tag).
+const ENCRYPTED_STATIC_LEN: usize = 48;
+
+fn handshake_error(stage: &str, e: snow::Error) -> Error {
+    Error::Connection(ConnectionError::HandshakeFailed(format!(
+        "noise {}: {}",
+        stage, e
+    )))
+}
+
+fn protocol_error(msg: &str) -> Error {
+    Error::Connection(ConnectionError::HandshakeFailed(msg.to_string()))
 }
 
 /// Run the Noise XX handshake as initiator over the framed WebSocket.
-/// First frame sent is: prologue (WA header + pattern) + first handshake message.
+/// The connection header (WA_CONN_HEADER) is the Noise prologue and is sent in front of the
+/// first frame; each handshake message travels wrapped in a `HandshakeMessage` protobuf.
+/// `params.noise_key` is used as our static key and `params.client_payload` is sent
+/// encrypted in the final handshake message.
 /// Consumes the framed send/recv and returns Noise transport and recv halves.
 pub async fn run_client_handshake(
     send: FramedSend,
     recv: FramedRecv,
+    params: &HandshakeParams,
 ) -> Result<(NoiseTransport, NoiseRecv)> {
-    let prologue = prologue();
-    let params = NOISE_PATTERN.parse().map_err(|e: snow::Error| {
-        Error::Connection(ConnectionError::WebSocket(format!("noise params: {}", e)))
-    })?;
-    let mut handshake = snow::Builder::new(params)
-        .prologue(&prologue[..])
-        .map_err(|e| Error::Connection(ConnectionError::WebSocket(format!("noise init: {}", e))))?
+    let noise_params = NOISE_PATTERN
+        .parse()
+        .map_err(|e: snow::Error| handshake_error("params", e))?;
+    let builder = snow::Builder::new(noise_params);
+    let static_key = match params.noise_key {
+        Some(key) => key.to_vec(),
+        None => {
+            builder
+                .generate_keypair()
+                .map_err(|e| handshake_error("keygen", e))?
+                .private
+        }
+    };
+    let mut handshake = builder
+        .prologue(&WA_CONN_HEADER)
+        .map_err(|e| handshake_error("init", e))?
+        .local_private_key(&static_key)
+        .map_err(|e| handshake_error("key", e))?
         .build_initiator()
-        .map_err(|e| {
-            Error::Connection(ConnectionError::WebSocket(format!("noise build: {}", e)))
-        })?;
+        .map_err(|e| handshake_error("build", e))?;
 
     // XX: initiator sends e
     let mut msg_buf = [0u8; 65535];
-    let len = handshake.write_message(&[], &mut msg_buf).map_err(|e| {
-        Error::Connection(ConnectionError::WebSocket(format!("noise write: {}", e)))
-    })?;
-    let mut first_frame = prologue.clone();
-    first_frame.extend_from_slice(&msg_buf[..len]);
-    send.send_frame(&first_frame).await?;
+    let len = handshake
+        .write_message(&[], &mut msg_buf)
+        .map_err(|e| handshake_error("write", e))?;
+    let hello = HandshakeMessage {
+        client_hello: Some(handshake_message::ClientHello {
+            ephemeral: Some(msg_buf[..len].to_vec()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    send.send_frame_with_header(&WA_CONN_HEADER, &hello.encode_to_vec())
+        .await?;
 
     // XX: read e, ee, s, es from server
     let frame2 = recv.next_frame().await?;
+    let server_hello = HandshakeMessage::decode(frame2.as_slice())
+        .map_err(|e| protocol_error(&format!("invalid server hello: {}", e)))?
+        .server_hello
+        .ok_or_else(|| protocol_error("missing server hello"))?;
+    let mut server_msg = server_hello.ephemeral.unwrap_or_default();
+    server_msg.extend(server_hello.r#static.unwrap_or_default());
+    server_msg.extend(server_hello.payload.unwrap_or_default());
     let mut payload_buf = [0u8; 65535];
+    // The payload is the server's certificate chain; it is authenticated by the
+    // handshake itself but not checked against WhatsApp's root key.
     let _payload_len = handshake
-        .read_message(&frame2, &mut payload_buf)
-        .map_err(|e| Error::Connection(ConnectionError::WebSocket(format!("noise read: {}", e))))?;
+        .read_message(&server_msg, &mut payload_buf)
+        .map_err(|e| handshake_error("read", e))?;
 
-    // XX: initiator sends s, se
-    let len2 = handshake.write_message(&[], &mut msg_buf).map_err(|e| {
-        Error::Connection(ConnectionError::WebSocket(format!("noise write2: {}", e)))
-    })?;
-    send.send_frame(&msg_buf[..len2]).await?;
+    // XX: initiator sends s, se (with the client payload)
+    let len2 = handshake
+        .write_message(&params.client_payload, &mut msg_buf)
+        .map_err(|e| handshake_error("write2", e))?;
+    if len2 < ENCRYPTED_STATIC_LEN {
+        return Err(protocol_error("client finish too short"));
+    }
+    let (encrypted_static, encrypted_payload) = msg_buf[..len2].split_at(ENCRYPTED_STATIC_LEN);
+    let finish = HandshakeMessage {
+        client_finish: Some(handshake_message::ClientFinish {
+            r#static: Some(encrypted_static.to_vec()),
+            payload: Some(encrypted_payload.to_vec()),
+        }),
+        ..Default::default()
+    };
+    send.send_frame(&finish.encode_to_vec()).await?;
 
     if !handshake.is_handshake_finished() {
-        return Err(Error::Connection(ConnectionError::WebSocket(
+        return Err(Error::Connection(ConnectionError::HandshakeFailed(
             "noise handshake not finished".into(),
         )));
     }
 
-    let transport_state = handshake.into_transport_mode().map_err(|e| {
-        Error::Connection(ConnectionError::WebSocket(format!(
-            "noise transport: {}",
-            e
-        )))
-    })?;
+    let transport_state = handshake
+        .into_transport_mode()
+        .map_err(|e| handshake_error("transport", e))?;
     let state = Arc::new(Mutex::new(transport_state));
 
     Ok((
@@ -113,7 +161,7 @@ impl NoiseTransport {
 }
 
 #[async_trait]
-impl crate::transport::Transport for NoiseTransport {
+impl Transport for NoiseTransport {
     async fn send(&self, data: &[u8]) -> Result<()> {
         self.send_encrypted(data).await
     }
@@ -130,14 +178,48 @@ pub struct NoiseRecv {
 }
 
 /// Connect to the default WebSocket URL and complete the Noise handshake.
-pub async fn connect_noise_default() -> Result<(NoiseTransport, NoiseRecv)> {
-    connect_noise(crate::client::DEFAULT_WS_URL).await
+pub async fn connect_noise_default(
+    params: &HandshakeParams,
+) -> Result<(NoiseTransport, NoiseRecv)> {
+    connect_noise(crate::client::DEFAULT_WS_URL, params).await
 }
 
 /// Connect to the given WebSocket URL and complete the Noise handshake.
-pub async fn connect_noise(url: &str) -> Result<(NoiseTransport, NoiseRecv)> {
+pub async fn connect_noise(
+    url: &str,
+    params: &HandshakeParams,
+) -> Result<(NoiseTransport, NoiseRecv)> {
     let (send, recv) = super::ws::connect(url).await?;
-    run_client_handshake(send, recv).await
+    run_client_handshake(send, recv, params).await
+}
+
+/// Default [TransportFactory]: WebSocket (optionally through an HTTP proxy) with the Noise handshake.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoiseTransportFactory;
+
+#[async_trait]
+impl TransportFactory for NoiseTransportFactory {
+    async fn connect(&self, opts: &DialOptions) -> Result<Box<dyn PendingConnection>> {
+        let (send, recv) = super::ws::connect_with_proxy(&opts.url, opts.proxy.as_deref()).await?;
+        Ok(Box::new(PendingNoise { send, recv }))
+    }
+}
+
+/// Open WebSocket waiting for the Noise handshake.
+struct PendingNoise {
+    send: FramedSend,
+    recv: FramedRecv,
+}
+
+#[async_trait]
+impl PendingConnection for PendingNoise {
+    async fn handshake(
+        self: Box<Self>,
+        params: &HandshakeParams,
+    ) -> Result<(Arc<dyn Transport>, Box<dyn FrameReceiver>)> {
+        let (tx, rx) = run_client_handshake(self.send, self.recv, params).await?;
+        Ok((Arc::new(tx), Box::new(rx)))
+    }
 }
 
 impl NoiseRecv {
@@ -155,3 +237,93 @@ impl NoiseRecv {
         Ok(plaintext)
     }
 }
+
+#[async_trait]
+impl FrameReceiver for NoiseRecv {
+    async fn next_frame(&self) -> Result<Vec<u8>> {
+        self.next_decrypted_frame().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+    async fn next_message<S>(ws: &mut S) -> Vec<u8>
+    where
+        S: futures::Stream<Item = tokio_tungstenite::tungstenite::Result<WsMessage>> + Unpin,
+    {
+        ws.next().await.unwrap().unwrap().into_data().to_vec()
+    }
+
+    /// Play the server side of the WhatsApp Noise handshake on one accepted socket and
+    /// return the client payload plus the first transport message.
+    async fn serve_handshake(listener: tokio::net::TcpListener) -> (Vec<u8>, Vec<u8>) {
+        let (tcp, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+        let first = next_message(&mut ws).await;
+        assert_eq!(first[..4], WA_CONN_HEADER);
+        let hello = HandshakeMessage::decode(&first[7..]).unwrap();
+        let client_e = hello.client_hello.unwrap().ephemeral.unwrap();
+
+        let builder = snow::Builder::new(NOISE_PATTERN.parse().unwrap());
+        let key = builder.generate_keypair().unwrap().private;
+        let mut responder = snow::Builder::new(NOISE_PATTERN.parse().unwrap())
+            .prologue(&WA_CONN_HEADER)
+            .unwrap()
+            .local_private_key(&key)
+            .unwrap()
+            .build_responder()
+            .unwrap();
+        let mut buf = [0u8; 65535];
+        responder.read_message(&client_e, &mut buf).unwrap();
+        let len = responder.write_message(b"cert", &mut buf).unwrap();
+        let reply = HandshakeMessage {
+            server_hello: Some(handshake_message::ServerHello {
+                ephemeral: Some(buf[..32].to_vec()),
+                r#static: Some(buf[32..32 + ENCRYPTED_STATIC_LEN].to_vec()),
+                payload: Some(buf[32 + ENCRYPTED_STATIC_LEN..len].to_vec()),
+            }),
+            ..Default::default()
+        }
+        .encode_to_vec();
+        let mut frame = vec![0u8; 3];
+        super::super::write_frame_len(&mut frame, reply.len());
+        frame.extend(reply);
+        ws.send(WsMessage::Binary(frame)).await.unwrap();
+
+        let finish = HandshakeMessage::decode(&next_message(&mut ws).await[3..])
+            .unwrap()
+            .client_finish
+            .unwrap();
+        let mut msg = finish.r#static.unwrap();
+        msg.extend(finish.payload.unwrap());
+        let len = responder.read_message(&msg, &mut buf).unwrap();
+        let payload = buf[..len].to_vec();
+
+        let mut transport = responder.into_transport_mode().unwrap();
+        let data = next_message(&mut ws).await;
+        let len = transport.read_message(&data[3..], &mut buf).unwrap();
+        (payload, buf[..len].to_vec())
+    }
+
+    #[tokio::test]
+    async fn handshake_speaks_whatsapp_framing() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(serve_handshake(listener));
+
+        let params = HandshakeParams {
+            noise_key: None,
+            client_payload: b"client-payload".to_vec(),
+        };
+        let (transport, _recv) = connect_noise(&url, &params).await.unwrap();
+        transport.send(b"hello").await.unwrap();
+
+        let (payload, first) = server.await.unwrap();
+        assert_eq!(payload, b"client-payload");
+        assert_eq!(first, b"hello");
+    }
+}
@@ -0,0 +1,194 @@
+//! Conformance tests for store backends. Call [run_all] from a test with a fresh, empty
+//! store (or the function for a single trait); they panic on the first deviation.
+//!
+//! ```ignore
+//! #[tokio::test]
+//! async fn sqlite_store_conforms() {
+//!     whatsapp_pkg::store::conformance::run_all(&SqliteStore::open_in_memory()).await;
+//! }
+//! ```
+
+use super::{Device, DeviceStore, IdentityStore, PreKeyStore, SenderKeyStore, SessionStore};
+use crate::crypto::KeyPair;
+use crate::signal::{PreKey, ProtocolAddress, SenderKeyName, SignedPreKey};
+use crate::types::Jid;
+
+/// Run every conformance test against one store.
+pub async fn run_all<S: DeviceStore>(store: &S) {
+    identity_store(store).await;
+    session_store(store).await;
+    prekey_store(store).await;
+    sender_key_store(store).await;
+    // Last: logging out deletes the keys saved above.
+    device_store(store).await;
+}
+
+pub async fn device_store<S: DeviceStore>(store: &S) {
+    let unpaired = Device {
+        registration_id: 7,
+        ..Default::default()
+    };
+    store.save(&unpaired).await.unwrap();
+    let first = store.get_first_device().await.unwrap();
+    assert_eq!(first.map(|d| d.registration_id), Some(7), "unpaired device");
+    assert!(
+        store.get_all_devices().await.unwrap().is_empty(),
+        "unpaired devices are not listed"
+    );
+
+    let jid = Jid::new_ad("5511999990000", 0, 3, "s.whatsapp.net");
+    let paired = Device {
+        id: Some(jid.clone()),
+        ..unpaired
+    };
+    store.save(&paired).await.unwrap();
+    let first = store
+        .get_first_device()
+        .await
+        .unwrap()
+        .expect("paired device");
+    assert_eq!(first.id.as_ref(), Some(&jid));
+    assert_eq!(first.registration_id, 7);
+    assert!(store.get_device(&jid).await.unwrap().is_some());
+    assert_eq!(store.get_all_devices().await.unwrap().len(), 1);
+
+    let address = ProtocolAddress::new("5522222", 1);
+    store.put_session(&address, b"session").await.unwrap();
+    store.put_identity(&address, [1; 32]).await.unwrap();
+    store.delete(&jid).await.unwrap();
+    assert!(store.get_device(&jid).await.unwrap().is_none());
+    assert!(store.get_first_device().await.unwrap().is_none());
+    assert!(
+        !store.has_session(&address).await.unwrap(),
+        "logout deletes sessions"
+    );
+    assert!(
+        store.get_identity(&address).await.unwrap().is_none(),
+        "logout deletes identities"
+    );
+}
+
+pub async fn identity_store<S: IdentityStore>(store: &S) {
+    let address = ProtocolAddress::new("5511111", 0);
+    assert!(store.get_identity(&address).await.unwrap().is_none());
+    assert!(
+        store.is_trusted_identity(&address, &[1; 32]).await.unwrap(),
+        "unknown identities are trusted on first use"
+    );
+
+    store.put_identity(&address, [1; 32]).await.unwrap();
+    assert_eq!(store.get_identity(&address).await.unwrap(), Some([1; 32]));
+    assert!(store.is_trusted_identity(&address, &[1; 32]).await.unwrap());
+    assert!(!store.is_trusted_identity(&address, &[2; 32]).await.unwrap());
+    let other_device = ProtocolAddress::new("5511111", 1);
+    assert!(store.get_identity(&other_device).await.unwrap().is_none());
+
+    store.put_identity(&address, [2; 32]).await.unwrap();
+    assert_eq!(store.get_identity(&address).await.unwrap(), Some([2; 32]));
+    store.delete_identity(&address).await.unwrap();
+    assert!(store.get_identity(&address).await.unwrap().is_none());
+}
+
+pub async fn session_store<S: SessionStore>(store: &S) {
+    let phone = ProtocolAddress::new("5533333", 0);
+    let laptop = ProtocolAddress::new("5533333", 2);
+    let other = ProtocolAddress::new("5544444", 0);
+    assert!(store.get_session(&phone).await.unwrap().is_none());
+    assert!(!store.has_session(&phone).await.unwrap());
+
+    store.put_session(&phone, b"phone").await.unwrap();
+    store.put_session(&laptop, b"laptop").await.unwrap();
+    store.put_session(&other, b"other").await.unwrap();
+    assert_eq!(
+        store.get_session(&phone).await.unwrap().as_deref(),
+        Some(&b"phone"[..])
+    );
+    assert!(store.has_session(&laptop).await.unwrap());
+
+    store.put_session(&phone, b"phone 2").await.unwrap();
+    assert_eq!(
+        store.get_session(&phone).await.unwrap().as_deref(),
+        Some(&b"phone 2"[..])
+    );
+
+    store.delete_session(&phone).await.unwrap();
+    assert!(!store.has_session(&phone).await.unwrap());
+    assert!(store.has_session(&laptop).await.unwrap());
+
+    store.delete_all_sessions("5533333").await.unwrap();
+    assert!(!store.has_session(&laptop).await.unwrap());
+    assert!(
+        store.has_session(&other).await.unwrap(),
+        "other users' sessions are kept"
+    );
+    store.delete_session(&other).await.unwrap();
+}
+
+pub async fn prekey_store<S: PreKeyStore>(store: &S) {
+    assert!(store.get_prekey(1).await.unwrap().is_none());
+    assert_eq!(store.last_prekey_id().await.unwrap(), 0);
+    let prekey = PreKey {
+        id: 1,
+        key_pair: KeyPair::generate(),
+    };
+    store.put_prekey(&prekey).await.unwrap();
+    let loaded = store.get_prekey(1).await.unwrap().expect("saved prekey");
+    assert_eq!(loaded.id, 1);
+    assert_eq!(loaded.key_pair.public, prekey.key_pair.public);
+    assert_eq!(loaded.key_pair.private, prekey.key_pair.private);
+    store.remove_prekey(1).await.unwrap();
+    assert!(store.get_prekey(1).await.unwrap().is_none());
+    store.remove_prekey(1).await.unwrap();
+    assert_eq!(
+        store.last_prekey_id().await.unwrap(),
+        1,
+        "IDs of removed prekeys are not reused"
+    );
+
+    for id in [3, 2, 4] {
+        store.put_prekey(&PreKey::generate(id)).await.unwrap();
+    }
+    let ids = |prekeys: Vec<PreKey>| prekeys.iter().map(|p| p.id).collect::<Vec<_>>();
+    assert_eq!(ids(store.unuploaded_prekeys().await.unwrap()), [2, 3, 4]);
+    store.mark_prekeys_uploaded(3).await.unwrap();
+    assert_eq!(ids(store.unuploaded_prekeys().await.unwrap()), [4]);
+    assert!(
+        store.get_prekey(2).await.unwrap().is_some(),
+        "uploaded prekeys stay until used"
+    );
+    assert_eq!(store.last_prekey_id().await.unwrap(), 4);
+
+    assert!(store.get_signed_prekey(1).await.unwrap().is_none());
+    let signed = SignedPreKey {
+        id: 1,
+        key_pair: KeyPair::generate(),
+        signature: [9; 64],
+    };
+    store.put_signed_prekey(&signed).await.unwrap();
+    let loaded = store
+        .get_signed_prekey(1)
+        .await
+        .unwrap()
+        .expect("saved signed prekey");
+    assert_eq!(loaded.key_pair.private, signed.key_pair.private);
+    assert_eq!(loaded.signature, signed.signature);
+}
+
+pub async fn sender_key_store<S: SenderKeyStore>(store: &S) {
+    let group = Jid::new("120363001", "g.us");
+    let alice = SenderKeyName::new(&group, &Jid::new_ad("5511111", 0, 0, "s.whatsapp.net"));
+    let alice_laptop = SenderKeyName::new(&group, &Jid::new_ad("5511111", 0, 2, "s.whatsapp.net"));
+    assert!(store.get_sender_key(&alice).await.unwrap().is_none());
+
+    store.put_sender_key(&alice, b"alice").await.unwrap();
+    assert_eq!(
+        store.get_sender_key(&alice).await.unwrap().as_deref(),
+        Some(&b"alice"[..])
+    );
+    assert!(store.get_sender_key(&alice_laptop).await.unwrap().is_none());
+    store.put_sender_key(&alice, b"alice 2").await.unwrap();
+    assert_eq!(
+        store.get_sender_key(&alice).await.unwrap().as_deref(),
+        Some(&b"alice 2"[..])
+    );
+}
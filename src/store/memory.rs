@@ -1,13 +1,21 @@
-use super::{Device, DeviceStore};
+use super::{Device, DeviceStore, IdentityStore, PreKeyStore, SenderKeyStore, SessionStore};
+use crate::signal::{PreKey, ProtocolAddress, SenderKeyName, SignedPreKey};
 use crate::{error::StoreError, Result};
 use async_trait::async_trait;
 use std::collections::HashMap;
-use std::sync::RwLock;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 /// In-memory device store (for testing or single-run; not persistent).
 pub struct MemoryStore {
     devices: RwLock<HashMap<String, Device>>,
     first_jid: RwLock<Option<String>>,
+    identities: RwLock<HashMap<ProtocolAddress, [u8; 32]>>,
+    sessions: RwLock<HashMap<ProtocolAddress, Vec<u8>>>,
+    /// One-time prekeys and whether each was uploaded.
+    prekeys: RwLock<HashMap<u32, (PreKey, bool)>>,
+    last_prekey_id: RwLock<u32>,
+    signed_prekeys: RwLock<HashMap<u32, SignedPreKey>>,
+    sender_keys: RwLock<HashMap<SenderKeyName, Vec<u8>>>,
 }
 
 impl MemoryStore {
@@ -15,6 +23,12 @@ impl MemoryStore {
         Self {
             devices: RwLock::new(HashMap::new()),
             first_jid: RwLock::new(None),
+            identities: RwLock::new(HashMap::new()),
+            sessions: RwLock::new(HashMap::new()),
+            prekeys: RwLock::new(HashMap::new()),
+            last_prekey_id: RwLock::new(0),
+            signed_prekeys: RwLock::new(HashMap::new()),
+            sender_keys: RwLock::new(HashMap::new()),
         }
     }
 
@@ -29,6 +43,14 @@ impl Default for MemoryStore {
     }
 }
 
+fn read<T>(lock: &RwLock<T>) -> Result<RwLockReadGuard<'_, T>> {
+    Ok(lock.read().map_err(|e| StoreError::Load(e.to_string()))?)
+}
+
+fn write<T>(lock: &RwLock<T>) -> Result<RwLockWriteGuard<'_, T>> {
+    Ok(lock.write().map_err(|e| StoreError::Save(e.to_string()))?)
+}
+
 #[async_trait]
 impl DeviceStore for MemoryStore {
     async fn get_first_device(&self) -> Result<Option<Device>> {
@@ -60,16 +82,19 @@ impl DeviceStore for MemoryStore {
             .as_ref()
             .map(|j| j.to_string())
             .unwrap_or_else(Self::first_jid_key);
+        let mut devices = self
+            .devices
+            .write()
+            .map_err(|e| StoreError::Save(e.to_string()))?;
         if device.id.is_some() {
             *self
                 .first_jid
                 .write()
                 .map_err(|e| StoreError::Save(e.to_string()))? = Some(key.clone());
+            // The unpaired placeholder becomes this device once paired.
+            devices.remove(&Self::first_jid_key());
         }
-        self.devices
-            .write()
-            .map_err(|e| StoreError::Save(e.to_string()))?
-            .insert(key, device.clone());
+        devices.insert(key, device.clone());
         Ok(())
     }
 
@@ -86,6 +111,12 @@ impl DeviceStore for MemoryStore {
         if *first == Some(key) {
             *first = None;
         }
+        write(&self.identities)?.clear();
+        write(&self.sessions)?.clear();
+        write(&self.prekeys)?.clear();
+        *write(&self.last_prekey_id)? = 0;
+        write(&self.signed_prekeys)?.clear();
+        write(&self.sender_keys)?.clear();
         Ok(())
     }
 
@@ -102,7 +133,110 @@ impl DeviceStore for MemoryStore {
     }
 }
 
+#[async_trait]
+impl IdentityStore for MemoryStore {
+    async fn put_identity(&self, address: &ProtocolAddress, key: [u8; 32]) -> Result<()> {
+        write(&self.identities)?.insert(address.clone(), key);
+        Ok(())
+    }
+
+    async fn get_identity(&self, address: &ProtocolAddress) -> Result<Option<[u8; 32]>> {
+        Ok(read(&self.identities)?.get(address).copied())
+    }
+
+    async fn delete_identity(&self, address: &ProtocolAddress) -> Result<()> {
+        write(&self.identities)?.remove(address);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SessionStore for MemoryStore {
+    async fn get_session(&self, address: &ProtocolAddress) -> Result<Option<Vec<u8>>> {
+        Ok(read(&self.sessions)?.get(address).cloned())
+    }
+
+    async fn put_session(&self, address: &ProtocolAddress, record: &[u8]) -> Result<()> {
+        write(&self.sessions)?.insert(address.clone(), record.to_vec());
+        Ok(())
+    }
+
+    async fn delete_session(&self, address: &ProtocolAddress) -> Result<()> {
+        write(&self.sessions)?.remove(address);
+        Ok(())
+    }
+
+    async fn delete_all_sessions(&self, name: &str) -> Result<()> {
+        write(&self.sessions)?.retain(|address, _| address.name != name);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PreKeyStore for MemoryStore {
+    async fn put_prekey(&self, prekey: &PreKey) -> Result<()> {
+        write(&self.prekeys)?.insert(prekey.id, (prekey.clone(), false));
+        let mut last = write(&self.last_prekey_id)?;
+        *last = (*last).max(prekey.id);
+        Ok(())
+    }
+
+    async fn get_prekey(&self, id: u32) -> Result<Option<PreKey>> {
+        Ok(read(&self.prekeys)?.get(&id).map(|(p, _)| p.clone()))
+    }
+
+    async fn remove_prekey(&self, id: u32) -> Result<()> {
+        write(&self.prekeys)?.remove(&id);
+        Ok(())
+    }
+
+    async fn last_prekey_id(&self) -> Result<u32> {
+        Ok(*read(&self.last_prekey_id)?)
+    }
+
+    async fn unuploaded_prekeys(&self) -> Result<Vec<PreKey>> {
+        let mut prekeys: Vec<PreKey> = read(&self.prekeys)?
+            .values()
+            .filter(|(_, uploaded)| !uploaded)
+            .map(|(p, _)| p.clone())
+            .collect();
+        prekeys.sort_by_key(|p| p.id);
+        Ok(prekeys)
+    }
+
+    async fn mark_prekeys_uploaded(&self, up_to_id: u32) -> Result<()> {
+        for (prekey, uploaded) in write(&self.prekeys)?.values_mut() {
+            if prekey.id <= up_to_id {
+                *uploaded = true;
+            }
+        }
+        Ok(())
+    }
+
+    async fn put_signed_prekey(&self, prekey: &SignedPreKey) -> Result<()> {
+        write(&self.signed_prekeys)?.insert(prekey.id, prekey.clone());
+        Ok(())
+    }
+
+    async fn get_signed_prekey(&self, id: u32) -> Result<Option<SignedPreKey>> {
+        Ok(read(&self.signed_prekeys)?.get(&id).cloned())
+    }
+}
+
+#[async_trait]
+impl SenderKeyStore for MemoryStore {
+    async fn get_sender_key(&self, name: &SenderKeyName) -> Result<Option<Vec<u8>>> {
+        Ok(read(&self.sender_keys)?.get(name).cloned())
+    }
+
+    async fn put_sender_key(&self, name: &SenderKeyName, record: &[u8]) -> Result<()> {
+        write(&self.sender_keys)?.insert(name.clone(), record.to_vec());
+        Ok(())
+    }
+}
+
 #[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
 mod tests {
     use super::*;
     use crate::types::Jid;
@@ -121,6 +255,31 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn memory_store_replaces_unpaired_device_when_paired() {
+        let store = MemoryStore::new();
+        let mut dev = Device {
+            registration_id: 7,
+            ..Default::default()
+        };
+        store.save(&dev).await.unwrap();
+        assert_eq!(
+            store
+                .get_first_device()
+                .await
+                .unwrap()
+                .unwrap()
+                .registration_id,
+            7
+        );
+
+        let jid = Jid::new("123", "s.whatsapp.net");
+        dev.id = Some(jid.clone());
+        store.save(&dev).await.unwrap();
+        store.delete(&jid).await.unwrap();
+        assert!(store.get_first_device().await.unwrap().is_none());
+    }
+
     #[tokio::test]
     async fn memory_store_get_device_by_jid() {
         let store = MemoryStore::new();
@@ -147,6 +306,11 @@ mod tests {
         assert!(store.get_first_device().await.unwrap().is_none());
     }
 
+    #[tokio::test]
+    async fn memory_store_conformance() {
+        crate::store::conformance::run_all(&MemoryStore::new()).await;
+    }
+
     #[tokio::test]
     async fn memory_store_get_all_devices() {
         let store = MemoryStore::new();
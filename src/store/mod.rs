@@ -1,9 +1,14 @@
 //! Device/session store.
 
+pub mod conformance;
 mod memory;
+mod signal;
 
 pub use memory::MemoryStore;
+pub use signal::{IdentityStore, PreKeyStore, SenderKeyStore, SessionStore};
 
+use crate::crypto::KeyPair;
+use crate::signal::SignedPreKey;
 use crate::types::Jid;
 use async_trait::async_trait;
 use std::sync::Arc;
@@ -16,8 +21,12 @@ pub struct Device {
     pub lid: Option<Jid>,
     pub business_name: Option<String>,
     pub platform: Option<String>,
-    /// Noise public key (32 bytes).
+    /// Our display name (`notify` on the messages we send); presence needs one, see
+    /// [Client::set_push_name](crate::Client::set_push_name).
+    pub push_name: Option<String>,
+    /// Noise static key pair (32 + 32 bytes), generated before pairing and used for every connection.
     pub noise_key_pub: Option<[u8; 32]>,
+    pub noise_key_priv: Option<[u8; 32]>,
     /// Identity key pair (32 + 32 bytes).
     pub identity_key_pub: Option<[u8; 32]>,
     pub identity_key_priv: Option<[u8; 32]>,
@@ -29,17 +38,68 @@ pub struct Device {
     pub registration_id: u32,
     /// Signed prekey ID.
     pub signed_prekey_id: u32,
+    /// Signed prekey pair (32 + 32 bytes) and its identity signature (64 bytes).
+    pub signed_prekey_pub: Option<[u8; 32]>,
+    pub signed_prekey_priv: Option<[u8; 32]>,
+    pub signed_prekey_sig: Option<[u8; 64]>,
 }
 
 impl Device {
     pub fn is_logged_in(&self) -> bool {
         self.id.is_some()
     }
+
+    /// Unpaired device holding freshly generated keys (see [generate_pairing_keys](crate::pairing::generate_pairing_keys)).
+    pub fn with_keys(keys: &crate::pairing::PairingKeys) -> Self {
+        Self {
+            noise_key_pub: Some(keys.noise_public),
+            noise_key_priv: Some(keys.noise_private),
+            identity_key_pub: Some(keys.identity_public),
+            identity_key_priv: Some(keys.identity_private),
+            adv_secret_key: Some(keys.adv_secret),
+            registration_id: keys.registration_id,
+            signed_prekey_id: crate::pairing::SIGNED_PREKEY_ID,
+            signed_prekey_pub: Some(keys.signed_prekey_public),
+            signed_prekey_priv: Some(keys.signed_prekey_private),
+            signed_prekey_sig: Some(keys.signed_prekey_signature),
+            ..Default::default()
+        }
+    }
+
+    /// Our identity key pair, if generated.
+    pub fn identity_key_pair(&self) -> Option<KeyPair> {
+        Some(KeyPair {
+            public: self.identity_key_pub?,
+            private: self.identity_key_priv?,
+        })
+    }
+
+    /// Our signed prekey, if generated.
+    pub fn signed_prekey(&self) -> Option<SignedPreKey> {
+        Some(SignedPreKey {
+            id: self.signed_prekey_id,
+            key_pair: KeyPair {
+                public: self.signed_prekey_pub?,
+                private: self.signed_prekey_priv?,
+            },
+            signature: self.signed_prekey_sig?,
+        })
+    }
+
+    /// Whether the keys needed to pair (or log in) have been generated.
+    pub fn has_keys(&self) -> bool {
+        self.noise_key_priv.is_some()
+            && self.identity_key_priv.is_some()
+            && self.adv_secret_key.is_some()
+            && self.signed_prekey_priv.is_some()
+    }
 }
 
-/// Store trait: persist and load device state.
+/// Store trait: persist and load device state, and the device's Signal keys.
+///
+/// Backends can check themselves against [conformance::run_all].
 #[async_trait]
-pub trait DeviceStore: Send + Sync {
+pub trait DeviceStore: IdentityStore + SessionStore + PreKeyStore + SenderKeyStore {
     /// Get the first (or only) device. Used to create a client.
     async fn get_first_device(&self) -> crate::Result<Option<Device>>;
 
@@ -49,7 +109,7 @@ pub trait DeviceStore: Send + Sync {
     /// Save device state (after pairing or key changes).
     async fn save(&self, device: &Device) -> crate::Result<()>;
 
-    /// Delete device (logout).
+    /// Delete device (logout), with its Signal keys.
     async fn delete(&self, jid: &Jid) -> crate::Result<()>;
 
     /// Get all stored devices.
@@ -0,0 +1,79 @@
+//! Signal key stores: what end-to-end encryption needs to persist besides the [Device].
+//!
+//! Sessions and sender keys are stored as the serialized records of [crate::signal], so
+//! backends only deal with bytes. All keys belong to the store's device.
+
+use crate::signal::{PreKey, ProtocolAddress, SenderKeyName, SignedPreKey};
+use crate::Result;
+use async_trait::async_trait;
+
+/// Identity keys of remote devices, trusted on first use.
+#[async_trait]
+pub trait IdentityStore: Send + Sync {
+    /// Save (or replace) the identity key of a remote device.
+    async fn put_identity(&self, address: &ProtocolAddress, key: [u8; 32]) -> Result<()>;
+
+    async fn get_identity(&self, address: &ProtocolAddress) -> Result<Option<[u8; 32]>>;
+
+    async fn delete_identity(&self, address: &ProtocolAddress) -> Result<()>;
+
+    /// Whether `key` may be used for `address`: it is the one we saved, or we have none yet.
+    async fn is_trusted_identity(&self, address: &ProtocolAddress, key: &[u8; 32]) -> Result<bool> {
+        Ok(self
+            .get_identity(address)
+            .await?
+            .is_none_or(|saved| saved == *key))
+    }
+}
+
+/// Pairwise sessions with remote devices (serialized [crate::signal::SessionRecord]s).
+#[async_trait]
+pub trait SessionStore: Send + Sync {
+    async fn get_session(&self, address: &ProtocolAddress) -> Result<Option<Vec<u8>>>;
+
+    async fn put_session(&self, address: &ProtocolAddress, record: &[u8]) -> Result<()>;
+
+    async fn delete_session(&self, address: &ProtocolAddress) -> Result<()>;
+
+    /// Delete the sessions with every device of a user (`name` of their addresses).
+    async fn delete_all_sessions(&self, name: &str) -> Result<()>;
+
+    async fn has_session(&self, address: &ProtocolAddress) -> Result<bool> {
+        Ok(self.get_session(address).await?.is_some())
+    }
+}
+
+/// Our one-time and signed prekeys.
+#[async_trait]
+pub trait PreKeyStore: Send + Sync {
+    /// Save a newly generated one-time prekey (not yet uploaded).
+    async fn put_prekey(&self, prekey: &PreKey) -> Result<()>;
+
+    async fn get_prekey(&self, id: u32) -> Result<Option<PreKey>>;
+
+    /// Remove a one-time prekey once a remote device has used it.
+    async fn remove_prekey(&self, id: u32) -> Result<()>;
+
+    /// Highest one-time prekey ID ever saved (0 if none), so new IDs never repeat even after
+    /// prekeys were removed.
+    async fn last_prekey_id(&self) -> Result<u32>;
+
+    /// Saved one-time prekeys not uploaded to the server yet, by ascending ID.
+    async fn unuploaded_prekeys(&self) -> Result<Vec<PreKey>>;
+
+    /// Mark the one-time prekeys up to and including `id` as uploaded.
+    async fn mark_prekeys_uploaded(&self, up_to_id: u32) -> Result<()>;
+
+    async fn put_signed_prekey(&self, prekey: &SignedPreKey) -> Result<()>;
+
+    async fn get_signed_prekey(&self, id: u32) -> Result<Option<SignedPreKey>>;
+}
+
+/// Sender keys for group messages (serialized [crate::signal::SenderKeyRecord]s), ours and
+/// other members'.
+#[async_trait]
+pub trait SenderKeyStore: Send + Sync {
+    async fn get_sender_key(&self, name: &SenderKeyName) -> Result<Option<Vec<u8>>>;
+
+    async fn put_sender_key(&self, name: &SenderKeyName, record: &[u8]) -> Result<()>;
+}
@@ -3,9 +3,15 @@
 //! Implement this trait to plug in a WebSocket, Noise-wrapped socket, or other
 //! transport. The client uses it for sending; receiving is typically handled by
 //! a dedicated task that reads from the transport and feeds decoded nodes to the client.
+//!
+//! Connections are opened through a [TransportFactory] in two steps, so the client can tell
+//! dialing apart from the protocol handshake: [TransportFactory::connect] opens the raw socket
+//! and [PendingConnection::handshake] completes the Noise handshake, yielding the send and
+//! receive halves used for the rest of the session.
 
 use crate::Result;
 use async_trait::async_trait;
+use std::sync::Arc;
 
 /// Async trait for a connection transport (e.g. WebSocket, Noise over WebSocket).
 #[async_trait]
@@ -16,3 +22,46 @@ pub trait Transport: Send + Sync {
     /// Close the transport.
     async fn close(&self) -> Result<()>;
 }
+
+/// Receive half of an established connection.
+#[async_trait]
+pub trait FrameReceiver: Send + Sync {
+    /// Wait for the next frame (decrypted, without length prefix).
+    /// Returns an error once the connection is closed.
+    async fn next_frame(&self) -> Result<Vec<u8>>;
+}
+
+/// Where and how to open a connection.
+#[derive(Clone, Debug)]
+pub struct DialOptions {
+    /// WebSocket URL.
+    pub url: String,
+    /// Optional HTTP proxy (`http://host:port`) to tunnel the connection through.
+    pub proxy: Option<String>,
+}
+
+/// Data needed to complete the handshake on a freshly opened connection.
+#[derive(Clone, Debug)]
+pub struct HandshakeParams {
+    /// Static Noise private key identifying this device. A random key is used when `None`.
+    pub noise_key: Option<[u8; 32]>,
+    /// Encoded `ClientPayload` protobuf sent in the final handshake message.
+    pub client_payload: Vec<u8>,
+}
+
+/// A connection that is open but has not completed the handshake yet.
+#[async_trait]
+pub trait PendingConnection: Send {
+    /// Run the handshake and return the send and receive halves of the connection.
+    async fn handshake(
+        self: Box<Self>,
+        params: &HandshakeParams,
+    ) -> Result<(Arc<dyn Transport>, Box<dyn FrameReceiver>)>;
+}
+
+/// Opens connections for the client; see [ClientBuilder::transport_factory](crate::client::ClientBuilder::transport_factory).
+#[async_trait]
+pub trait TransportFactory: Send + Sync {
+    /// Open a raw connection to the server.
+    async fn connect(&self, opts: &DialOptions) -> Result<Box<dyn PendingConnection>>;
+}
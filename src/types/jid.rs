@@ -190,6 +190,7 @@ impl<'de> serde::Deserialize<'de> for Jid {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_comparison)]
 mod tests {
     use super::*;
 
@@ -0,0 +1,158 @@
+use crate::proto::wa_e2e::{protocol_message, Message};
+
+/// `edit` attribute of a `<message>`: whether it edits, revokes or pins an earlier message.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum EditAttribute {
+    /// A regular message (no attribute).
+    #[default]
+    Empty,
+    MessageEdit,
+    PinInChat,
+    AdminEdit,
+    /// Deleted for everyone by its sender (also used to remove a reaction).
+    SenderRevoke,
+    /// Deleted for everyone by a group admin.
+    AdminRevoke,
+}
+
+impl EditAttribute {
+    /// Value of the attribute; empty for [EditAttribute::Empty].
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Empty => "",
+            Self::MessageEdit => "1",
+            Self::PinInChat => "2",
+            Self::AdminEdit => "3",
+            Self::SenderRevoke => "7",
+            Self::AdminRevoke => "8",
+        }
+    }
+
+    /// Parse an attribute value; unknown values are treated as a regular message.
+    pub fn from_attr(value: &str) -> Self {
+        match value {
+            "1" => Self::MessageEdit,
+            "2" => Self::PinInChat,
+            "3" => Self::AdminEdit,
+            "7" => Self::SenderRevoke,
+            "8" => Self::AdminRevoke,
+            _ => Self::Empty,
+        }
+    }
+
+    /// The attribute a message needs: edits, revokes, reaction removals and pins get theirs,
+    /// everything else none.
+    pub fn for_message(message: &Message) -> Self {
+        if let Some(edited) = message
+            .edited_message
+            .as_ref()
+            .and_then(|e| e.message.as_deref())
+        {
+            return Self::for_message(edited);
+        }
+        if let Some(protocol) = message
+            .protocol_message
+            .as_ref()
+            .filter(|p| p.key.is_some())
+        {
+            match protocol.r#type() {
+                protocol_message::Type::Revoke => {
+                    return match protocol.key.as_ref().and_then(|k| k.from_me) {
+                        Some(true) => Self::SenderRevoke,
+                        _ => Self::AdminRevoke,
+                    };
+                }
+                protocol_message::Type::MessageEdit if protocol.edited_message.is_some() => {
+                    return Self::MessageEdit;
+                }
+                _ => {}
+            }
+        }
+        if message
+            .reaction_message
+            .as_ref()
+            .is_some_and(|r| r.text().is_empty())
+        {
+            Self::SenderRevoke
+        } else if message.pin_in_chat_message.is_some() {
+            Self::PinInChat
+        } else {
+            Self::Empty
+        }
+    }
+}
+
+/// Where a message sent by another device of ours was going.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceSentMeta {
+    pub destination_jid: String,
+    pub phash: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::wa_common::MessageKey;
+    use crate::proto::wa_e2e::{FutureProofMessage, ProtocolMessage, ReactionMessage};
+
+    fn protocol(kind: protocol_message::Type, from_me: bool) -> Message {
+        Message {
+            protocol_message: Some(Box::new(ProtocolMessage {
+                key: Some(MessageKey {
+                    from_me: Some(from_me),
+                    ..Default::default()
+                }),
+                r#type: Some(kind as i32),
+                edited_message: Some(Box::new(Message {
+                    conversation: Some("fixed".into()),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn edit_attribute_follows_message_kind() {
+        use protocol_message::Type;
+        let cases = [
+            (protocol(Type::Revoke, true), EditAttribute::SenderRevoke),
+            (protocol(Type::Revoke, false), EditAttribute::AdminRevoke),
+            (
+                protocol(Type::MessageEdit, true),
+                EditAttribute::MessageEdit,
+            ),
+            (
+                Message {
+                    edited_message: Some(Box::new(FutureProofMessage {
+                        message: Some(Box::new(protocol(Type::MessageEdit, true))),
+                    })),
+                    ..Default::default()
+                },
+                EditAttribute::MessageEdit,
+            ),
+            (
+                Message {
+                    reaction_message: Some(ReactionMessage::default()),
+                    ..Default::default()
+                },
+                EditAttribute::SenderRevoke,
+            ),
+            (
+                Message {
+                    reaction_message: Some(ReactionMessage {
+                        text: Some("👍".into()),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                EditAttribute::Empty,
+            ),
+        ];
+        for (message, expected) in cases {
+            assert_eq!(EditAttribute::for_message(&message), expected);
+            assert_eq!(EditAttribute::from_attr(expected.as_str()), expected);
+        }
+    }
+}
@@ -1,6 +1,15 @@
 mod jid;
+mod message;
+mod presence;
+mod receipt;
 
 pub use jid::Jid;
+pub(crate) use jid::{
+    BROADCAST_SERVER, DEFAULT_USER_SERVER, GROUP_SERVER, HIDDEN_USER_SERVER, NEWSLETTER_SERVER,
+};
+pub use message::{DeviceSentMeta, EditAttribute};
+pub use presence::{ChatPresence, ChatPresenceMedia, Presence};
+pub use receipt::ReceiptType;
 
 /// Message ID type (WhatsApp internal ID string).
 pub type MessageId = String;
@@ -0,0 +1,72 @@
+/// Whether we're online, see [Client::send_presence](crate::Client::send_presence).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Presence {
+    Available,
+    Unavailable,
+}
+
+impl Presence {
+    /// Value of the `type` attribute.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Available => "available",
+            Self::Unavailable => "unavailable",
+        }
+    }
+}
+
+/// Typing state in a chat, sent as the child of a `<chatstate>`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChatPresence {
+    /// Typing, or recording with [ChatPresenceMedia::Audio].
+    Composing,
+    /// Stopped typing or recording.
+    Paused,
+}
+
+impl ChatPresence {
+    /// Tag of the child node.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Composing => "composing",
+            Self::Paused => "paused",
+        }
+    }
+
+    /// Parse a child tag; `None` for states this library doesn't know about.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        match tag {
+            "composing" => Some(Self::Composing),
+            "paused" => Some(Self::Paused),
+            _ => None,
+        }
+    }
+}
+
+/// What is being composed: the `media` attribute of `<composing>`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ChatPresenceMedia {
+    /// A text message (no attribute).
+    #[default]
+    Text,
+    /// A voice message.
+    Audio,
+}
+
+impl ChatPresenceMedia {
+    /// Value of the attribute; empty for [ChatPresenceMedia::Text].
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Text => "",
+            Self::Audio => "audio",
+        }
+    }
+
+    /// Parse an attribute value; anything but `audio` means text.
+    pub fn from_attr(value: &str) -> Self {
+        match value {
+            "audio" => Self::Audio,
+            _ => Self::Text,
+        }
+    }
+}
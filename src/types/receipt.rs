@@ -0,0 +1,93 @@
+/// `type` attribute of a `<receipt>`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ReceiptType {
+    /// Delivered to the recipient's device (no attribute).
+    #[default]
+    Delivered,
+    /// Delivered to another device of the sender, for messages sent from our own devices.
+    Sender,
+    /// The recipient couldn't decrypt the message and asks for it again.
+    Retry,
+    Read,
+    /// Read, without telling the sender (read receipts disabled, or newsletters).
+    ReadSelf,
+    /// Voice message or video note played.
+    Played,
+    PlayedSelf,
+    ServerError,
+    /// Delivered to a device that isn't in use (we haven't sent available presence).
+    Inactive,
+    /// Delivered between our own devices (`category="peer"` messages).
+    PeerMsg,
+    HistorySync,
+    /// A type this library doesn't know about.
+    Other(String),
+}
+
+impl ReceiptType {
+    /// Value of the attribute; empty for [ReceiptType::Delivered].
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Delivered => "",
+            Self::Sender => "sender",
+            Self::Retry => "retry",
+            Self::Read => "read",
+            Self::ReadSelf => "read-self",
+            Self::Played => "played",
+            Self::PlayedSelf => "played-self",
+            Self::ServerError => "server-error",
+            Self::Inactive => "inactive",
+            Self::PeerMsg => "peer_msg",
+            Self::HistorySync => "hist_sync",
+            Self::Other(other) => other,
+        }
+    }
+
+    /// Parse an attribute value (empty or missing means delivered).
+    pub fn from_attr(value: &str) -> Self {
+        match value {
+            "" => Self::Delivered,
+            "sender" => Self::Sender,
+            "retry" => Self::Retry,
+            "read" => Self::Read,
+            "read-self" => Self::ReadSelf,
+            "played" => Self::Played,
+            "played-self" => Self::PlayedSelf,
+            "server-error" => Self::ServerError,
+            "inactive" => Self::Inactive,
+            "peer_msg" => Self::PeerMsg,
+            "hist_sync" => Self::HistorySync,
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn receipt_types_round_trip() {
+        for value in [
+            "",
+            "sender",
+            "retry",
+            "read",
+            "read-self",
+            "played",
+            "played-self",
+            "server-error",
+            "inactive",
+            "peer_msg",
+            "hist_sync",
+            "enc_rekey_retry",
+        ] {
+            assert_eq!(ReceiptType::from_attr(value).as_str(), value);
+        }
+        assert_eq!(ReceiptType::from_attr("read"), ReceiptType::Read);
+        assert_eq!(
+            ReceiptType::from_attr("enc_rekey_retry"),
+            ReceiptType::Other("enc_rekey_retry".into())
+        );
+    }
+}